@@ -0,0 +1,57 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Compare sequential and parallel conversion of a full internet sized routing table,
+//! run with `cargo bench --features rayon`
+
+#[cfg(windows)]
+fn main() {
+    use std::{net::IpAddr, time::Instant};
+
+    use rayon::prelude::*;
+    use winapi::shared::netioapi::MIB_IPFORWARD_ROW2;
+    use winroute::Route;
+
+    const ROWS: u32 = 800_000;
+
+    let rows: Vec<MIB_IPFORWARD_ROW2> = (0..ROWS)
+        .map(|i| {
+            let route = Route::new(IpAddr::from((i << 8).to_be_bytes()), 24)
+                .gateway("192.168.1.1".parse().unwrap())
+                .ifindex(1)
+                .metric(i % 256);
+            MIB_IPFORWARD_ROW2::from(&route)
+        })
+        .collect();
+
+    let start = Instant::now();
+    let sequential: Vec<Route> = rows.iter().map(Route::from).collect();
+    let sequential_time = start.elapsed();
+
+    let start = Instant::now();
+    let parallel: Vec<Route> = rows.par_iter().map(Route::from).collect();
+    let parallel_time = start.elapsed();
+
+    assert_eq!(sequential, parallel);
+    println!("{ROWS} rows, sequential {sequential_time:?}, parallel {parallel_time:?}");
+}
+
+#[cfg(not(windows))]
+fn main() {
+    eprintln!("routing table conversion benchmark only runs on windows");
+}
@@ -0,0 +1,138 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+const GROWTH_WINDOW: Duration = Duration::from_secs(60);
+
+/// Diagnostic event emitted when routing table crosses a configured watermark, see
+/// ```RouteManagerBuilder::size_watermark``` and ```RouteManagerBuilder::growth_watermark```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TableAlert {
+    /// Table grew beyond `limit` entries, emitted again only after it shrank back to `limit`
+    Size { entries: usize, limit: usize },
+    /// Table grew by more than `limit` entries within a minute, emitted at most once a minute
+    Growth { growth: usize, limit: usize },
+}
+
+impl Display for TableAlert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TableAlert::Size { entries, limit } => {
+                write!(f, "routing table has {entries} entries, limit {limit}")
+            }
+            TableAlert::Growth { growth, limit } => write!(
+                f,
+                "routing table grew by {growth} entries in a minute, limit {limit}"
+            ),
+        }
+    }
+}
+
+/// Watermark state, checked after every processed event
+pub(crate) struct Watermarks {
+    size: Option<usize>,
+    growth: Option<usize>,
+    above_size: bool,
+    window: Option<(Instant, usize)>,
+    growth_alerted: bool,
+}
+
+impl Watermarks {
+    pub(crate) fn new(size: Option<usize>, growth: Option<usize>) -> Self {
+        Self {
+            size,
+            growth,
+            above_size: false,
+            window: None,
+            growth_alerted: false,
+        }
+    }
+
+    pub(crate) fn check(&mut self, entries: usize) -> Vec<TableAlert> {
+        self.check_at(entries, Instant::now())
+    }
+
+    fn check_at(&mut self, entries: usize, now: Instant) -> Vec<TableAlert> {
+        let mut alerts = Vec::new();
+        if let Some(limit) = self.size {
+            let above = entries > limit;
+            if above && !self.above_size {
+                alerts.push(TableAlert::Size { entries, limit });
+            }
+            self.above_size = above;
+        }
+        if let Some(limit) = self.growth {
+            let (start, base) = match self.window {
+                Some((start, base)) if now.duration_since(start) < GROWTH_WINDOW => (start, base),
+                _ => {
+                    self.growth_alerted = false;
+                    (now, entries)
+                }
+            };
+            self.window = Some((start, base));
+            let growth = entries.saturating_sub(base);
+            if growth > limit && !self.growth_alerted {
+                self.growth_alerted = true;
+                alerts.push(TableAlert::Growth { growth, limit });
+            }
+        }
+        alerts
+    }
+}
+
+#[cfg(test)]
+pub mod test_alert {
+    use std::time::{Duration, Instant};
+
+    use super::{TableAlert, Watermarks};
+
+    #[test]
+    fn test_watermarks() {
+        let mut watermarks = Watermarks::new(Some(10), Some(3));
+        let start = Instant::now();
+        assert!(watermarks.check_at(8, start).is_empty());
+        assert!(watermarks.check_at(11, start).contains(&TableAlert::Size {
+            entries: 11,
+            limit: 10
+        }));
+        assert_eq!(
+            vec![TableAlert::Growth {
+                growth: 4,
+                limit: 3
+            }],
+            watermarks.check_at(12, start + Duration::from_secs(1))
+        );
+        assert!(watermarks
+            .check_at(9, start + Duration::from_secs(2))
+            .is_empty());
+        assert_eq!(
+            vec![TableAlert::Size {
+                entries: 11,
+                limit: 10
+            }],
+            watermarks.check_at(11, start + Duration::from_secs(3))
+        );
+        assert!(watermarks
+            .check_at(11, start + Duration::from_secs(61))
+            .is_empty());
+    }
+}
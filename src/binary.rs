@@ -0,0 +1,84 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Compact binary encoding of [`Route`] and [`RouteEvent`] based on bincode,
+//! enabled with the `binary` feature
+
+use std::io;
+
+use crate::{Route, RouteEvent};
+
+fn to_io_error(e: bincode::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+impl Route {
+    /// Encode route into bytes
+    ///
+    /// # Errors
+    /// When bincode fails to serialize the route
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        bincode::serialize(self).map_err(to_io_error)
+    }
+
+    /// Decode route from bytes produced by [`Route::to_bytes`]
+    ///
+    /// # Errors
+    /// When bytes are truncated or contain invalid route fields
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        bincode::deserialize(bytes).map_err(to_io_error)
+    }
+}
+
+impl RouteEvent {
+    /// Encode event into bytes
+    ///
+    /// # Errors
+    /// When bincode fails to serialize the event
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        bincode::serialize(self).map_err(to_io_error)
+    }
+
+    /// Decode event from bytes produced by [`RouteEvent::to_bytes`]
+    ///
+    /// # Errors
+    /// When bytes are truncated or contain invalid event fields
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        bincode::deserialize(bytes).map_err(to_io_error)
+    }
+}
+
+#[cfg(test)]
+pub mod test_binary {
+    use crate::{Route, RouteEvent};
+
+    #[test]
+    fn round_trip() {
+        let route = Route::new("192.168.0.0".parse().unwrap(), 24)
+            .gateway("172.1.1.254".parse().unwrap())
+            .ifindex(1)
+            .metric(5);
+        let bytes = route.to_bytes().unwrap();
+        assert_eq!(route, Route::from_bytes(&bytes).unwrap());
+
+        let event = RouteEvent::Delete(Route::new("fe80:9464::".parse().unwrap(), 32));
+        let bytes = event.to_bytes().unwrap();
+        assert_eq!(event, RouteEvent::from_bytes(&bytes).unwrap());
+        assert!(RouteEvent::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
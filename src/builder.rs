@@ -0,0 +1,214 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{io, time::Duration};
+
+use crate::{
+    history::DEFAULT_HISTORY_CAPACITY, OverflowPolicy, RouteManager, SystemRouteOperate,
+    ThreadOptions,
+};
+
+/// Configure and create a [`RouteManager`], using ```RouteManager::builder()``` to create one
+///
+/// # Examples
+///
+/// ```rust no_run
+/// use winroute::*;
+///
+/// let manager = RouteManager::builder()
+///     .cleanup_on_drop(true)
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct RouteManagerBuilder {
+    pub(crate) cleanup_on_drop: bool,
+    pub(crate) history_capacity: usize,
+    pub(crate) listen: bool,
+    pub(crate) threads: ThreadOptions,
+    pub(crate) size_watermark: Option<usize>,
+    pub(crate) growth_watermark: Option<usize>,
+    pub(crate) validate_gateway: bool,
+    pub(crate) event_buffer: (Option<usize>, OverflowPolicy),
+    pub(crate) subscriber_buffer: (Option<usize>, OverflowPolicy),
+    pub(crate) coalesce: Option<Duration>,
+    pub(crate) suppress_own_events: bool,
+    pub(crate) initial_notification: bool,
+    pub(crate) dry_run: bool,
+}
+
+impl Default for RouteManagerBuilder {
+    fn default() -> Self {
+        Self {
+            cleanup_on_drop: false,
+            history_capacity: DEFAULT_HISTORY_CAPACITY,
+            listen: true,
+            threads: ThreadOptions::default(),
+            size_watermark: None,
+            growth_watermark: None,
+            validate_gateway: false,
+            event_buffer: (None, OverflowPolicy::Block),
+            subscriber_buffer: (None, OverflowPolicy::Block),
+            coalesce: None,
+            suppress_own_events: false,
+            initial_notification: false,
+            dry_run: false,
+        }
+    }
+}
+
+impl RouteManagerBuilder {
+    /// Create a builder with default configuration
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Delete all routes added through the manager when it is dropped or shut down, default is false
+    pub fn cleanup_on_drop(mut self, cleanup: bool) -> Self {
+        self.cleanup_on_drop = cleanup;
+        self
+    }
+
+    /// How many processed events are retained for ```RouteManager::events_since```, default is 1024
+    pub fn history_capacity(mut self, capacity: usize) -> Self {
+        self.history_capacity = capacity;
+        self
+    }
+
+    /// Register route change listener and cache the routing table, default is true
+    ///
+    /// When disabled manager only performs operations: nothing is cached, ```poll``` returns
+    /// an error and queries read the table from system
+    pub fn listen(mut self, listen: bool) -> Self {
+        self.listen = listen;
+        self
+    }
+
+    /// Name, stack size and priority of background threads the manager spawns
+    pub fn thread_options(mut self, threads: ThreadOptions) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Emit a [`TableAlert`](crate::TableAlert) when routing table grows beyond `entries`,
+    /// disabled by default
+    pub fn size_watermark(mut self, entries: usize) -> Self {
+        self.size_watermark = Some(entries);
+        self
+    }
+
+    /// Emit a [`TableAlert`](crate::TableAlert) when routing table grows by more than
+    /// `entries` within a minute, disabled by default
+    pub fn growth_watermark(mut self, entries: usize) -> Self {
+        self.growth_watermark = Some(entries);
+        self
+    }
+
+    /// Refuse to add routes whose gateway is not within a subnet connected to the route's
+    /// interface, see ```RouteManager::check_gateway```, default is false
+    pub fn validate_gateway(mut self, validate: bool) -> Self {
+        self.validate_gateway = validate;
+        self
+    }
+
+    /// Buffer at most `capacity` system events not yet polled, `policy` decides what happens
+    /// when ```poll``` falls behind, default is unbounded
+    ///
+    /// Only applies to the system backend and to the ```coalesce``` buffer, custom backends
+    /// of ```build_with_backend``` own their channel. With ```OverflowPolicy::Block``` system
+    /// notifications wait for ```poll```, with a drop policy the table is read again once
+    /// ```poll``` notices lost events. A `capacity` of 0 makes building fail
+    pub fn event_buffer(mut self, capacity: usize, policy: OverflowPolicy) -> Self {
+        self.event_buffer = (Some(capacity), policy);
+        self
+    }
+
+    /// Buffer at most `capacity` events per subscription created without an explicit buffer,
+    /// e.g. by ```RouteManager::subscribe```, `policy` decides what happens when subscriber
+    /// falls behind, default is unbounded. A `capacity` of 0 makes building fail
+    pub fn subscriber_buffer(mut self, capacity: usize, policy: OverflowPolicy) -> Self {
+        self.subscriber_buffer = (Some(capacity), policy);
+        self
+    }
+
+    /// Merge bursts of system events, e.g. during DHCP renews or VPN connects: within `window`
+    /// of the first event of a burst, repeated Change events of an entry are delivered once
+    /// with its latest state, disabled by default
+    ///
+    /// Every event is delayed by up to `window`
+    pub fn coalesce(mut self, window: Duration) -> Self {
+        self.coalesce = Some(window);
+        self
+    }
+
+    /// Do not deliver events caused by changes made through the manager to subscribers,
+    /// ```RouteManager::subscribe_tagged``` still receives them, default is false
+    pub fn suppress_own_events(mut self, suppress: bool) -> Self {
+        self.suppress_own_events = suppress;
+        self
+    }
+
+    /// Ask system for an initial notification once the route change listener is registered,
+    /// delivered as ```RouteEvent::Initial``` so subscribers know listener is live and can
+    /// sync, default is false
+    ///
+    /// Only applies to the system backend
+    pub fn initial_notification(mut self, initial: bool) -> Self {
+        self.initial_notification = initial;
+        self
+    }
+
+    /// Validate route additions, deletions and updates against system interfaces and the
+    /// cached table, then record them instead of sending them to system, default is false
+    ///
+    /// Recorded changes are read with ```RouteManager::planned_changes```
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Create the RouteManager
+    ///
+    /// # Errors
+    /// ErrorKind::InvalidInput when a buffer capacity is 0, or when windows
+    /// NotifyRouteChange2 return error
+    #[cfg(windows)]
+    pub fn build(self) -> io::Result<RouteManager> {
+        let (capacity, policy) = self.event_buffer;
+        let operator = crate::windows::WindowsOperator::with_buffer(capacity, policy)
+            .initial_notification(self.initial_notification);
+        self.build_with_backend(operator)
+    }
+
+    #[cfg(not(windows))]
+    pub fn build(self) -> io::Result<RouteManager> {
+        Err(io::Error::other("None windows system not supported"))
+    }
+
+    /// Create the RouteManager on top of a custom backend
+    ///
+    /// # Errors
+    /// ErrorKind::InvalidInput when a buffer capacity is 0, or when backend fails to
+    /// initialize or to read the routing table
+    pub fn build_with_backend<O: SystemRouteOperate>(
+        self,
+        backend: O,
+    ) -> io::Result<RouteManager<O>> {
+        RouteManager::from_operator(backend, self)
+    }
+}
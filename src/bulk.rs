@@ -0,0 +1,352 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    collections::HashMap,
+    io,
+    net::IpAddr,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::{prefix::verify_gateway, Prefix, Route, RouteEvent, RouteManager, SystemRouteOperate};
+
+/// How ```RouteManager::add_routes_bulk``` loads routes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BulkOptions {
+    threads: usize,
+    chunk_size: usize,
+}
+
+impl Default for BulkOptions {
+    fn default() -> Self {
+        Self {
+            threads: 1,
+            chunk_size: 1000,
+        }
+    }
+}
+
+impl BulkOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of threads installing routes in parallel, default is 1
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Number of routes handed to backend at once, progress is reported after every chunk,
+    /// default is 1000
+    pub fn chunk_size(mut self, size: usize) -> Self {
+        self.chunk_size = size.max(1);
+        self
+    }
+}
+
+/// Progress of a bulk load, reported after every chunk and returned when it finishes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BulkProgress {
+    pub total: usize,
+    /// Routes installed so far
+    pub installed: usize,
+    /// Routes that failed so far
+    pub failed: usize,
+    pub elapsed: Duration,
+}
+
+impl BulkProgress {
+    /// Routes processed per second so far
+    pub fn throughput(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs == 0.0 {
+            return 0.0;
+        }
+        (self.installed + self.failed) as f64 / secs
+    }
+}
+
+/// Outcome of a batch operation without rollback, results are aligned with the input
+#[derive(Debug)]
+pub struct BatchResult {
+    pub results: Vec<io::Result<()>>,
+}
+
+/// Counts of a [`BatchResult`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchSummary {
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+impl BatchResult {
+    pub fn summary(&self) -> BatchSummary {
+        let failed = self.results.iter().filter(|r| r.is_err()).count();
+        BatchSummary {
+            total: self.results.len(),
+            succeeded: self.results.len() - failed,
+            failed,
+        }
+    }
+
+    /// Whether every item succeeded
+    pub fn is_ok(&self) -> bool {
+        self.results.iter().all(|r| r.is_ok())
+    }
+
+    /// Items of input whose operation failed, to retry only them
+    pub fn failed<'a, T>(&self, input: &'a [T]) -> Vec<&'a T> {
+        input
+            .iter()
+            .zip(&self.results)
+            .filter(|(_, r)| r.is_err())
+            .map(|(item, _)| item)
+            .collect()
+    }
+}
+
+impl IntoIterator for BatchResult {
+    type Item = io::Result<()>;
+    type IntoIter = std::vec::IntoIter<io::Result<()>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.results.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a BatchResult {
+    type Item = &'a io::Result<()>;
+    type IntoIter = std::slice::Iter<'a, io::Result<()>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.results.iter()
+    }
+}
+
+impl From<BatchResult> for Vec<io::Result<()>> {
+    fn from(result: BatchResult) -> Self {
+        result.results
+    }
+}
+
+impl<O: SystemRouteOperate> RouteManager<O> {
+    /// Add routes one by one without rollback, a failure does not stop the batch
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// let routes = vec![Route::new("10.0.0.0".parse().unwrap(), 8).ifindex(12)];
+    /// let result = manager.add_routes(&routes);
+    /// if !result.is_ok() {
+    ///     let retry: Vec<Route> = result.failed(&routes).into_iter().cloned().collect();
+    ///     manager.add_routes(&retry);
+    /// }
+    /// ```
+    pub fn add_routes(&self, routes: &[Route]) -> BatchResult {
+        BatchResult {
+            results: routes
+                .iter()
+                .map(|route| self.install(route).map(|_| ()))
+                .collect(),
+        }
+    }
+
+    /// Delete routes one by one without rollback, a failure does not stop the batch
+    pub fn delete_routes(&self, routes: &[Route]) -> BatchResult {
+        BatchResult {
+            results: routes.iter().map(|route| self.remove(route)).collect(),
+        }
+    }
+
+    /// Install many routes quickly, e.g. a block list of tens of thousands of prefixes
+    ///
+    /// Interface of routes without ifindex is resolved once per gateway, routes are handed to
+    /// backend in chunks, optionally on several threads, and `progress` is called on the
+    /// calling thread after every chunk. A failing route does not stop the load, it is
+    /// counted in ```BulkProgress::failed```. Installed routes are owned by manager
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// let routes: Vec<Route> = (0..50_000u32)
+    ///     .map(|i| Route::new(std::net::Ipv4Addr::from(i << 8).into(), 24).ifindex(12))
+    ///     .collect();
+    /// let report = manager
+    ///     .add_routes_bulk(&routes, &BulkOptions::new().threads(4), |p| {
+    ///         println!("{}/{} at {:.0} routes/s", p.installed, p.total, p.throughput())
+    ///     })
+    ///     .unwrap();
+    /// ```
+    ///
+    /// # Errors
+    /// When a worker thread can not be spawned or Mutex lock fails
+    pub fn add_routes_bulk<F>(
+        &self,
+        routes: &[Route],
+        options: &BulkOptions,
+        mut progress: F,
+    ) -> io::Result<BulkProgress>
+    where
+        F: FnMut(&BulkProgress),
+    {
+        let start = Instant::now();
+        let mut report = BulkProgress {
+            total: routes.len(),
+            installed: 0,
+            failed: 0,
+            elapsed: Duration::ZERO,
+        };
+
+        let mut interfaces: HashMap<IpAddr, Option<u32>> = HashMap::new();
+        let mut resolved = Vec::with_capacity(routes.len());
+        for route in routes {
+            if route.ifindex.is_some() || route.luid.is_some() {
+                resolved.push(route.clone());
+                continue;
+            }
+            let ifindex = *interfaces
+                .entry(route.gateway)
+                .or_insert_with(|| self.backend().best_interface(route.gateway).ok());
+            match ifindex {
+                Some(ifindex) => resolved.push(route.clone().ifindex(ifindex)),
+                None => report.failed += 1,
+            }
+        }
+        if self.validates_gateway() {
+            let mut subnets: HashMap<u32, Vec<Prefix>> = HashMap::new();
+            resolved.retain(|route| {
+                let Some(ifindex) = route.ifindex else {
+                    return true;
+                };
+                let subnets = subnets.entry(ifindex).or_insert_with(|| {
+                    self.backend()
+                        .interface_prefixes(ifindex)
+                        .unwrap_or_default()
+                });
+                let valid = verify_gateway(route.gateway, ifindex, subnets).is_ok();
+                if !valid {
+                    report.failed += 1;
+                }
+                valid
+            });
+        }
+
+        let chunks: Vec<&[Route]> = resolved.chunks(options.chunk_size).collect();
+        let next = AtomicUsize::new(0);
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        std::thread::scope(|scope| -> io::Result<()> {
+            for _ in 0..options.threads.min(chunks.len()) {
+                let sender = sender.clone();
+                let (chunks, next) = (&chunks, &next);
+                self.thread_options()
+                    .spawn_scoped(scope, "bulk", move || loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(chunk) = chunks.get(index) else {
+                            break;
+                        };
+                        if sender.send(self.install_chunk(chunk)).is_err() {
+                            break;
+                        }
+                    })?;
+            }
+            drop(sender);
+
+            for results in receiver {
+                let mut installed = Vec::with_capacity(results.len());
+                for result in results {
+                    match result {
+                        Ok(route) => installed.push(route),
+                        Err(_) => report.failed += 1,
+                    }
+                }
+                report.installed += installed.len();
+                report.elapsed = start.elapsed();
+                if !self.is_dry_run() {
+                    for route in &installed {
+                        self.note_own(RouteEvent::Add(route.clone()));
+                    }
+                    self.lock_owned()?.extend(installed);
+                }
+                progress(&report);
+            }
+            Ok(())
+        })?;
+        report.elapsed = start.elapsed();
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+pub mod test_bulk {
+    use super::{BatchSummary, BulkOptions};
+    use crate::{mock::MockOperator, Route, RouteManager};
+
+    #[test]
+    fn test_bulk_load() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        let mut routes: Vec<Route> = (0..100u32)
+            .map(|i| Route::new(std::net::Ipv4Addr::from((10 << 24) | (i << 8)).into(), 24))
+            .collect();
+        routes.push(routes[0].clone().ifindex(1));
+
+        let mut reports = Vec::new();
+        let report = manager
+            .add_routes_bulk(
+                &routes,
+                &BulkOptions::new().threads(3).chunk_size(10),
+                |p| reports.push(*p),
+            )
+            .unwrap();
+        assert_eq!(11, reports.len());
+        assert_eq!(101, report.total);
+        assert_eq!(100, report.installed);
+        assert_eq!(1, report.failed);
+        assert_eq!(100, manager.backend().table.lock().unwrap().len());
+    }
+
+    #[test]
+    fn test_batch_results() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        let a = Route::new("10.0.0.0".parse().unwrap(), 8);
+        let b = Route::new("10.1.0.0".parse().unwrap(), 16);
+        let result = manager.add_routes(&[a.clone(), a.clone(), b.clone()]);
+        assert_eq!(
+            BatchSummary {
+                total: 3,
+                succeeded: 2,
+                failed: 1
+            },
+            result.summary()
+        );
+        assert!(result.results[1].is_err());
+
+        let input = [b.clone(), Route::new("10.2.0.0".parse().unwrap(), 16)];
+        let result = manager.delete_routes(&input);
+        assert_eq!(vec![&input[1]], result.failed(&input));
+
+        let outcomes: Vec<std::io::Result<()>> = manager.delete_routes(&[a, b]).into();
+        assert_eq!(2, outcomes.len());
+        assert!(outcomes[0].is_ok());
+        assert!(outcomes[1].is_err());
+    }
+}
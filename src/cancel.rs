@@ -0,0 +1,137 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use crossbeam_channel::{Receiver, Sender};
+
+/// Cancellation flag shared between a blocking wait and the code that may abort it, e.g. a
+/// shutdown path. Clones share the same flag
+///
+/// ```rust no_run
+/// use std::time::Duration;
+/// use winroute::*;
+///
+/// let manager = RouteManager::new().unwrap();
+/// let token = CancelToken::new();
+/// let shutdown = token.clone();
+/// std::thread::spawn(move || shutdown.cancel());
+/// // returns ErrorKind::Interrupted as soon as token is cancelled
+/// let _ = manager.wait_stable_addresses(None, Duration::from_secs(30), &token);
+/// ```
+#[derive(Debug, Clone)]
+pub struct CancelToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    cancelled: AtomicBool,
+    // dropped on cancel, disconnecting receiver wakes every waiter at once
+    sender: Mutex<Option<Sender<()>>>,
+    receiver: Receiver<()>,
+}
+
+impl Default for CancelToken {
+    fn default() -> Self {
+        let (sender, receiver) = crossbeam_channel::bounded(0);
+        Self {
+            inner: Arc::new(Inner {
+                cancelled: AtomicBool::new(false),
+                sender: Mutex::new(Some(sender)),
+                receiver,
+            }),
+        }
+    }
+}
+
+impl CancelToken {
+    /// Create a token that is not cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Abort waits using this token, pending and future ones
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        if let Ok(mut sender) = self.inner.sender.lock() {
+            sender.take();
+        }
+    }
+
+    /// Whether ```cancel``` was called on this token or one of its clones
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Receiver that becomes ready, disconnected, once token is cancelled, for use in
+    /// `crossbeam_channel::select!`
+    pub(crate) fn receiver(&self) -> &Receiver<()> {
+        &self.inner.receiver
+    }
+
+    /// Block until token is cancelled or timeout elapses, return whether it was cancelled
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let _ = self.receiver().recv_timeout(timeout);
+        self.is_cancelled()
+    }
+
+    /// Fail when token is cancelled, for waits polling the token between steps
+    ///
+    /// # Errors
+    /// ErrorKind::Interrupted when token is cancelled
+    pub fn check(&self) -> io::Result<()> {
+        match self.is_cancelled() {
+            true => Err(io::Error::new(io::ErrorKind::Interrupted, "wait cancelled")),
+            false => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test_cancel {
+    use std::time::{Duration, Instant};
+
+    use super::CancelToken;
+
+    #[test]
+    fn test_cancel() {
+        let token = CancelToken::new();
+        assert!(!token.wait(Duration::from_millis(1)));
+
+        let shutdown = token.clone();
+        let start = Instant::now();
+        let thread = std::thread::spawn(move || shutdown.wait(Duration::from_secs(30)));
+        token.cancel();
+        assert!(thread.join().unwrap());
+        assert!(start.elapsed() < Duration::from_secs(30));
+        assert!(token.is_cancelled());
+        assert!(token.wait(Duration::from_secs(30)));
+        assert_eq!(
+            std::io::ErrorKind::Interrupted,
+            token.check().unwrap_err().kind()
+        );
+    }
+}
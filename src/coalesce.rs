@@ -0,0 +1,145 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    io,
+    sync::{atomic::AtomicUsize, Arc},
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{Receiver, RecvTimeoutError};
+
+use crate::{
+    subscription::{Delivery, PolicySender},
+    OverflowPolicy, RouteEvent, ThreadOptions, TimedEvent,
+};
+
+/// Forward backend events through a thread merging bursts: within `window` of the first
+/// event of a burst, a Change of an entry folds into the pending Add or Change of that entry
+///
+/// Merged events are buffered like backend ones, at most `capacity` of them with `policy`
+/// applied on overflow, returned counter tells how many were discarded. Thread ends once
+/// backend or the returned receiver is dropped
+pub(crate) fn coalesce(
+    events: Receiver<TimedEvent>,
+    window: Duration,
+    (capacity, policy): (Option<usize>, OverflowPolicy),
+    threads: &ThreadOptions,
+) -> io::Result<(Receiver<TimedEvent>, Arc<AtomicUsize>)> {
+    let (sender, receiver) = PolicySender::channel(capacity, policy);
+    let lost = sender.lost();
+    threads.spawn("coalesce", move || {
+        while let Ok(first) = events.recv() {
+            let deadline = Instant::now() + window;
+            let mut pending = vec![first];
+            loop {
+                match events.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(timed) => merge(&mut pending, timed),
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => {
+                        for timed in pending {
+                            let _ = sender.send(timed);
+                        }
+                        return;
+                    }
+                }
+            }
+            for timed in pending {
+                if let Delivery::Closed = sender.send(timed) {
+                    return;
+                }
+            }
+        }
+    })?;
+    Ok((receiver, lost))
+}
+
+/// Fold a Change into the pending Add or Change of the same entry, or queue event
+fn merge(pending: &mut Vec<TimedEvent>, timed: TimedEvent) {
+    if let RouteEvent::Change(route) = &timed.event {
+        let same = pending.iter_mut().rev().find(|p| match &p.event {
+            RouteEvent::Add(r) | RouteEvent::Change(r) => r.is_same_entry(route),
+            RouteEvent::Delete(r) => r.is_same_entry(route),
+            RouteEvent::Initial => false,
+        });
+        if let Some(TimedEvent {
+            event: RouteEvent::Add(r) | RouteEvent::Change(r),
+            timestamp,
+        }) = same
+        {
+            *r = route.clone();
+            *timestamp = timed.timestamp;
+            return;
+        }
+    }
+    pending.push(timed);
+}
+
+#[cfg(test)]
+pub mod test_coalesce {
+    use std::time::Duration;
+
+    use super::{coalesce, merge};
+    use crate::{OverflowPolicy, Route, RouteEvent, ThreadOptions, TimedEvent};
+
+    fn route(metric: u32) -> Route {
+        Route::new("10.0.0.0".parse().unwrap(), 8).metric(metric)
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut pending = vec![TimedEvent::now(RouteEvent::Add(route(1)))];
+        merge(&mut pending, TimedEvent::now(RouteEvent::Change(route(2))));
+        merge(&mut pending, TimedEvent::now(RouteEvent::Delete(route(2))));
+        merge(&mut pending, TimedEvent::now(RouteEvent::Change(route(3))));
+        merge(&mut pending, TimedEvent::now(RouteEvent::Change(route(4))));
+        let events: Vec<RouteEvent> = pending.into_iter().map(|t| t.event).collect();
+        assert_eq!(
+            vec![
+                RouteEvent::Add(route(2)),
+                RouteEvent::Delete(route(2)),
+                RouteEvent::Change(route(4)),
+            ],
+            events
+        );
+    }
+
+    #[test]
+    fn test_coalesce_buffer() {
+        let (sender, events) = crossbeam_channel::unbounded();
+        let (merged, lost) = coalesce(
+            events,
+            Duration::from_millis(10),
+            (Some(1), OverflowPolicy::DropOldest),
+            &ThreadOptions::new(),
+        )
+        .unwrap();
+        for prefix in [8, 16, 24] {
+            let route = Route::new("10.0.0.0".parse().unwrap(), prefix);
+            sender
+                .send(TimedEvent::now(RouteEvent::Add(route)))
+                .unwrap();
+        }
+        drop(sender);
+        std::thread::sleep(Duration::from_millis(100));
+        let events: Vec<RouteEvent> = merged.iter().map(|t| t.event).collect();
+        assert_eq!(1, events.len());
+        assert_eq!(24, events[0].route().unwrap().prefix);
+        assert_eq!(2, lost.load(std::sync::atomic::Ordering::SeqCst));
+    }
+}
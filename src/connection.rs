@@ -0,0 +1,106 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fmt::Display;
+
+/// Kind of a dial-up, RAS or VPN adapter
+#[cfg_attr(feature = "serializable", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AdapterKind {
+    /// PPP link, used by dial-up and the built-in RAS VPN clients (PPTP, L2TP, SSTP, IKEv2)
+    Ppp,
+    /// Tunnel interface
+    Tunnel,
+    /// Proprietary virtual adapter, used by most third party VPN clients
+    Virtual,
+}
+
+impl Display for AdapterKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AdapterKind::Ppp => write!(f, "ppp"),
+            AdapterKind::Tunnel => write!(f, "tunnel"),
+            AdapterKind::Virtual => write!(f, "virtual"),
+        }
+    }
+}
+
+/// Dial-up, RAS or VPN adapter
+#[cfg_attr(feature = "serializable", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Adapter {
+    pub ifindex: u32,
+    pub luid: u64,
+    pub alias: String,
+    pub kind: AdapterKind,
+}
+
+/// Connection state change of a dial-up, RAS or VPN adapter
+#[cfg_attr(feature = "serializable", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    /// Adapter arrived or its link came up
+    Connected(Adapter),
+    /// Adapter was removed or its link went down
+    Disconnected(Adapter),
+}
+
+impl ConnectionEvent {
+    pub fn adapter(&self) -> &Adapter {
+        match self {
+            ConnectionEvent::Connected(adapter) | ConnectionEvent::Disconnected(adapter) => adapter,
+        }
+    }
+}
+
+impl Display for ConnectionEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (state, adapter) = match self {
+            ConnectionEvent::Connected(adapter) => ("CONNECTED", adapter),
+            ConnectionEvent::Disconnected(adapter) => ("DISCONNECTED", adapter),
+        };
+        write!(
+            f,
+            "{state} {} {} (if {})",
+            adapter.kind, adapter.alias, adapter.ifindex
+        )
+    }
+}
+
+#[cfg(test)]
+pub mod test_connection {
+    use super::{Adapter, AdapterKind, ConnectionEvent};
+
+    #[test]
+    fn test_display() {
+        let adapter = Adapter {
+            ifindex: 23,
+            luid: 1,
+            alias: "Office VPN".to_string(),
+            kind: AdapterKind::Ppp,
+        };
+        assert_eq!(
+            "CONNECTED ppp Office VPN (if 23)",
+            ConnectionEvent::Connected(adapter.clone()).to_string()
+        );
+        assert_eq!(
+            &adapter,
+            ConnectionEvent::Disconnected(adapter.clone()).adapter()
+        );
+    }
+}
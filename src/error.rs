@@ -0,0 +1,69 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{error::Error, fmt::Display, sync::PoisonError};
+
+use crossbeam_channel::RecvError;
+
+use crate::TimedEvent;
+
+/// Error returned by ```RouteManager::poll``` and its variants
+#[derive(Debug)]
+pub enum PollError {
+    /// Backend listener is gone, was disabled with ```RouteManagerBuilder::listen``` or
+    /// manager was shut down with ```RouteManager::shutdown```, no event will ever arrive
+    /// again: stop polling
+    Disconnected,
+    /// Event was processed, but `dropped` subscriptions created with a drop overflow policy
+    /// were full and lost an event: keep polling, the event is handed back here and lagging
+    /// subscribers can catch up with ```RouteManager::events_since```
+    Lagged {
+        event: Box<TimedEvent>,
+        dropped: usize,
+    },
+    /// Manager state is unusable, usually because a thread panicked while holding one of its
+    /// locks: the event was not fully processed and polling again is likely to fail the same
+    /// way, rebuild the manager
+    Internal(String),
+}
+
+impl Display for PollError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PollError::Disconnected => write!(f, "route change listener is disconnected"),
+            PollError::Lagged { dropped, .. } => {
+                write!(f, "{dropped} subscriptions lagged and lost an event")
+            }
+            PollError::Internal(msg) => write!(f, "internal error: {msg}"),
+        }
+    }
+}
+
+impl Error for PollError {}
+
+impl From<RecvError> for PollError {
+    fn from(_: RecvError) -> Self {
+        PollError::Disconnected
+    }
+}
+
+impl<T> From<PoisonError<T>> for PollError {
+    fn from(e: PoisonError<T>) -> Self {
+        PollError::Internal(e.to_string())
+    }
+}
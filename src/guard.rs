@@ -0,0 +1,380 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io;
+
+use crate::{operator::DefaultOperator, Route, RouteManager, SystemRouteOperate};
+
+/// Collection of routes applied together with ```RouteSet::apply```
+///
+/// ```rust no_run
+/// use winroute::*;
+///
+/// let manager = RouteManager::new().unwrap();
+/// let gateway = "10.8.0.1".parse().unwrap();
+/// let tunnel: RouteSet = ["0.0.0.0", "128.0.0.0"]
+///     .iter()
+///     .map(|dst| Route::new(dst.parse().unwrap(), 1).gateway(gateway))
+///     .collect();
+/// let applied = tunnel.apply(&manager).unwrap();
+/// // traffic goes through tunnel until previous state is restored
+/// applied.revert().unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RouteSet {
+    routes: Vec<Route>,
+}
+
+impl RouteSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a route to the set
+    pub fn route(mut self, route: Route) -> Self {
+        self.routes.push(route);
+        self
+    }
+
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+
+    pub fn len(&self) -> usize {
+        self.routes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    /// Install all routes of the set with ```RouteManager::apply_route_set```, previous state is
+    /// restored when the returned guard is dropped or reverted
+    ///
+    /// # Errors
+    /// When a route can not be read, deleted or added, nothing is left applied
+    pub fn apply<'a, O: SystemRouteOperate>(
+        &self,
+        manager: &'a RouteManager<O>,
+    ) -> io::Result<RouteSetGuard<'a, O>> {
+        manager.apply_route_set(&self.routes)
+    }
+}
+
+impl FromIterator<Route> for RouteSet {
+    fn from_iter<T: IntoIterator<Item = Route>>(iter: T) -> Self {
+        Self {
+            routes: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl From<Vec<Route>> for RouteSet {
+    fn from(routes: Vec<Route>) -> Self {
+        Self { routes }
+    }
+}
+
+/// Set of routes installed by ```RouteManager::apply_route_set```, reverted when dropped
+///
+/// Reverting deletes the installed routes and puts back the entries they displaced
+pub struct RouteSetGuard<'a, O: SystemRouteOperate = DefaultOperator> {
+    manager: &'a RouteManager<O>,
+    installed: Vec<Route>,
+    displaced: Vec<Route>,
+    active: bool,
+}
+
+impl<O: SystemRouteOperate> RouteSetGuard<'_, O> {
+    /// Routes as installed by system
+    pub fn installed(&self) -> &[Route] {
+        &self.installed
+    }
+
+    /// Entries that were in system for the same destination, prefix and interface and were
+    /// replaced by the set, restored on revert
+    pub fn displaced(&self) -> &[Route] {
+        &self.displaced
+    }
+
+    /// Delete installed routes and restore displaced ones
+    ///
+    /// # Errors
+    /// The first error met, remaining routes are still deleted or restored
+    pub fn revert(mut self) -> io::Result<()> {
+        self.undo()
+    }
+
+    /// Leave the set in place and stop guarding it
+    pub fn keep(mut self) -> Vec<Route> {
+        self.active = false;
+        std::mem::take(&mut self.installed)
+    }
+
+    fn undo(&mut self) -> io::Result<()> {
+        if !std::mem::replace(&mut self.active, false) {
+            return Ok(());
+        }
+        let mut result = Ok(());
+        for route in self.installed.iter().rev() {
+            if let Err(e) = self.manager.remove(route) {
+                result = result.and(Err(e));
+            }
+        }
+        // displaced routes did not belong to manager, restore them without taking ownership
+        for route in self.displaced.iter().rev() {
+            if let Err(e) = self.manager.restore(route) {
+                result = result.and(Err(e));
+            }
+        }
+        result
+    }
+}
+
+impl<O: SystemRouteOperate> Drop for RouteSetGuard<'_, O> {
+    fn drop(&mut self) {
+        let _ = self.undo();
+    }
+}
+
+/// Route installed by ```RouteManager::add_route_scoped```, deleted when dropped
+///
+/// Deleting on drop is best effort, errors are ignored
+pub struct RouteGuard<'a, O: SystemRouteOperate = DefaultOperator> {
+    manager: &'a RouteManager<O>,
+    route: Option<Route>,
+}
+
+impl<O: SystemRouteOperate> RouteGuard<'_, O> {
+    /// Route as installed by system
+    pub fn route(&self) -> &Route {
+        self.route
+            .as_ref()
+            .expect("route is only taken when guard is consumed")
+    }
+
+    /// Delete the route now and report the outcome
+    ///
+    /// # Errors
+    /// when system api return error
+    pub fn revert(mut self) -> io::Result<()> {
+        match self.route.take() {
+            Some(route) => self.manager.remove(&route),
+            None => Ok(()),
+        }
+    }
+
+    /// Leave the route in place and stop guarding it
+    pub fn keep(mut self) -> Route {
+        self.route
+            .take()
+            .expect("route is only taken when guard is consumed")
+    }
+}
+
+impl<O: SystemRouteOperate> Drop for RouteGuard<'_, O> {
+    fn drop(&mut self) {
+        if let Some(route) = self.route.take() {
+            let _ = self.manager.remove(&route);
+        }
+    }
+}
+
+impl<O: SystemRouteOperate> RouteManager<O> {
+    /// Add a route that lives as long as the returned guard, it is deleted when the guard is
+    /// dropped, also on early return or panic
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// let route = manager
+    ///     .add_route_scoped(&Route::new("10.8.0.0".parse().unwrap(), 16).ifindex(12))
+    ///     .unwrap();
+    /// // route is in place while connected
+    /// drop(route);
+    /// ```
+    ///
+    /// # Errors
+    /// when system api return error
+    pub fn add_route_scoped(&self, route: &Route) -> io::Result<RouteGuard<'_, O>> {
+        Ok(RouteGuard {
+            manager: self,
+            route: Some(self.install(route)?),
+        })
+    }
+
+    /// Install all routes, replacing entries with the same destination, prefix and interface
+    /// whatever their gateway, and return a guard that reverts the set when dropped
+    ///
+    /// Routes without ifindex or luid go to the best interface for their gateway, entries
+    /// on that interface are the ones replaced
+    ///
+    /// Either the whole set is applied or nothing: changes are reverted when one route fails
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// let portal = manager
+    ///     .apply_route_set(&[Route::new("0.0.0.0".parse().unwrap(), 0)
+    ///         .gateway("192.168.1.1".parse().unwrap())
+    ///         .metric(1)])
+    ///     .unwrap();
+    /// // captive portal mode until portal is dropped
+    /// drop(portal);
+    /// ```
+    ///
+    /// # Errors
+    /// When a route can not be read, deleted or added
+    pub fn apply_route_set(&self, routes: &[Route]) -> io::Result<RouteSetGuard<'_, O>> {
+        let mut guard = RouteSetGuard {
+            manager: self,
+            installed: Vec::with_capacity(routes.len()),
+            displaced: Vec::new(),
+            active: true,
+        };
+        // read once, so routes of the set never displace each other
+        let table = self.backend().read_all_routes()?;
+        for route in routes {
+            let route = match (route.ifindex, route.luid) {
+                (None, None) => route.clone().ifindex(self.route_ifindex(route)?),
+                _ => route.clone(),
+            };
+            let same_interface = |r: &Route| match route.ifindex {
+                Some(ifindex) => r.ifindex == Some(ifindex),
+                None => r.luid == route.luid,
+            };
+            for existing in table.iter().filter(|r| {
+                r.destination == route.destination && r.prefix == route.prefix && same_interface(r)
+            }) {
+                self.remove(existing)?;
+                guard.displaced.push(existing.clone());
+            }
+            guard.installed.push(self.install(&route)?);
+        }
+        Ok(guard)
+    }
+}
+
+#[cfg(test)]
+pub mod test_guard {
+    use super::RouteSet;
+    use crate::{
+        mock::{route, MockOperator},
+        Route, RouteManager, SystemRouteOperate,
+    };
+
+    #[test]
+    fn test_route_set_guard() {
+        let original = route("0.0.0.0", 0, 25);
+        let manager =
+            RouteManager::with_backend(MockOperator::new(vec![original.clone()])).unwrap();
+        let table = || manager.backend().read_all_routes().unwrap();
+
+        let guard = manager
+            .apply_route_set(&[route("0.0.0.0", 0, 1), route("10.0.0.0", 8, 1)])
+            .unwrap();
+        assert_eq!(vec![original.clone()], guard.displaced());
+        assert_eq!(2, guard.installed().len());
+        assert!(table().iter().all(|r| r.metric == Some(1)));
+
+        drop(guard);
+        assert_eq!(vec![original.clone()], table());
+
+        let kept = manager
+            .apply_route_set(&[route("10.0.0.0", 8, 1)])
+            .unwrap()
+            .keep();
+        assert_eq!(2, table().len());
+        manager.apply_route_set(&kept).unwrap().revert().unwrap();
+        assert_eq!(vec![original.clone(), kept[0].clone()], table());
+    }
+
+    #[test]
+    fn test_route_guard() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        let table = || manager.backend().read_all_routes().unwrap();
+
+        let guard = manager.add_route_scoped(&route("10.0.0.0", 8, 1)).unwrap();
+        assert_eq!(vec![guard.route().clone()], table());
+        drop(guard);
+        assert!(table().is_empty());
+
+        let kept = manager
+            .add_route_scoped(&route("10.0.0.0", 8, 1))
+            .unwrap()
+            .keep();
+        assert_eq!(vec![kept.clone()], table());
+        manager.delete_route(&kept).unwrap();
+
+        let guard = manager.add_route_scoped(&route("10.0.0.0", 8, 1)).unwrap();
+        manager.delete_route(guard.route()).unwrap();
+        assert!(guard.revert().is_err());
+    }
+
+    #[test]
+    fn test_route_set() {
+        let original = route("0.0.0.0", 0, 25);
+        let manager =
+            RouteManager::with_backend(MockOperator::new(vec![original.clone()])).unwrap();
+        let table = || manager.backend().read_all_routes().unwrap();
+
+        let set = RouteSet::new()
+            .route(route("0.0.0.0", 0, 1))
+            .route(route("10.0.0.0", 8, 1));
+        assert_eq!(2, set.len());
+        let applied = set.apply(&manager).unwrap();
+        assert_eq!(2, table().len());
+        assert!(table().iter().all(|r| r.metric == Some(1)));
+        applied.revert().unwrap();
+        assert_eq!(vec![original], table());
+    }
+
+    #[test]
+    fn test_route_set_unresolved_interface() {
+        // default route via the real gateway, portal route names no interface
+        let original = route("0.0.0.0", 0, 25).gateway("192.168.1.254".parse().unwrap());
+        let manager =
+            RouteManager::with_backend(MockOperator::new(vec![original.clone()])).unwrap();
+        let table = || manager.backend().read_all_routes().unwrap();
+
+        let portal = Route::new("0.0.0.0".parse().unwrap(), 0)
+            .gateway("192.168.1.1".parse().unwrap())
+            .metric(1);
+        let guard = manager.apply_route_set(&[portal]).unwrap();
+        assert_eq!(vec![original.clone()], guard.displaced());
+        assert_eq!(Some(1), guard.installed()[0].ifindex);
+        assert_eq!(guard.installed(), table());
+        drop(guard);
+        assert_eq!(vec![original], table());
+    }
+
+    #[test]
+    fn test_route_set_failure() {
+        let manager = RouteManager::builder()
+            .validate_gateway(true)
+            .build_with_backend(MockOperator::new(vec![]))
+            .unwrap();
+        let bad = route("10.1.0.0", 16, 1).gateway("172.16.0.1".parse().unwrap());
+        assert!(manager
+            .apply_route_set(&[route("10.0.0.0", 8, 1), bad])
+            .is_err());
+        assert!(manager.backend().read_all_routes().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,147 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    io,
+    sync::Arc,
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+
+use crate::{Route, RouteManager, SystemRouteOperate};
+
+/// Gateway reachability change reported by ```GatewayMonitor```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GatewayEvent {
+    /// Gateway of route stopped answering address resolution
+    Down(Route),
+    /// Gateway of route answers again after being reported down
+    Recovered(Route),
+}
+
+/// Background prober created by ```RouteManager::monitor_gateways```, stops when dropped
+pub struct GatewayMonitor {
+    events: Receiver<GatewayEvent>,
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl GatewayMonitor {
+    /// Events emitted every time a gateway goes down or recovers
+    pub fn events(&self) -> &Receiver<GatewayEvent> {
+        &self.events
+    }
+}
+
+impl Drop for GatewayMonitor {
+    fn drop(&mut self) {
+        // disconnecting wakes the prober thread immediately
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl<O: SystemRouteOperate + 'static> RouteManager<O> {
+    /// Probe gateways of routes every interval with ```RouteManager::probe_next_hop```, which
+    /// sends ARP or neighbor solicitation unless the neighbor table entry was confirmed
+    /// reachable (stale, probing and unreachable entries are not trusted), and emit
+    /// ```GatewayEvent::Down``` when one stops answering and ```GatewayEvent::Recovered```
+    /// when it answers again
+    ///
+    /// Every gateway is assumed reachable at start, a failed probe (including backend errors)
+    /// counts as unreachable. Routes without gateway are never probed
+    ///
+    /// # Errors
+    /// When the prober thread can not be spawned
+    pub fn monitor_gateways(
+        self: &Arc<Self>,
+        routes: Vec<Route>,
+        interval: Duration,
+    ) -> io::Result<GatewayMonitor> {
+        let (sender, events) = crossbeam_channel::unbounded();
+        let (stop, stopped) = crossbeam_channel::bounded::<()>(0);
+        let manager = self.clone();
+        let mut gateways: Vec<(Route, bool)> = routes
+            .into_iter()
+            .filter(|r| !r.gateway.is_unspecified())
+            .map(|r| (r, true))
+            .collect();
+        let thread = self.thread_options().spawn("gateway", move || loop {
+            let started = Instant::now();
+            for (route, up) in gateways.iter_mut() {
+                let reachable = matches!(manager.probe_next_hop(route), Ok(Some(_)));
+                if reachable == *up {
+                    continue;
+                }
+                *up = reachable;
+                let event = match reachable {
+                    true => GatewayEvent::Recovered(route.clone()),
+                    false => GatewayEvent::Down(route.clone()),
+                };
+                if sender.send(event).is_err() {
+                    return;
+                }
+            }
+            let wait = interval.saturating_sub(started.elapsed());
+            if stopped.recv_timeout(wait) != Err(RecvTimeoutError::Timeout) {
+                return;
+            }
+        })?;
+        Ok(GatewayMonitor {
+            events,
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod test_health {
+    use std::{sync::Arc, time::Duration};
+
+    use super::GatewayEvent;
+    use crate::{mock::MockOperator, Route, RouteManager};
+
+    #[test]
+    fn test_monitor_gateways() {
+        let manager = Arc::new(RouteManager::with_backend(MockOperator::new(vec![])).unwrap());
+        let up = Route::new("10.0.0.0".parse().unwrap(), 8)
+            .gateway("192.168.1.1".parse().unwrap())
+            .ifindex(1);
+        let down = Route::new("10.1.0.0".parse().unwrap(), 16)
+            .gateway("192.168.1.2".parse().unwrap())
+            .ifindex(1);
+        let on_link = Route::new("10.2.0.0".parse().unwrap(), 16).ifindex(1);
+        let monitor = manager
+            .monitor_gateways(vec![up, down.clone(), on_link], Duration::from_millis(10))
+            .unwrap();
+        let event = monitor
+            .events()
+            .recv_timeout(Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(GatewayEvent::Down(down), event);
+        assert!(monitor
+            .events()
+            .recv_timeout(Duration::from_millis(50))
+            .is_err());
+    }
+}
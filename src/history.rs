@@ -0,0 +1,140 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    collections::VecDeque,
+    time::{Instant, SystemTime},
+};
+
+use crate::{RouteEvent, Timestamp};
+
+pub(crate) const DEFAULT_HISTORY_CAPACITY: usize = 1024;
+
+/// Route event stamped with a sequence number and the time backend observed it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SequencedEvent {
+    /// Sequence number, starts from 1 and increases by one for every processed event
+    pub seq: u64,
+    /// Wall clock time when event was observed
+    pub time: SystemTime,
+    /// Monotonic time when event was observed
+    pub instant: Instant,
+    pub event: RouteEvent,
+}
+
+/// Bounded history of processed events, oldest events are dropped first
+pub(crate) struct EventHistory {
+    events: VecDeque<SequencedEvent>,
+    capacity: usize,
+    last_seq: u64,
+}
+
+impl EventHistory {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            events: VecDeque::new(),
+            capacity,
+            last_seq: 0,
+        }
+    }
+
+    pub(crate) fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.truncate();
+    }
+
+    pub(crate) fn push(&mut self, event: RouteEvent, timestamp: Timestamp) -> SequencedEvent {
+        self.last_seq += 1;
+        let record = SequencedEvent {
+            seq: self.last_seq,
+            time: timestamp.system,
+            instant: timestamp.instant,
+            event,
+        };
+        self.events.push_back(record.clone());
+        self.truncate();
+        record
+    }
+
+    pub(crate) fn last_seq(&self) -> u64 {
+        self.last_seq
+    }
+
+    /// Events with sequence number greater than `seq`, `None` when some of them were
+    /// already dropped from history
+    pub(crate) fn since(&self, seq: u64) -> Option<Vec<SequencedEvent>> {
+        let first = self
+            .events
+            .front()
+            .map(|e| e.seq)
+            .unwrap_or(self.last_seq.saturating_add(1));
+        if seq.saturating_add(1) < first {
+            return None;
+        }
+        Some(
+            self.events
+                .iter()
+                .filter(|e| e.seq > seq)
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// Events processed at or after `time`
+    pub(crate) fn since_time(&self, time: SystemTime) -> Vec<SequencedEvent> {
+        self.events
+            .iter()
+            .filter(|e| e.time >= time)
+            .cloned()
+            .collect()
+    }
+
+    fn truncate(&mut self) {
+        while self.events.len() > self.capacity {
+            self.events.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test_history {
+    use super::EventHistory;
+    use crate::{Route, RouteEvent, Timestamp};
+
+    #[test]
+    fn test_since() {
+        let route = Route::new("10.0.0.0".parse().unwrap(), 8);
+        let mut history = EventHistory::new(2);
+        assert_eq!(Some(vec![]), history.since(0));
+
+        history.push(RouteEvent::Add(route.clone()), Timestamp::now());
+        history.push(RouteEvent::Change(route.clone()), Timestamp::now());
+        let observed = Timestamp::now();
+        history.push(RouteEvent::Delete(route.clone()), observed);
+        assert_eq!(3, history.last_seq());
+        assert_eq!(None, history.since(0));
+
+        let events = history.since(1).unwrap();
+        assert_eq!(vec![2, 3], events.iter().map(|e| e.seq).collect::<Vec<_>>());
+        assert_eq!(RouteEvent::Delete(route), events[1].event);
+        assert_eq!(observed.instant, events[1].instant);
+        assert_eq!(observed.system, events[1].time);
+        assert!(history.since(3).unwrap().is_empty());
+        assert!(history.since(u64::MAX).unwrap().is_empty());
+    }
+}
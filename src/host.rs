@@ -0,0 +1,257 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    io,
+    net::{IpAddr, ToSocketAddrs},
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use crossbeam_channel::{RecvTimeoutError, Sender};
+
+use crate::{Prefix, Route, RouteManager, SystemRouteOperate};
+
+/// Routes to every address of a host name, created by ```RouteManager::add_route_for_host```
+///
+/// Name is resolved again every refresh interval and routes follow the address set, dropping
+/// the handle stops tracking and leaves current routes in place
+pub struct HostRoutes {
+    host: String,
+    routes: Arc<Mutex<Vec<Route>>>,
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HostRoutes {
+    /// Host name routes are tracked for
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Routes currently installed for host, one host route per address
+    pub fn routes(&self) -> Vec<Route> {
+        self.routes.lock().map(|r| r.clone()).unwrap_or_default()
+    }
+}
+
+impl Drop for HostRoutes {
+    fn drop(&mut self) {
+        // disconnecting wakes the tracking thread immediately
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Addresses host resolves to that a route via gateway can reach, both families when gateway
+/// is unspecified
+fn resolve_host(host: &str, via: IpAddr) -> io::Result<Vec<IpAddr>> {
+    let mut addrs: Vec<IpAddr> = (host, 0)
+        .to_socket_addrs()?
+        .map(|a| a.ip())
+        .filter(|a| via.is_unspecified() || a.is_ipv4() == via.is_ipv4())
+        .collect();
+    addrs.sort();
+    addrs.dedup();
+    Ok(addrs)
+}
+
+impl<O: SystemRouteOperate + 'static> RouteManager<O> {
+    /// Resolve host and install a /32 (/128 for IPv6) route via gateway for each of its
+    /// addresses, then resolve it again every refresh interval, adding routes for new
+    /// addresses and deleting routes of addresses no longer returned
+    ///
+    /// When gateway is unspecified each address gets an on-link route pinned to the interface
+    /// of its current best route. A failed resolution after the first one keeps current routes
+    /// until the next refresh
+    ///
+    /// ```rust no_run
+    /// use std::{sync::Arc, time::Duration};
+    /// use winroute::*;
+    ///
+    /// let manager = Arc::new(RouteManager::new().unwrap());
+    /// let routes = manager
+    ///     .add_route_for_host("api.example.com", "10.8.0.1".parse().unwrap(), Duration::from_secs(300))
+    ///     .unwrap();
+    /// println!("{:?}", routes.routes());
+    /// ```
+    ///
+    /// # Errors
+    /// When host can not be resolved or resolves to no address reachable via gateway, when
+    /// gateway is unspecified and an address has no best route, when a route can not be added,
+    /// or the tracking thread can not be spawned
+    pub fn add_route_for_host(
+        self: &Arc<Self>,
+        host: impl Into<String>,
+        via: IpAddr,
+        refresh: Duration,
+    ) -> io::Result<HostRoutes> {
+        let host = host.into();
+        let addrs = resolve_host(&host, via)?;
+        if addrs.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{host} has no address reachable via {via}"),
+            ));
+        }
+        let mut installed = Vec::new();
+        if let Err(e) = self.sync_host_routes(&addrs, via, &mut installed) {
+            for route in &installed {
+                let _ = self.remove(route);
+            }
+            return Err(e);
+        }
+        let routes = Arc::new(Mutex::new(installed));
+        let (stop, stopped) = crossbeam_channel::bounded::<()>(0);
+        let (manager, name, tracked) = (self.clone(), host.clone(), routes.clone());
+        let thread = self.thread_options().spawn("host", move || {
+            while stopped.recv_timeout(refresh) == Err(RecvTimeoutError::Timeout) {
+                let Ok(addrs) = resolve_host(&name, via) else {
+                    continue;
+                };
+                if let Ok(mut installed) = tracked.lock() {
+                    // failures are retried on next refresh
+                    let _ = manager.sync_host_routes(&addrs, via, &mut installed);
+                }
+            }
+        })?;
+        Ok(HostRoutes {
+            host,
+            routes,
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+
+    /// Make installed hold exactly one host route via gateway, or on the interface of the best
+    /// route when gateway is unspecified, per address
+    fn sync_host_routes(
+        &self,
+        addrs: &[IpAddr],
+        via: IpAddr,
+        installed: &mut Vec<Route>,
+    ) -> io::Result<()> {
+        let mut result = Ok(());
+        installed.retain(|route| {
+            if addrs.contains(&route.destination) {
+                return true;
+            }
+            match self.remove(route) {
+                Ok(()) => false,
+                Err(e) => {
+                    result = Err(e);
+                    true
+                }
+            }
+        });
+        for addr in addrs {
+            if installed.iter().any(|r| r.destination == *addr) {
+                continue;
+            }
+            let mut route = Route::from(Prefix::host(*addr));
+            // unspecified via keeps the on-link gateway of the address family, the interface
+            // must then come from the address itself rather than the best one for 0.0.0.0
+            route = match via.is_unspecified() {
+                true => {
+                    let best = self.best_route(*addr)?;
+                    route.ifindex(self.route_ifindex(&best)?)
+                }
+                false => route.gateway(via),
+            };
+            installed.push(self.install(&route)?);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+pub mod test_host {
+    use std::{net::IpAddr, sync::Arc, time::Duration};
+
+    use crate::{mock::MockOperator, Route, RouteManager};
+
+    #[test]
+    fn test_sync_host_routes() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        let via: IpAddr = "192.168.1.1".parse().unwrap();
+        let a: IpAddr = "203.0.113.1".parse().unwrap();
+        let b: IpAddr = "203.0.113.2".parse().unwrap();
+        let mut installed = Vec::new();
+        manager
+            .sync_host_routes(&[a, b], via, &mut installed)
+            .unwrap();
+        assert_eq!(2, installed.len());
+        assert_eq!((32, via), (installed[0].prefix, installed[0].gateway));
+
+        manager.sync_host_routes(&[b], via, &mut installed).unwrap();
+        assert_eq!(
+            vec![b],
+            installed.iter().map(|r| r.destination).collect::<Vec<_>>()
+        );
+        assert_eq!(1, manager.lock_owned().unwrap().len());
+    }
+
+    #[test]
+    fn test_sync_host_routes_unspecified_via() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![
+            Route::new("0.0.0.0".parse().unwrap(), 0)
+                .gateway("192.168.1.1".parse().unwrap())
+                .ifindex(2),
+            Route::new("::".parse().unwrap(), 0)
+                .gateway("fe80::1".parse().unwrap())
+                .ifindex(3),
+        ]))
+        .unwrap();
+        let via: IpAddr = "0.0.0.0".parse().unwrap();
+        let v4: IpAddr = "203.0.113.1".parse().unwrap();
+        let v6: IpAddr = "2001:db8::1".parse().unwrap();
+        let mut installed = Vec::new();
+        manager
+            .sync_host_routes(&[v4, v6], via, &mut installed)
+            .unwrap();
+        assert_eq!(2, installed.len());
+        assert_eq!("0.0.0.0".parse::<IpAddr>().unwrap(), installed[0].gateway);
+        assert_eq!("::".parse::<IpAddr>().unwrap(), installed[1].gateway);
+        assert_eq!((128, v6), (installed[1].prefix, installed[1].destination));
+        assert_eq!(
+            (Some(2), Some(3)),
+            (installed[0].ifindex, installed[1].ifindex)
+        );
+    }
+
+    #[test]
+    fn test_add_route_for_host() {
+        let manager = Arc::new(RouteManager::with_backend(MockOperator::new(vec![])).unwrap());
+        let routes = manager
+            .add_route_for_host(
+                "localhost",
+                "192.168.1.1".parse().unwrap(),
+                Duration::from_secs(60),
+            )
+            .unwrap();
+        assert_eq!("localhost", routes.host());
+        assert!(routes
+            .routes()
+            .iter()
+            .all(|r| r.destination.is_ipv4() && r.prefix == 32));
+        assert!(!routes.routes().is_empty());
+    }
+}
@@ -0,0 +1,484 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Minimal HTTP API over the routing table, enabled with the `http` feature
+
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use crossbeam_channel::{Receiver, Sender};
+
+use crate::{Route, RouteManager, SystemRouteOperate};
+
+/// Request bodies larger than this are rejected
+const MAX_BODY: usize = 64 * 1024;
+
+/// Request line and headers together larger than this are rejected
+const MAX_HEADER: u64 = 8 * 1024;
+
+/// Longest a client may take to send its request or accept a chunk of the response
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Clients served at once, including open event streams, others are answered `503`
+const MAX_CLIENTS: usize = 32;
+
+/// HTTP server created by ```RouteManager::serve_http```, stops accepting when dropped
+pub struct HttpServer {
+    addr: SocketAddr,
+    stopped: Arc<AtomicBool>,
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HttpServer {
+    /// Address server listens on, useful when bound to port 0
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for HttpServer {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // ends open event streams
+        self.stop.take();
+        // wake the accept loop
+        let _ = TcpStream::connect(self.addr);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Place of a served client, freed when its thread ends or could not be spawned
+struct ClientSlot(Arc<AtomicUsize>);
+
+impl Drop for ClientSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    host: Option<String>,
+    origin: Option<String>,
+    content_type: Option<String>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    /// Status to refuse the request with when it may come from a web page instead of a local
+    /// client: browsers send Origin on cross-origin requests, only preflighted requests can
+    /// carry a JSON Content-Type, and a rebound DNS name shows up in Host
+    fn rejection(&self, local: SocketAddr) -> Option<u16> {
+        if self.origin.is_some() {
+            return Some(403);
+        }
+        let host_allowed = self.host.as_deref().is_some_and(|host| {
+            host == local.to_string() || host == format!("localhost:{}", local.port())
+        });
+        if !host_allowed {
+            return Some(403);
+        }
+        let json = self.content_type.as_deref().is_some_and(|value| {
+            let media = value.split(';').next().unwrap_or_default();
+            media.trim().eq_ignore_ascii_case("application/json")
+        });
+        if self.method != "GET" && !json {
+            return Some(415);
+        }
+        None
+    }
+}
+
+/// Read one header line, failing once the header limit of reader is exhausted mid line
+fn read_header_line(reader: &mut impl BufRead, line: &mut String) -> io::Result<usize> {
+    line.clear();
+    let read = reader.read_line(line)?;
+    if read > 0 && !line.ends_with('\n') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "request header too large",
+        ));
+    }
+    Ok(read)
+}
+
+fn read_request(stream: &TcpStream) -> io::Result<Request> {
+    let mut reader = BufReader::new(stream).take(MAX_HEADER);
+    let mut line = String::new();
+    read_header_line(&mut reader, &mut line)?;
+    let mut parts = line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next()) {
+        (Some(method), Some(path)) => (method.to_string(), path.to_string()),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "malformed request line",
+            ))
+        }
+    };
+    let mut length = 0;
+    let (mut host, mut origin, mut content_type) = (None, None, None);
+    loop {
+        if read_header_line(&mut reader, &mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let (name, value) = (name.trim(), value.trim());
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.parse().map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, "invalid Content-Length")
+                })?;
+            } else if name.eq_ignore_ascii_case("host") {
+                host = Some(value.to_string());
+            } else if name.eq_ignore_ascii_case("origin") {
+                origin = Some(value.to_string());
+            } else if name.eq_ignore_ascii_case("content-type") {
+                content_type = Some(value.to_string());
+            }
+        }
+    }
+    if length > MAX_BODY {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "request body too large",
+        ));
+    }
+    let mut body = vec![0u8; length];
+    reader.set_limit(length as u64);
+    reader.read_exact(&mut body)?;
+    Ok(Request {
+        method,
+        path,
+        host,
+        origin,
+        content_type,
+        body,
+    })
+}
+
+fn respond(mut stream: &TcpStream, status: u16, body: &[u8]) -> io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        408 => "Request Timeout",
+        415 => "Unsupported Media Type",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        reason,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+fn respond_error(stream: &TcpStream, err: &io::Error) -> io::Result<()> {
+    let status = match err.kind() {
+        io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData => 400,
+        io::ErrorKind::PermissionDenied => 403,
+        io::ErrorKind::NotFound => 404,
+        io::ErrorKind::AlreadyExists => 409,
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => 408,
+        _ => 500,
+    };
+    let body = serde_json::json!({ "error": err.to_string() });
+    respond(stream, status, body.to_string().as_bytes())
+}
+
+/// Closing with unread input resets the connection and may discard the answer, let client
+/// read it first
+fn close_unread(stream: &TcpStream) {
+    let _ = stream.shutdown(Shutdown::Write);
+    let _ = io::copy(
+        &mut stream.take(MAX_HEADER + MAX_BODY as u64),
+        &mut io::sink(),
+    );
+}
+
+fn parse_route(body: &[u8]) -> io::Result<Route> {
+    serde_json::from_slice(body).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+impl<O: SystemRouteOperate + 'static> RouteManager<O> {
+    /// Serve a small HTTP API over the routing table on addr
+    ///
+    /// | Request          | Body       | Response                               |
+    /// |------------------|------------|----------------------------------------|
+    /// | `GET /routes`    |            | `200` JSON array of routes             |
+    /// | `POST /routes`   | route JSON | `201` route as installed               |
+    /// | `DELETE /routes` | route JSON | `204`                                  |
+    /// | `GET /events`    |            | `200` server-sent events, one per line |
+    ///
+    /// Errors are answered with a status matching the io error kind and a `{"error": ...}` body
+    ///
+    /// The API has no authentication, anyone able to connect can change routes, so bind it
+    /// to a loopback address. Someone has to keep calling ```poll``` for `/events` to stream
+    ///
+    /// To keep web pages from reaching it through the browser, requests carrying an `Origin`
+    /// header or a `Host` other than the bound address (or `localhost` with its port) get
+    /// `403`, and `POST` and `DELETE` without `Content-Type: application/json` get `415`
+    ///
+    /// Clients get 10 seconds to send their request and to accept each write, a request line
+    /// and headers may take 8 KiB and a body 64 KiB. At most 32 clients, event streams
+    /// included, are served at once, the next ones get `503`
+    ///
+    /// ```rust no_run
+    /// use std::sync::Arc;
+    /// use winroute::*;
+    ///
+    /// let manager = Arc::new(RouteManager::new().unwrap());
+    /// let _server = manager.serve_http("127.0.0.1:8053").unwrap();
+    /// loop {
+    ///     manager.poll().unwrap();
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    /// When address can not be bound or server thread can not be spawned
+    pub fn serve_http(self: &Arc<Self>, addr: impl ToSocketAddrs) -> io::Result<HttpServer> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let (stop, stop_receiver) = crossbeam_channel::bounded::<()>(0);
+        let (manager, flag) = (self.clone(), stopped.clone());
+        let clients = Arc::new(AtomicUsize::new(0));
+        let thread = self.thread_options().spawn("http", move || {
+            for stream in listener.incoming() {
+                if flag.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(stream) = stream else { continue };
+                if stream.set_read_timeout(Some(CLIENT_TIMEOUT)).is_err()
+                    || stream.set_write_timeout(Some(CLIENT_TIMEOUT)).is_err()
+                {
+                    continue;
+                }
+                if clients.fetch_add(1, Ordering::SeqCst) >= MAX_CLIENTS {
+                    clients.fetch_sub(1, Ordering::SeqCst);
+                    // not drained, a slow client must not hold up the accept loop
+                    let _ = respond(&stream, 503, b"");
+                    continue;
+                }
+                let (manager, stop) = (manager.clone(), stop_receiver.clone());
+                let slot = ClientSlot(clients.clone());
+                let _ = manager
+                    .thread_options()
+                    .clone()
+                    .spawn("http-client", move || {
+                        let _slot = slot;
+                        manager.handle_http(stream, addr, stop)
+                    });
+            }
+        })?;
+        Ok(HttpServer {
+            addr,
+            stopped,
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+
+    fn handle_http(&self, stream: TcpStream, local: SocketAddr, stop: Receiver<()>) {
+        let request = match read_request(&stream) {
+            Ok(request) => request,
+            Err(e) => {
+                let _ = respond_error(&stream, &e);
+                close_unread(&stream);
+                return;
+            }
+        };
+        if let Some(status) = request.rejection(local) {
+            let _ = respond(&stream, status, b"");
+            return;
+        }
+        let result = match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/routes") => self.routes().and_then(|routes| {
+                let body = serde_json::to_vec(&routes)?;
+                respond(&stream, 200, &body)
+            }),
+            ("POST", "/routes") => parse_route(&request.body)
+                .and_then(|route| self.add_route_resolved(&route))
+                .and_then(|route| respond(&stream, 201, &serde_json::to_vec(&route)?)),
+            ("DELETE", "/routes") => parse_route(&request.body)
+                .and_then(|route| self.delete_route(&route))
+                .and_then(|()| respond(&stream, 204, b"")),
+            ("GET", "/events") => self.stream_events(&stream, stop),
+            (_, "/routes" | "/events") => respond(&stream, 405, b""),
+            _ => respond(&stream, 404, b""),
+        };
+        if let Err(e) = result {
+            let _ = respond_error(&stream, &e);
+        }
+    }
+
+    /// Write every event as a server-sent event until client disconnects or server stops
+    fn stream_events(&self, mut stream: &TcpStream, stop: Receiver<()>) -> io::Result<()> {
+        let subscription = self.subscribe()?;
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\r\n"
+        )?;
+        stream.flush()?;
+        loop {
+            crossbeam_channel::select! {
+                recv(subscription.receiver()) -> event => {
+                    let Ok(event) = event else { return Ok(()) };
+                    let data = serde_json::to_string(&event)?;
+                    // client went away, nothing left to answer
+                    if write!(stream, "data: {}\n\n", data).and_then(|()| stream.flush()).is_err() {
+                        return Ok(());
+                    }
+                }
+                recv(stop) -> _ => return Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test_http {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::{SocketAddr, TcpStream},
+        sync::Arc,
+    };
+
+    use crate::{mock::MockOperator, Route, RouteManager};
+
+    fn request(addr: SocketAddr, method: &str, path: &str, body: &str) -> (u16, String) {
+        request_with(addr, method, path, "", body)
+    }
+
+    /// Request with extra `headers`, each ending with CRLF, that override the defaults
+    fn request_with(
+        addr: SocketAddr,
+        method: &str,
+        path: &str,
+        headers: &str,
+        body: &str,
+    ) -> (u16, String) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n{}Content-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            addr,
+            headers,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let body = response.split("\r\n\r\n").nth(1).unwrap_or("").to_string();
+        (status, body)
+    }
+
+    #[test]
+    fn test_api() {
+        let manager = Arc::new(RouteManager::with_backend(MockOperator::new(vec![])).unwrap());
+        let server = manager.serve_http("127.0.0.1:0").unwrap();
+        let addr = server.local_addr();
+
+        let mut events = TcpStream::connect(addr).unwrap();
+        write!(events, "GET /events HTTP/1.1\r\nHost: {}\r\n\r\n", addr).unwrap();
+        let mut events = BufReader::new(events);
+        let mut line = String::new();
+        events.read_line(&mut line).unwrap();
+        assert!(line.starts_with("HTTP/1.1 200"));
+        while line != "\r\n" {
+            line.clear();
+            events.read_line(&mut line).unwrap();
+        }
+
+        let route = Route::new("10.0.0.0".parse().unwrap(), 8).ifindex(1);
+        let json = serde_json::to_string(&route).unwrap();
+        let (status, body) = request(addr, "POST", "/routes", &json);
+        assert_eq!(201, status);
+        assert_eq!(route, serde_json::from_str(&body).unwrap());
+        assert_eq!(409, request(addr, "POST", "/routes", &json).0);
+        assert_eq!(400, request(addr, "POST", "/routes", "{}").0);
+
+        manager.poll().unwrap();
+        line.clear();
+        events.read_line(&mut line).unwrap();
+        assert!(line.starts_with("data: {\"Add\""), "{}", line);
+
+        let (status, body) = request(addr, "GET", "/routes", "");
+        assert_eq!(200, status);
+        assert_eq!(
+            vec![route],
+            serde_json::from_str::<Vec<Route>>(&body).unwrap()
+        );
+        assert_eq!(204, request(addr, "DELETE", "/routes", &json).0);
+        assert_eq!(404, request(addr, "GET", "/missing", "").0);
+        drop(server);
+    }
+
+    #[test]
+    fn test_reject_browser_requests() {
+        let manager = Arc::new(RouteManager::with_backend(MockOperator::new(vec![])).unwrap());
+        let server = manager.serve_http("127.0.0.1:0").unwrap();
+        let addr = server.local_addr();
+        let json =
+            serde_json::to_string(&Route::new("10.0.0.0".parse().unwrap(), 8).ifindex(1)).unwrap();
+
+        let text = "Content-Type: text/plain\r\n";
+        assert_eq!(415, request_with(addr, "POST", "/routes", text, &json).0);
+        assert_eq!(415, request_with(addr, "DELETE", "/routes", text, &json).0);
+        let origin = "Origin: http://example.com\r\n";
+        assert_eq!(403, request_with(addr, "GET", "/routes", origin, "").0);
+        let rebound = "Host: attacker.example:80\r\n";
+        assert_eq!(403, request_with(addr, "GET", "/routes", rebound, "").0);
+        assert!(manager.routes().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reject_large_header() {
+        let manager = Arc::new(RouteManager::with_backend(MockOperator::new(vec![])).unwrap());
+        let server = manager.serve_http("127.0.0.1:0").unwrap();
+        let addr = server.local_addr();
+        let large = format!("X-Padding: {}\r\n", "a".repeat(8 * 1024));
+        assert_eq!(400, request_with(addr, "GET", "/routes", &large, "").0);
+        assert_eq!(200, request(addr, "GET", "/routes", "").0);
+    }
+}
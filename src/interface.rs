@@ -0,0 +1,223 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{collections::HashMap, io};
+
+use crossbeam_channel::Receiver;
+
+use crate::{ConnectionEvent, Family, SystemRouteOperate};
+
+/// Identity of a network interface
+#[cfg_attr(feature = "serializable", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceInfo {
+    pub ifindex: u32,
+    pub luid: u64,
+    pub alias: String,
+    /// Interface GUID in registry format, e.g. `{4D36E972-E325-11CE-BFC1-08002BE10318}`
+    pub guid: String,
+}
+
+/// Mapping of interface index to interface alias, e.g. `12 => "Ethernet"`
+///
+/// Used by [`Route::display_with`](crate::Route::display_with) to print readable interface names
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InterfaceNames {
+    names: HashMap<u32, String>,
+}
+
+impl InterfaceNames {
+    /// Create an empty mapping
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read aliases of all interfaces present in system
+    ///
+    /// # Errors
+    /// when system api return error
+    #[cfg(windows)]
+    pub fn load() -> io::Result<Self> {
+        Ok(Self::from_interfaces(&crate::windows::read_interfaces()?))
+    }
+
+    #[cfg(not(windows))]
+    pub fn load() -> io::Result<Self> {
+        Err(io::Error::other("None windows system not supported"))
+    }
+
+    pub(crate) fn from_interfaces(interfaces: &[InterfaceInfo]) -> Self {
+        let names = interfaces
+            .iter()
+            .map(|i| (i.ifindex, i.alias.clone()))
+            .collect();
+        Self { names }
+    }
+
+    /// Set alias of an interface index
+    pub fn insert(&mut self, ifindex: u32, alias: impl Into<String>) {
+        self.names.insert(ifindex, alias.into());
+    }
+
+    /// Alias of an interface index
+    pub fn get(&self, ifindex: u32) -> Option<&str> {
+        self.names.get(&ifindex).map(String::as_str)
+    }
+
+    /// Interface index of an alias, aliases are compared case insensitively
+    pub fn index_of(&self, alias: &str) -> Option<u32> {
+        self.names
+            .iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(alias))
+            .map(|(idx, _)| *idx)
+    }
+}
+
+/// Interface identities and metrics read from backend, shared by manager lookups so they hit
+/// the system once and agree with each other
+///
+/// Cleared when an adapter connects or disconnects, when manager changes an interface
+/// configuration, and reloaded once when a lookup misses
+#[derive(Default)]
+pub(crate) struct InterfaceCache {
+    interfaces: Option<Vec<InterfaceInfo>>,
+    metrics: HashMap<(Family, u32), u32>,
+    changes: Option<Receiver<ConnectionEvent>>,
+}
+
+impl InterfaceCache {
+    pub(crate) fn clear(&mut self) {
+        self.interfaces = None;
+        self.metrics.clear();
+    }
+
+    /// Clear cache when an adapter changed since last lookup
+    fn expire<O: SystemRouteOperate>(&mut self, operator: &O) {
+        match &self.changes {
+            Some(changes) => {
+                if changes.try_iter().count() > 0 {
+                    self.clear();
+                }
+            }
+            // backends without adapter watching are only refreshed by misses
+            None => self.changes = operator.connection_events().ok(),
+        }
+    }
+
+    pub(crate) fn interfaces<O: SystemRouteOperate>(
+        &mut self,
+        operator: &O,
+    ) -> io::Result<&[InterfaceInfo]> {
+        self.expire(operator);
+        if self.interfaces.is_none() {
+            self.interfaces = Some(operator.interfaces()?);
+        }
+        Ok(self.interfaces.as_deref().unwrap_or_default())
+    }
+
+    /// First interface matching predicate, reloading interfaces once when none matches
+    pub(crate) fn find<O: SystemRouteOperate>(
+        &mut self,
+        operator: &O,
+        predicate: impl Fn(&InterfaceInfo) -> bool,
+    ) -> io::Result<Option<InterfaceInfo>> {
+        let fresh = self.interfaces.is_none();
+        if let Some(found) = self.interfaces(operator)?.iter().find(|i| predicate(i)) {
+            return Ok(Some(found.clone()));
+        }
+        if fresh {
+            return Ok(None);
+        }
+        self.clear();
+        Ok(self
+            .interfaces(operator)?
+            .iter()
+            .find(|i| predicate(i))
+            .cloned())
+    }
+
+    pub(crate) fn metric<O: SystemRouteOperate>(
+        &mut self,
+        operator: &O,
+        family: Family,
+        ifindex: u32,
+    ) -> io::Result<u32> {
+        self.expire(operator);
+        if let Some(metric) = self.metrics.get(&(family, ifindex)) {
+            return Ok(*metric);
+        }
+        let metric = operator.interface_metric(family, ifindex)?;
+        self.metrics.insert((family, ifindex), metric);
+        Ok(metric)
+    }
+}
+
+/// Neighbor discovery router discovery behavior of an interface
+#[cfg_attr(feature = "serializable", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouterDiscovery {
+    Disabled,
+    Enabled,
+    /// Enabled when DHCP is used to configure the interface
+    Dhcp,
+}
+
+/// IP configuration of an interface for one address family, read by
+/// ```RouteManager::interface_config```
+///
+/// Fields documented as read-only are ignored by ```RouteManager::set_interface_config```
+#[cfg_attr(feature = "serializable", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceConfig {
+    pub family: Family,
+    pub ifindex: u32,
+    /// Interface metric, only effective when ```use_automatic_metric``` is false
+    pub metric: u32,
+    pub use_automatic_metric: bool,
+    /// Link MTU in bytes
+    pub mtu: u32,
+    /// Number of duplicate address detection probes
+    pub dad_transmits: u32,
+    pub router_discovery: RouterDiscovery,
+    /// Base neighbor reachable time in milliseconds
+    pub base_reachable_time: u32,
+    /// Neighbor solicitation retransmit time in milliseconds
+    pub retransmit_time: u32,
+    /// Path MTU discovery timeout in milliseconds
+    pub path_mtu_discovery_timeout: u32,
+    pub use_neighbor_unreachability_detection: bool,
+    pub forwarding_enabled: bool,
+    pub weak_host_send: bool,
+    pub weak_host_receive: bool,
+    /// Whether interface sends router advertisements, IPv6 only
+    pub advertising_enabled: bool,
+    /// Whether advertisements announce this host as a default router
+    pub advertise_default_route: bool,
+    /// Minimum interval between unsolicited router advertisements in milliseconds
+    pub min_router_advertisement_interval: u32,
+    /// Maximum interval between unsolicited router advertisements in milliseconds
+    pub max_router_advertisement_interval: u32,
+    /// Read-only, randomized reachable time in milliseconds derived from ```base_reachable_time```
+    pub reachable_time: u32,
+    /// Read-only, whether interface is connected to a network
+    pub connected: bool,
+    /// Read-only
+    pub supports_neighbor_discovery: bool,
+    /// Read-only
+    pub supports_router_discovery: bool,
+}
@@ -24,7 +24,7 @@
 //! ## Manage routing table
 //! ```rust no_run
 //! use winroute::*;
-//! 
+//!
 //! let manager = RouteManager::new().unwrap();
 //! let new_route = Route::new("223.6.6.6".parse().unwrap(), 32).metric(1);
 //! // add route
@@ -37,27 +37,35 @@
 //! }
 //! ```
 //!
+//! ## One-shot operations without a manager
+//! ```rust no_run
+//! let route = winroute::Route::new("223.6.6.6".parse().unwrap(), 32);
+//! winroute::add_route(&route).unwrap();
+//! println!("{}", winroute::list_routes().unwrap().len());
+//! winroute::delete_route(&route).unwrap();
+//! ```
+//!
 //! ## Listen a table change event
 //! ```rust no_run
 //! use winroute::*;
 //! use std::sync::Arc;
-//! 
+//!
 //! fn main() -> std::io::Result<()> {
 //!     let manager = RouteManager::new()?;
 //!     let recvier = manager.subscribe_route_change();
 //!     let ma = Arc::new(manager);
 //!     let mb = ma.clone();
-//! 
+//!
 //!     // start a thread to driven event loop, also can use async task to run this
 //!     std::thread::spawn(move || loop {
 //!         ma.poll().unwrap();
 //!     });
-//! 
+//!
 //!     // create a new route
 //!     let new_route = Route::new("223.6.6.6".parse().unwrap(), 32);
 //!     // add route to system
 //!     mb.add_route(&new_route)?;
-//! 
+//!
 //!     loop {
 //!         // listeing on route change event
 //!         let event = recvier.recv().unwrap();
@@ -66,13 +74,126 @@
 //! }
 //! ```
 
+mod alert;
+mod builder;
+mod bulk;
+mod cancel;
+mod coalesce;
+mod connection;
+mod error;
+mod guard;
+mod health;
+mod history;
+mod host;
+mod interface;
 mod manager;
+mod metric;
+#[cfg(test)]
+mod mock;
+mod neighbor;
+mod oneshot;
+mod operator;
+mod origin;
+mod pending;
+mod poller;
+mod prefix;
+mod reader;
+mod resync;
 mod route;
+mod shared;
+mod stats;
+mod subscription;
+mod tamper;
+mod thread;
+mod trace;
+mod transaction;
+mod trie;
+
+#[cfg(feature = "binary")]
+mod binary;
+#[cfg(feature = "binary")]
+mod replay;
+
+#[cfg(feature = "net-route")]
+mod netroute;
+
+#[cfg(feature = "profiles")]
+mod profile;
+
+#[cfg(feature = "powershell")]
+mod powershell;
+
+#[cfg(feature = "pipe")]
+mod pipe;
+
+#[cfg(feature = "http")]
+mod http;
+
+#[cfg(all(feature = "service", windows))]
+mod service;
+
+#[cfg(feature = "async")]
+mod stream;
+
+#[cfg(feature = "tokio")]
+mod tokio_support;
 
 #[cfg(windows)]
 mod windows;
 
-pub use manager::RouteEvent;
-pub use manager::RouteManager;
-pub use route::Route;
+#[cfg(windows)]
+pub use windows::WindowsOperator;
 
+pub use alert::TableAlert;
+pub use builder::RouteManagerBuilder;
+pub use bulk::{BatchResult, BatchSummary, BulkOptions, BulkProgress};
+pub use cancel::CancelToken;
+pub use connection::{Adapter, AdapterKind, ConnectionEvent};
+pub use error::PollError;
+pub use guard::{RouteGuard, RouteSet, RouteSetGuard};
+pub use health::{GatewayEvent, GatewayMonitor};
+pub use history::SequencedEvent;
+pub use host::HostRoutes;
+#[cfg(feature = "http")]
+pub use http::HttpServer;
+pub use interface::{InterfaceConfig, InterfaceInfo, InterfaceNames, RouterDiscovery};
+pub use manager::RouteEvent;
+pub use manager::{Events, RouteManager, TimedEvent, Timestamp};
+pub use metric::{Competitor, Metric, Preference};
+pub use neighbor::MacAddr;
+pub use oneshot::{add_route, delete_route, list_routes};
+#[cfg(not(windows))]
+pub use operator::NoSystemOperator;
+pub use operator::{DefaultOperator, SystemRouteOperate};
+pub use origin::{Origin, TaggedEvent};
+pub use pending::InterfaceSelector;
+#[cfg(all(feature = "pipe", windows))]
+pub use pipe::PipeServer;
+#[cfg(feature = "pipe")]
+pub use pipe::{FrameFormat, FrameReader, FrameWriter};
+pub use poller::Poller;
+#[cfg(feature = "powershell")]
+pub use powershell::{parse_net_route_json, NetRouteComparison};
+pub use prefix::Prefix;
+#[cfg(feature = "profiles")]
+pub use profile::{Profile, ProfileEvent, ProfileWatcher, Profiles, Reconciled, RouteSpec};
+pub use reader::RouteTableReader;
+#[cfg(feature = "binary")]
+pub use replay::{EventRecorder, RecordedEvent, ReplayOperator};
+pub use resync::Resyncer;
+pub use route::{Cidr, Family, Route, RouteDisplay};
+#[cfg(all(feature = "service", windows))]
+pub use service::{run_service, ServiceContext, ServiceControl, ServiceState};
+pub use shared::{SharedBackend, SharedOperator};
+pub use stats::{EventCounts, RouteStatistics};
+#[cfg(feature = "async")]
+pub use stream::RouteEventStream;
+pub use subscription::{
+    BatchSubscription, OverflowPolicy, Subscription, SubscriptionIntoIter, SubscriptionIter,
+};
+pub use tamper::OwnedRouteEvent;
+pub use thread::{ThreadOptions, ThreadPriority};
+#[cfg(feature = "tokio")]
+pub use tokio_support::AsyncSubscription;
+pub use trace::{BestRouteChange, ResolvedRouteChange, Trace, TraceStep};
+pub use transaction::RouteTransaction;
@@ -18,36 +18,127 @@
 
 use std::{
     cell::RefCell,
-    error::Error,
+    collections::HashMap,
+    fmt::Display,
     io,
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
-    sync::{Mutex, PoisonError},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    time::{Duration, Instant, SystemTime},
 };
 
 use crossbeam_channel::{Receiver, Sender};
 
-use crate::Route;
+use crate::{
+    alert::Watermarks,
+    history::EventHistory,
+    interface::InterfaceCache,
+    metric::{auto_metric, windows_metric},
+    operator::{DefaultOperator, SystemRouteOperate},
+    origin::OwnEvents,
+    prefix::{is_on_link, verify_gateway},
+    route::fingerprint,
+    stats::StatisticsCollector,
+    subscription::{Delivery, Subscriber},
+    tamper, trace,
+    trace::{BestRouteWatcher, ResolvedRouteWatcher},
+    trie::RouteTrie,
+    BatchSubscription, BestRouteChange, CancelToken, Competitor, ConnectionEvent, Family,
+    InterfaceConfig, InterfaceInfo, InterfaceNames, InterfaceSelector, MacAddr, Metric, Origin,
+    OverflowPolicy, OwnedRouteEvent, PollError, Prefix, ResolvedRouteChange, Route,
+    RouteManagerBuilder, RouteStatistics, SequencedEvent, Subscription, TableAlert, TaggedEvent,
+    ThreadOptions, Trace,
+};
 
-pub(crate) trait SystemRouteOperate {
-    fn new(sender: Sender<RouteEvent>) -> Self
-    where
-        Self: Sized;
-    fn init(&self) -> io::Result<()>;
-    fn read_all_routes(&self) -> io::Result<Vec<Route>>;
-    fn add_route(&self, route: &Route) -> io::Result<()>;
-    fn delete_route(&self, route: &Route) -> io::Result<()>;
+/// Longest a ```wait_for_route``` poll blocks before checking its own subscription again
+const WAIT_SLICE: Duration = Duration::from_millis(100);
+
+/// Reads of the full table ```resync``` tries before giving up on a table that keeps changing
+const RESYNC_ATTEMPTS: usize = 3;
+
+/// Moment an event was observed by the backend, as both monotonic and wall clock time
+///
+/// Use ```instant``` to measure intervals between events, it is not affected by clock
+/// adjustments, and ```system``` to correlate events with external logs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp {
+    pub instant: Instant,
+    pub system: SystemTime,
+}
+
+impl Timestamp {
+    pub fn now() -> Self {
+        Self {
+            instant: Instant::now(),
+            system: SystemTime::now(),
+        }
+    }
+}
+
+/// Route event stamped by the backend at the time it was notified
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimedEvent {
+    pub event: RouteEvent,
+    pub timestamp: Timestamp,
+}
+
+impl TimedEvent {
+    /// Stamp event with current time
+    pub fn now(event: RouteEvent) -> Self {
+        Self {
+            event,
+            timestamp: Timestamp::now(),
+        }
+    }
 }
 
 /// Routing table change event
+#[cfg_attr(feature = "serializable", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum RouteEvent {
     Add(Route),
     Delete(Route),
     Change(Route),
+    /// Listener is registered and live, only reported when requested with
+    /// ```RouteManagerBuilder::initial_notification```
+    Initial,
+}
+
+impl RouteEvent {
+    /// Route the event is about, `None` for ```RouteEvent::Initial```
+    pub fn route(&self) -> Option<&Route> {
+        match self {
+            RouteEvent::Add(route) | RouteEvent::Delete(route) | RouteEvent::Change(route) => {
+                Some(route)
+            }
+            RouteEvent::Initial => None,
+        }
+    }
+}
+
+impl Display for RouteEvent {
+    /// Print event as a log line, e.g. `ADD 10.1.0.0/16 via 192.168.1.1 (if 12, metric 5)`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (action, route) = match self {
+            RouteEvent::Add(route) => ("ADD", route),
+            RouteEvent::Delete(route) => ("DELETE", route),
+            RouteEvent::Change(route) => ("CHANGE", route),
+            RouteEvent::Initial => return write!(f, "INITIAL"),
+        };
+        write!(f, "{} {} via {}", action, route.cidr(), route.gateway)?;
+        match (route.ifindex, route.metric) {
+            (Some(ifindex), Some(metric)) => write!(f, " (if {}, metric {})", ifindex, metric),
+            (Some(ifindex), None) => write!(f, " (if {})", ifindex),
+            (None, Some(metric)) => write!(f, " (metric {})", metric),
+            (None, None) => Ok(()),
+        }
+    }
 }
 
 /// Route manager structure, using ```RouteManager::new()``` to create a new one
-/// 
+///
 /// # Examples
 ///
 /// ```rust no_run
@@ -62,53 +153,174 @@ pub enum RouteEvent {
 ///     Ok(())
 /// }
 /// ```
-/// 
-pub struct RouteManager {
+///
+pub struct RouteManager<O: SystemRouteOperate = DefaultOperator> {
     routes: Mutex<RefCell<Vec<Route>>>,
-    operator: Box<dyn SystemRouteOperate>,
-    operator_receiver: Receiver<RouteEvent>,
-    subscribers: Receiver<RouteEvent>,
-    producer: Sender<RouteEvent>,
+    // kept in step with `routes`, locked after it
+    index: Mutex<RouteTrie>,
+    history: Mutex<EventHistory>,
+    stats: Mutex<StatisticsCollector>,
+    watermarks: Mutex<Watermarks>,
+    alert_subscribers: Mutex<Vec<Sender<TableAlert>>>,
+    tamper_subscribers: Mutex<Vec<Sender<OwnedRouteEvent>>>,
+    route_watchers: Mutex<Vec<BestRouteWatcher>>,
+    resolved_watchers: Mutex<Vec<ResolvedRouteWatcher>>,
+    interfaces: Mutex<InterfaceCache>,
+    operator: O,
+    operator_receiver: Receiver<TimedEvent>,
+    // events discarded by coalesce thread's buffer
+    coalesce_lost: Option<Arc<AtomicUsize>>,
+    // events known lost and not yet recovered by a resync
+    lost_events: AtomicUsize,
+    // bumped under the `routes` lock whenever an event is applied, lets resync notice that its
+    // snapshot, read without the lock, is older than the cache
+    applied: AtomicU64,
+    // dropped by shutdown, which disconnects `closed` and wakes pending polls
+    closing: Mutex<Option<Sender<()>>>,
+    closed: Receiver<()>,
+    dedicated_subscribers: Mutex<Vec<Subscriber>>,
+    owned: Mutex<Vec<Route>>,
+    cleanup_on_drop: bool,
+    listening: bool,
+    suspended: AtomicBool,
+    quiescing: AtomicUsize,
+    own_events: Mutex<OwnEvents>,
+    tagged_subscribers: Mutex<Vec<Sender<TaggedEvent>>>,
+    suppress_own_events: bool,
+    pending: Mutex<Vec<(Route, InterfaceSelector)>>,
+    #[cfg(feature = "tokio")]
+    bridge: crate::tokio_support::Bridge,
+    validate_gateway: bool,
+    dry_run: bool,
+    planned: Mutex<Vec<RouteEvent>>,
+    subscriber_buffer: (Option<usize>, OverflowPolicy),
+    threads: ThreadOptions,
 }
 
 impl RouteManager {
-    /// Create a RouteManager
+    /// Create a RouteManager with default configuration
     ///
     /// # Errors
-    /// When windows NotifyRouteChange2 return error will panic
-    #[cfg(windows)]
+    /// When windows NotifyRouteChange2 return error
     pub fn new() -> io::Result<Self> {
-        use crate::windows::WindowsOperator;
+        RouteManagerBuilder::new().build()
+    }
+
+    /// Create a RouteManager that only adds and deletes routes
+    ///
+    /// No route change listener is registered and the routing table is not read up front,
+    /// suited for one-shot tools. ```poll``` returns an error and ```routes``` reads the
+    /// table from system on every call
+    ///
+    /// # Errors
+    /// On non windows system
+    pub fn operations_only() -> io::Result<Self> {
+        RouteManagerBuilder::new().listen(false).build()
+    }
+
+    /// Create a builder to configure the RouteManager
+    pub fn builder() -> RouteManagerBuilder {
+        RouteManagerBuilder::new()
+    }
+}
+
+impl<O: SystemRouteOperate> RouteManager<O> {
+    /// Create a RouteManager with default configuration on top of a custom backend,
+    /// e.g. a simulated routing table
+    ///
+    /// # Errors
+    /// When backend fails to initialize or to read the routing table
+    pub fn with_backend(backend: O) -> io::Result<Self> {
+        RouteManagerBuilder::new().build_with_backend(backend)
+    }
+
+    /// Options background threads of this manager are spawned with
+    pub fn thread_options(&self) -> &ThreadOptions {
+        &self.threads
+    }
+
+    /// Backend the manager operates on
+    pub fn backend(&self) -> &O {
+        &self.operator
+    }
 
-        let (tx, rx) = crossbeam_channel::unbounded();
-        let (tx_loop, rx_loop) = crossbeam_channel::unbounded();
-        let operator = Box::new(WindowsOperator::new(tx));
-        operator.init()?;
-        let routes = operator.read_all_routes().unwrap();
+    pub(crate) fn from_operator(operator: O, config: RouteManagerBuilder) -> io::Result<Self> {
+        if config.event_buffer.0 == Some(0) || config.subscriber_buffer.0 == Some(0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "event and subscriber buffers must hold at least one event",
+            ));
+        }
+        let routes = if config.listen {
+            operator.init()?;
+            cacheable(operator.read_all_routes()?)
+        } else {
+            Vec::new()
+        };
+        let (operator_receiver, coalesce_lost) = match config.coalesce {
+            Some(window) if config.listen => {
+                let (receiver, lost) = crate::coalesce::coalesce(
+                    operator.event_receiver(),
+                    window,
+                    config.event_buffer,
+                    &config.threads,
+                )?;
+                (receiver, Some(lost))
+            }
+            _ => (operator.event_receiver(), None),
+        };
 
+        let (closing, closed) = crossbeam_channel::bounded(0);
         let manager = RouteManager {
+            index: Mutex::new(RouteTrie::new(&routes)),
             routes: Mutex::new(RefCell::new(routes)),
+            history: Mutex::new(EventHistory::new(config.history_capacity)),
+            stats: Mutex::new(StatisticsCollector::new()),
+            watermarks: Mutex::new(Watermarks::new(
+                config.size_watermark,
+                config.growth_watermark,
+            )),
+            alert_subscribers: Mutex::new(Vec::new()),
+            tamper_subscribers: Mutex::new(Vec::new()),
+            route_watchers: Mutex::new(Vec::new()),
+            resolved_watchers: Mutex::new(Vec::new()),
+            interfaces: Mutex::new(InterfaceCache::default()),
             operator,
-            operator_receiver: rx,
-            subscribers: rx_loop,
-            producer: tx_loop,
+            operator_receiver,
+            coalesce_lost,
+            lost_events: AtomicUsize::new(0),
+            applied: AtomicU64::new(0),
+            closing: Mutex::new(Some(closing)),
+            closed,
+            dedicated_subscribers: Mutex::new(Vec::new()),
+            owned: Mutex::new(Vec::new()),
+            cleanup_on_drop: config.cleanup_on_drop,
+            listening: config.listen,
+            suspended: AtomicBool::new(false),
+            quiescing: AtomicUsize::new(0),
+            own_events: Mutex::new(OwnEvents::default()),
+            tagged_subscribers: Mutex::new(Vec::new()),
+            suppress_own_events: config.suppress_own_events,
+            pending: Mutex::new(Vec::new()),
+            #[cfg(feature = "tokio")]
+            bridge: crate::tokio_support::Bridge::default(),
+            validate_gateway: config.validate_gateway,
+            dry_run: config.dry_run,
+            planned: Mutex::new(Vec::new()),
+            subscriber_buffer: config.subscriber_buffer,
+            threads: config.threads,
         };
 
         Ok(manager)
     }
 
-    #[cfg(not(windows))]
-    pub fn new() -> io::Result<Self> {
-        Err(io::Error::new(io::ErrorKind::Other, "None windows system not supported"))
-    }
-
     /// Driven subscribe event, you should run in separate thread or async task
     /// # Examples
     ///
     /// ```rust ignore
     /// use std::sync::Arc;
     /// use winroute::{Route, RouteManager};
-    /// 
+    ///
     /// let manager = Arc::new(RouteManager::new());
     /// let poll = manager.clone();
     /// ```
@@ -128,117 +340,3461 @@ impl RouteManager {
     /// ```
     ///
     /// # Errors
-    /// When Mutex return error while invoke lock() or channel producer send data occurs error
-    pub fn poll(&self) -> Result<(), Box<dyn Error>> {
-        let event: RouteEvent = self.operator_receiver.recv()?;
-        {
-            if let Ok(guard) = self.routes.lock() {
-                let mut routes = guard.borrow_mut();
-                match event.clone() {
-                    RouteEvent::Add(route) => routes.push(route),
-                    RouteEvent::Delete(route) => {
-                        if let Some(index) = routes.iter().position(|v| *v == route) {
-                            routes.remove(index);
-                        }
-                    }
-                    RouteEvent::Change(route) => {
-                        if let Some(index) = routes.iter().position(|v| {
-                            v.destination == route.destination && v.prefix == route.prefix
-                        }) {
-                            routes.remove(index);
-                            routes.push(route);
-                        }
-                    }
-                }
-            } else {
-                return Err(Box::new(PoisonError::new(
-                    "Can not lock private field routes",
-                )));
-            }
+    /// See ```PollError``` for what each error means and how to recover from it
+    pub fn poll(&self) -> Result<(), PollError> {
+        self.poll_event()?;
+        Ok(())
+    }
+
+    /// Same as ```poll```, but also return the processed event, so that a single consumer
+    /// can drive the loop and react to events without subscribing
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// loop {
+    ///     let event = manager.poll_event().unwrap();
+    ///     println!("{event}");
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    /// See ```PollError``` for what each error means and how to recover from it
+    pub fn poll_event(&self) -> Result<RouteEvent, PollError> {
+        Ok(self.poll_timed()?.event)
+    }
+
+    /// Same as ```poll_event```, but also return when backend observed the event
+    ///
+    /// # Errors
+    /// See ```PollError``` for what each error means and how to recover from it
+    pub fn poll_timed(&self) -> Result<TimedEvent, PollError> {
+        if !self.listening || self.is_shut_down() {
+            return Err(PollError::Disconnected);
         }
-        if let Err(e) = self.producer.send(event.clone()) {
-            return Err(Box::new(e));
+        crossbeam_channel::select! {
+            recv(self.operator_receiver) -> timed => self.handle(timed?),
+            recv(self.closed) -> _ => Err(PollError::Disconnected),
         }
-        Ok(())
     }
 
-    /// Subscribe routing table change event
+    /// Process an event if one is pending, return `None` without blocking otherwise
     ///
-    /// Return a Receiver, use .recv() method to receive RouteEvent
-    pub fn subscribe_route_change(&self) -> Receiver<RouteEvent> {
-        self.subscribers.clone()
+    /// # Errors
+    /// See ```PollError``` for what each error means and how to recover from it
+    pub fn try_poll(&self) -> Result<Option<RouteEvent>, PollError> {
+        self.poll_within(Duration::ZERO)
     }
 
-    /// Get system routing table, include IPv6 and IPv4 routes
+    /// Same as ```poll_event```, but return `None` when no event arrived within timeout, so
+    /// callers can run their own loop cadence and shutdown checks
+    ///
+    /// ```rust no_run
+    /// use std::time::Duration;
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// while !manager.is_shut_down() {
+    ///     if let Some(event) = manager.poll_timeout(Duration::from_millis(500)).unwrap() {
+    ///         println!("{event}");
+    ///     }
+    /// }
+    /// ```
     ///
     /// # Errors
-    /// When try to lock Mutex and it return an error
-    pub fn routes(&self) -> io::Result<Vec<Route>> {
-        if let Ok(guard) = self.routes.lock() {
-            Ok(guard.borrow_mut().clone())
-        } else {
-            Err(io::Error::new(io::ErrorKind::Other, "Can not lock inner data, this is a thread safe error"))
+    /// See ```PollError``` for what each error means and how to recover from it
+    pub fn poll_timeout(&self, timeout: Duration) -> Result<Option<RouteEvent>, PollError> {
+        self.poll_within(timeout)
+    }
+
+    /// Wait for an event, then process it together with every event queued behind it in one
+    /// pass, so storms of route changes take one wakeup and one cache lock
+    ///
+    /// Subscriptions losing events to their overflow policy are not reported, they can catch
+    /// up with ```events_since```
+    ///
+    /// # Errors
+    /// See ```PollError``` for what each error means and how to recover from it
+    pub fn poll_all(&self) -> Result<Vec<RouteEvent>, PollError> {
+        if !self.listening || self.is_shut_down() {
+            return Err(PollError::Disconnected);
         }
+        let first = crossbeam_channel::select! {
+            recv(self.operator_receiver) -> timed => timed?,
+            recv(self.closed) -> _ => return Err(PollError::Disconnected),
+        };
+        self.process_queued(Some(first))
     }
 
-    /// Add a new route to system's routing table
+    /// Process every queued event in one pass without blocking, see ```poll_all```
     ///
-    /// # NOTICE
+    /// # Errors
+    /// See ```PollError``` for what each error means and how to recover from it
+    pub fn drain(&self) -> Result<Vec<RouteEvent>, PollError> {
+        if !self.listening || self.is_shut_down() {
+            return Err(PollError::Disconnected);
+        }
+        self.process_queued(None)
+    }
+
+    fn process_queued(&self, first: Option<TimedEvent>) -> Result<Vec<RouteEvent>, PollError> {
+        let events: Vec<TimedEvent> = first
+            .into_iter()
+            .chain(self.operator_receiver.try_iter())
+            .collect();
+        self.process_batch(&events)?;
+        self.recover_lost();
+        Ok(events.into_iter().map(|timed| timed.event).collect())
+    }
+
+    fn poll_within(&self, timeout: Duration) -> Result<Option<RouteEvent>, PollError> {
+        if !self.listening || self.is_shut_down() {
+            return Err(PollError::Disconnected);
+        }
+        crossbeam_channel::select! {
+            recv(self.operator_receiver) -> timed => Ok(Some(self.handle(timed?)?.event)),
+            recv(self.closed) -> _ => Err(PollError::Disconnected),
+            default(timeout) => Ok(None),
+        }
+    }
+
+    /// Receiver of backend events, `None` when listener is disabled
+    pub(crate) fn backend_events(&self) -> Option<&Receiver<TimedEvent>> {
+        self.listening.then_some(&self.operator_receiver)
+    }
+
+    /// Receiver disconnected by ```shutdown```, for threads driving the manager to select on
+    pub(crate) fn closed(&self) -> &Receiver<()> {
+        &self.closed
+    }
+
+    /// Whether ```shutdown``` was called, polling returns ```PollError::Disconnected``` since
+    pub fn is_shut_down(&self) -> bool {
+        self.closing
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_none()
+    }
+
+    #[cfg(feature = "tokio")]
+    pub(crate) fn bridge(&self) -> &crate::tokio_support::Bridge {
+        &self.bridge
+    }
+
+    /// Process event received from backend
+    pub(crate) fn handle(&self, timed: TimedEvent) -> Result<TimedEvent, PollError> {
+        let dropped = self.process(&timed.event, timed.timestamp)?;
+        self.recover_lost();
+        match dropped {
+            0 => Ok(timed),
+            dropped => Err(PollError::Lagged {
+                event: Box::new(timed),
+                dropped,
+            }),
+        }
+    }
+
+    /// Read the table again when backend or coalesce buffer discarded events, so the cache
+    /// does not drift, the difference is delivered like system events. A failed read is
+    /// retried on next poll
+    fn recover_lost(&self) {
+        let mut lost = self.operator.take_lost_events();
+        if let Some(coalesce_lost) = &self.coalesce_lost {
+            lost += coalesce_lost.swap(0, Ordering::SeqCst);
+        }
+        let lost = self.lost_events.fetch_add(lost, Ordering::SeqCst) + lost;
+        if lost > 0 && self.resync(true).is_ok() {
+            self.lost_events.fetch_sub(lost, Ordering::SeqCst);
+        }
+    }
+
+    /// Shut the manager down, deleting all routes added through it when it was built with
+    /// ```cleanup_on_drop```
     ///
-    /// if ```add_route``` is called by a user that is not a administrator or root, the function will fail and return ERROR_ACCESS_DENIED
+    /// Pending and later ```poll``` calls return ```PollError::Disconnected```, so the thread
+    /// driving the manager can exit, threads of ```spawn_poller``` end as well
     ///
     /// # Errors
-    /// when system api return error
-    pub fn add_route(&self, route: &Route) -> io::Result<()> {
-        self.operator.add_route(route)?;
+    /// When deleting an owned route fails, remaining routes are still deleted
+    pub fn shutdown(&self) -> io::Result<()> {
+        self.closing
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if self.cleanup_on_drop {
+            self.remove_owned_routes()?;
+        }
         Ok(())
     }
 
-    /// Remove route from system's routing table
+    /// Stop delivering events to subscribers, e.g. while a service is paused
     ///
-    /// # NOTICE
+    /// Polling keeps the cached table, statistics and history up to date, so events missed
+    /// meanwhile can be caught up with ```events_since``` after ```resume```
+    pub fn suspend(&self) {
+        self.suspended.store(true, Ordering::SeqCst);
+    }
+
+    /// Deliver events to subscribers again after ```suspend```
+    pub fn resume(&self) {
+        self.suspended.store(false, Ordering::SeqCst);
+    }
+
+    /// Whether event delivery is suspended
+    pub fn is_suspended(&self) -> bool {
+        self.suspended.load(Ordering::SeqCst)
+    }
+
+    /// Run f without notifying subscribers of the events its own changes cause, so that
+    /// reconciling subscribers do not react to the manager's bulk work
     ///
-    /// if ```delete_route``` is called by a user that is not a administrator or root, the function will fail and return ERROR_ACCESS_DENIED
+    /// Routes added, deleted or changed through this manager while f runs are remembered, and
+    /// the matching events are still applied to cache, statistics and history but not delivered
+    /// when ```poll``` processes them, even after f returned. Changes made by others are
+    /// delivered as usual
     ///
-    /// # Errors
-    /// when system api return error
-    pub fn delete_route(&self, route: &Route) -> io::Result<()> {
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// manager.quiesce(|| {
+    ///     for prefix in 8..16 {
+    ///         let _ = manager.add_route(&Route::new("10.0.0.0".parse().unwrap(), prefix));
+    ///     }
+    /// });
+    /// ```
+    pub fn quiesce<F, T>(&self, f: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        /// Leave quiet mode even when `f` panics
+        struct Quiet<'a>(&'a AtomicUsize);
+
+        impl Drop for Quiet<'_> {
+            fn drop(&mut self) {
+                self.0.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+
+        self.quiescing.fetch_add(1, Ordering::SeqCst);
+        let _quiet = Quiet(&self.quiescing);
+        f()
+    }
+
+    /// Remember event caused by this manager, to tag it when backend reports it and not to
+    /// deliver it when quiescing
+    pub(crate) fn note_own(&self, event: RouteEvent) {
+        if !self.listening {
+            return;
+        }
+        let quiet = self.quiescing.load(Ordering::SeqCst) > 0;
+        if let Ok(mut own) = self.own_events.lock() {
+            own.note(event, quiet);
+        }
+    }
+
+    /// Whether event was caused by this manager and whether it was noted while quiescing,
+    /// forgetting it when so
+    fn take_own(&self, event: &RouteEvent) -> Option<bool> {
+        self.own_events.lock().ok()?.take(event)
+    }
+
+    fn remove_owned_routes(&self) -> io::Result<()> {
+        let owned = std::mem::take(&mut *self.lock_owned()?);
+        let mut result = Ok(());
+        for route in owned.iter().rev() {
+            if let Err(e) = self.operator.delete_route(route) {
+                if e.kind() != io::ErrorKind::NotFound && result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+
+    /// Add route to system and remember it as owned by this manager
+    pub(crate) fn install(&self, route: &Route) -> io::Result<Route> {
+        check_prefix_len(route)?;
+        if self.validate_gateway {
+            self.check_gateway(route)?;
+        }
+        if self.dry_run {
+            return self.plan(route, RouteEvent::Add);
+        }
+        let installed = self.operator.add_route(route)?;
+        self.note_own(RouteEvent::Add(installed.clone()));
+        self.lock_owned()?.push(installed.clone());
+        Ok(installed)
+    }
+
+    /// Delete route from system and forget it if it was owned by this manager
+    pub(crate) fn remove(&self, route: &Route) -> io::Result<()> {
+        if self.dry_run {
+            return self.plan(route, RouteEvent::Delete).map(|_| ());
+        }
         self.operator.delete_route(route)?;
+        self.note_own(RouteEvent::Delete(route.clone()));
+        self.lock_owned()?.retain(|r| !r.is_same_entry(route));
         Ok(())
     }
 
-    /// return default route
-    /// 
+    /// Hand a chunk of routes to backend at once, or plan them one by one in dry run mode,
+    /// callers take ownership of the installed routes
+    pub(crate) fn install_chunk(&self, routes: &[Route]) -> Vec<io::Result<Route>> {
+        let checked: Vec<io::Result<()>> = routes.iter().map(check_prefix_len).collect();
+        if checked.iter().any(|c| c.is_err()) {
+            let valid: Vec<Route> = routes
+                .iter()
+                .zip(&checked)
+                .filter(|(_, c)| c.is_ok())
+                .map(|(route, _)| route.clone())
+                .collect();
+            let mut installed = self.install_chunk(&valid).into_iter();
+            return checked
+                .into_iter()
+                .map(|c| {
+                    c.and_then(|()| {
+                        installed
+                            .next()
+                            .unwrap_or_else(|| Err(io::Error::other("backend skipped a route")))
+                    })
+                })
+                .collect();
+        }
+        if self.dry_run {
+            return routes
+                .iter()
+                .map(|route| self.plan(route, RouteEvent::Add))
+                .collect();
+        }
+        self.operator.add_routes(routes)
+    }
+
+    /// Add back a route that was deleted through the manager, without taking ownership of it
+    pub(crate) fn restore(&self, route: &Route) -> io::Result<()> {
+        if self.dry_run {
+            return self.plan(route, RouteEvent::Add).map(|_| ());
+        }
+        let restored = self.operator.add_route(route)?;
+        self.note_own(RouteEvent::Add(restored));
+        Ok(())
+    }
+
+    /// Check a change the way system would, against interfaces and the cached table with the
+    /// changes planned so far applied, and record it instead of applying it, return the route
+    /// as system would have it
+    fn plan(&self, route: &Route, change: fn(Route) -> RouteEvent) -> io::Result<Route> {
+        if Family::of(&route.gateway) != route.family() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "gateway {} is not in family of {}",
+                    route.gateway,
+                    route.cidr()
+                ),
+            ));
+        }
+        let mut route = route.clone();
+        if route.ifindex.is_none() && route.luid.is_none() {
+            route.ifindex = Some(self.operator.best_interface(route.gateway)?);
+        }
+        match self.operator.interfaces() {
+            Ok(interfaces) => {
+                let exists = interfaces.iter().any(|i| {
+                    route.ifindex.is_none_or(|ifindex| ifindex == i.ifindex)
+                        && route.luid.is_none_or(|luid| luid == i.luid)
+                });
+                if !exists {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("no interface for route {}", route),
+                    ));
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => {}
+            Err(e) => return Err(e),
+        }
+        let mut planned_changes = self.planned.lock().map_err(|_| {
+            io::Error::other("Can not lock inner data, this is a thread safe error")
+        })?;
+        let mut table = self.routes()?;
+        for event in planned_changes.iter() {
+            apply(&mut table, event);
+        }
+        let existing = table.into_iter().find(|r| r.is_same_entry(&route));
+        let requested = change(route.clone());
+        let adding = matches!(requested, RouteEvent::Add(_));
+        let planned = match existing {
+            Some(_) if adding => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("route {} already exists", route),
+                ))
+            }
+            None if adding => route,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no route {}", route),
+                ))
+            }
+            Some(mut existing) => {
+                if matches!(requested, RouteEvent::Change(_)) {
+                    existing.metric = route.metric;
+                }
+                existing
+            }
+        };
+        planned_changes.push(change(planned.clone()));
+        Ok(planned)
+    }
+
+    /// Whether manager was built with ```RouteManagerBuilder::dry_run```
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Changes recorded in dry run mode, in the order they were requested
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::builder().dry_run(true).build().unwrap();
+    /// manager
+    ///     .add_route(&Route::new("10.0.0.0".parse().unwrap(), 8).ifindex(12))
+    ///     .unwrap();
+    /// for change in manager.planned_changes().unwrap() {
+    ///     println!("would {change}");
+    /// }
+    /// ```
+    ///
     /// # Errors
     /// When try to lock Mutex and it return an error
-    pub fn default_route(&self) -> io::Result<Option<Route>> {
-        if let Ok(guard) = self.routes.lock() {
-            let guard = guard.borrow_mut();
-            let itr = guard.iter();
-            for route in itr {
-                if (route.destination == Ipv4Addr::UNSPECIFIED
-                    || route.destination == Ipv6Addr::UNSPECIFIED)
-                    && route.gateway != IpAddr::V4(Ipv4Addr::UNSPECIFIED)
-                    && route.gateway != IpAddr::V6(Ipv6Addr::UNSPECIFIED)
-                    && route.prefix == 0
-                {
-                    return Ok(Some(route.clone()));
+    pub fn planned_changes(&self) -> io::Result<Vec<RouteEvent>> {
+        self.planned
+            .lock()
+            .map(|planned| planned.clone())
+            .map_err(|_| io::Error::other("Can not lock inner data, this is a thread safe error"))
+    }
+
+    pub(crate) fn lock_pending(
+        &self,
+    ) -> io::Result<MutexGuard<'_, Vec<(Route, InterfaceSelector)>>> {
+        self.pending
+            .lock()
+            .map_err(|_| io::Error::other("Can not lock inner data, this is a thread safe error"))
+    }
+
+    pub(crate) fn lock_owned(&self) -> io::Result<MutexGuard<'_, Vec<Route>>> {
+        self.owned
+            .lock()
+            .map_err(|_| io::Error::other("Can not lock inner data, this is a thread safe error"))
+    }
+
+    /// Iterator that drives ```poll_event``` and yields every processed event, for the common
+    /// single consumer case where no separate poll thread is needed
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// for event in manager.events() {
+    ///     println!("{event}");
+    /// }
+    /// ```
+    ///
+    /// Iteration ends when polling fails
+    pub fn events(&self) -> Events<'_, O> {
+        Events { manager: self }
+    }
+
+    /// Apply event to cache, history and subscribers, return how many subscriptions lost an
+    /// event to their overflow policy
+    fn process(&self, event: &RouteEvent, timestamp: Timestamp) -> Result<usize, PollError> {
+        let entries = {
+            let guard = self.lock_routes()?;
+            let mut routes = guard.borrow_mut();
+            if apply(&mut routes, event) {
+                self.index.lock()?.apply(event);
+            }
+            self.applied.fetch_add(1, Ordering::Release);
+            routes.len()
+        };
+        self.dispatch(event, timestamp, entries)
+    }
+
+    /// Same as ```process``` for several events, applying all of them to the cache under a
+    /// single lock
+    fn process_batch(&self, events: &[TimedEvent]) -> Result<usize, PollError> {
+        let entries: Vec<usize> = {
+            let guard = self.lock_routes()?;
+            let mut routes = guard.borrow_mut();
+            let mut index = self.index.lock()?;
+            events
+                .iter()
+                .map(|timed| {
+                    if apply(&mut routes, &timed.event) {
+                        index.apply(&timed.event);
+                    }
+                    self.applied.fetch_add(1, Ordering::Release);
+                    routes.len()
+                })
+                .collect()
+        };
+        let mut dropped = 0;
+        for (timed, entries) in events.iter().zip(entries) {
+            dropped += self.dispatch(&timed.event, timed.timestamp, entries)?;
+        }
+        Ok(dropped)
+    }
+
+    fn lock_routes(&self) -> Result<MutexGuard<'_, RefCell<Vec<Route>>>, PollError> {
+        self.routes
+            .lock()
+            .map_err(|_| PollError::Internal("Can not lock private field routes".to_string()))
+    }
+
+    /// Everything but the cache update of ```process```, `entries` is the table size after
+    /// event was applied
+    fn dispatch(
+        &self,
+        event: &RouteEvent,
+        timestamp: Timestamp,
+        entries: usize,
+    ) -> Result<usize, PollError> {
+        if let Ok(mut stats) = self.stats.lock() {
+            stats.record(event);
+        }
+        self.update_route_watchers(event);
+        self.check_tamper(event);
+        self.install_pending(event);
+        let alerts = match self.watermarks.lock() {
+            Ok(mut watermarks) => watermarks.check(entries),
+            Err(_) => Vec::new(),
+        };
+        if !alerts.is_empty() {
+            if let Ok(mut subscribers) = self.alert_subscribers.lock() {
+                for alert in alerts {
+                    subscribers.retain(|s| s.send(alert.clone()).is_ok());
                 }
             }
-        } else {
-            return Err(io::Error::new(
-                io::ErrorKind::NotFound,
-                "can not found defualt route",
-            ));
         }
-        Ok(None)
+        self.history.lock()?.push(event.clone(), timestamp);
+        let own = self.take_own(event);
+        if self.suspended.load(Ordering::SeqCst) || own == Some(true) {
+            return Ok(0);
+        }
+        self.deliver_tagged(event, own.is_some());
+        if own.is_some() && self.suppress_own_events {
+            return Ok(0);
+        }
+        let mut dropped = 0;
+        self.dedicated_subscribers
+            .lock()?
+            .retain(|s| match s.deliver(event) {
+                Delivery::Delivered => true,
+                Delivery::Dropped => {
+                    dropped += 1;
+                    true
+                }
+                Delivery::Closed => false,
+            });
+        Ok(dropped)
     }
-}
 
-impl Drop for RouteManager {
-    fn drop(&mut self) {}
-}
+    fn deliver_tagged(&self, event: &RouteEvent, own: bool) {
+        let Ok(mut subscribers) = self.tagged_subscribers.lock() else {
+            return;
+        };
+        if subscribers.is_empty() {
+            return;
+        }
+        let tagged = TaggedEvent {
+            event: event.clone(),
+            origin: if own {
+                Origin::SelfOriginated
+            } else {
+                Origin::External
+            },
+        };
+        subscribers.retain(|s| s.send(tagged.clone()).is_ok());
+    }
+
+    /// Notify tamper subscribers when event is someone else's change to an owned route
+    fn check_tamper(&self, event: &RouteEvent) {
+        if matches!(event, RouteEvent::Add(_)) {
+            return;
+        }
+        let Ok(mut owned) = self.owned.lock() else {
+            return;
+        };
+        let Some(tampered) = tamper::detect(&mut owned, event) else {
+            return;
+        };
+        drop(owned);
+        if let Ok(mut subscribers) = self.tamper_subscribers.lock() {
+            subscribers.retain(|s| s.send(tampered.clone()).is_ok());
+        }
+    }
+
+    /// Recompute best routes of watched destinations the event may affect
+    fn update_route_watchers(&self, event: &RouteEvent) {
+        let Some(route) = event.route() else {
+            return;
+        };
+        if let Ok(mut watchers) = self.route_watchers.lock() {
+            if watchers.iter().any(|w| route.contains(w.destination)) {
+                if let Ok(guard) = self.routes.lock() {
+                    let routes = guard.borrow();
+                    watchers.retain_mut(|w| {
+                        !route.contains(w.destination)
+                            || w.update(&routes, |r| self.effective_metric(r))
+                    });
+                }
+            }
+        }
+        // looked up without holding the cache, backends without route lookup search it
+        if let Ok(mut watchers) = self.resolved_watchers.lock() {
+            watchers.retain_mut(|w| {
+                if !route.contains(w.destination) {
+                    return true;
+                }
+                match self.resolved_route(w.destination) {
+                    Ok(current) => w.update(current),
+                    // lookup failed, selection is compared again on next event
+                    Err(_) => true,
+                }
+            });
+        }
+    }
 
-unsafe impl Sync for RouteManager {}
+    /// Events processed by ```poll``` after sequence number `seq`, ordered by sequence number
+    ///
+    /// Return `None` when some of these events were already dropped from retained history,
+    /// consumer should fall back to reading the whole table with ```routes```
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn events_since(&self, seq: u64) -> io::Result<Option<Vec<SequencedEvent>>> {
+        Ok(self.lock_history()?.since(seq))
+    }
 
-unsafe impl Send for RouteManager {}
+    /// Retained events processed at or after `time`
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn events_since_time(&self, time: SystemTime) -> io::Result<Vec<SequencedEvent>> {
+        Ok(self.lock_history()?.since_time(time))
+    }
+
+    /// Sequence number of the last processed event, `0` if none was processed yet
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn last_sequence(&self) -> io::Result<u64> {
+        Ok(self.lock_history()?.last_seq())
+    }
+
+    /// Route change statistics of events processed so far, `top` limits how many most
+    /// churned prefixes are reported
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn statistics(&self, top: usize) -> io::Result<RouteStatistics> {
+        self.stats
+            .lock()
+            .map(|stats| stats.snapshot(top))
+            .map_err(|_| io::Error::other("Can not lock inner data, this is a thread safe error"))
+    }
+
+    /// Set how many processed events are retained for ```events_since```, default is 1024
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn set_history_capacity(&self, capacity: usize) -> io::Result<()> {
+        self.lock_history()?.set_capacity(capacity);
+        Ok(())
+    }
+
+    fn lock_history(&self) -> io::Result<MutexGuard<'_, EventHistory>> {
+        self.history
+            .lock()
+            .map_err(|_| io::Error::other("Can not lock inner data, this is a thread safe error"))
+    }
+
+    /// Subscribe routing table change event
+    ///
+    /// Return a Subscription, use .recv() method to receive RouteEvent. Every subscription gets
+    /// its own buffer, see ```RouteManagerBuilder::subscriber_buffer```, and sees every event
+    /// polled after it subscribed, dropping it or calling ```Subscription::unsubscribe```
+    /// detaches it from the manager
+    pub fn subscribe_route_change(&self) -> Subscription {
+        let (subscriber, subscription) = self.new_subscriber();
+        self.dedicated_subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(subscriber);
+        subscription
+    }
+
+    /// Subscribe routing table change event with a dedicated buffer
+    ///
+    /// Every subscription created this way receives every event, up to `capacity` events are
+    /// buffered and `policy` decides what happens when subscriber falls behind
+    ///
+    /// # Errors
+    /// ErrorKind::InvalidInput when capacity is 0, or when try to lock Mutex and it return an
+    /// error
+    pub fn subscribe_with(
+        &self,
+        capacity: usize,
+        policy: OverflowPolicy,
+    ) -> io::Result<Subscription> {
+        if capacity == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "subscription buffer must hold at least one event",
+            ));
+        }
+        self.register_subscriber(Some(capacity), policy)
+    }
+
+    /// Subscribe routing table change event with a dedicated buffer, unbounded unless
+    /// ```RouteManagerBuilder::subscriber_buffer``` is set
+    ///
+    /// Subscription can be iterated directly, iteration ends when manager is dropped
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// for event in manager.subscribe().unwrap() {
+    ///     println!("{:?}", event);
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn subscribe(&self) -> io::Result<Subscription> {
+        let (capacity, policy) = self.subscriber_buffer;
+        self.register_subscriber(capacity, policy)
+    }
+
+    /// Subscribe routing table change event for the duration of `scope` only
+    ///
+    /// Subscription is deregistered as soon as `scope` returns instead of on the next event,
+    /// so short lived watchers such as request handlers leave nothing behind
+    ///
+    /// ```rust no_run
+    /// use std::time::Duration;
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// let added = manager
+    ///     .subscribe_scoped(|events| {
+    ///         matches!(events.recv_timeout(Duration::from_secs(5)), Ok(RouteEvent::Add(_)))
+    ///     })
+    ///     .unwrap();
+    /// ```
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn subscribe_scoped<F, T>(&self, scope: F) -> io::Result<T>
+    where
+        F: FnOnce(&Subscription) -> T,
+    {
+        let subscription = self.register_subscriber(None, OverflowPolicy::Block)?;
+        let result = scope(&subscription);
+        drop(subscription);
+        self.dedicated_subscribers
+            .lock()
+            .map_err(|_| io::Error::other("Can not lock inner data, this is a thread safe error"))?
+            .retain(Subscriber::is_alive);
+        Ok(result)
+    }
+
+    /// Subscribe routing table change event in batches of at most `max_batch` events
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// for batch in manager.subscribe_batched(64).unwrap().iter() {
+    ///     println!("{} events", batch.len());
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn subscribe_batched(&self, max_batch: usize) -> io::Result<BatchSubscription> {
+        let subscription = self.register_subscriber(None, OverflowPolicy::Block)?;
+        Ok(BatchSubscription::new(subscription, max_batch))
+    }
+
+    /// Subscribe diagnostic alerts emitted when routing table crosses a watermark configured
+    /// with ```RouteManagerBuilder::size_watermark``` or ```growth_watermark```
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn subscribe_alerts(&self) -> io::Result<Receiver<TableAlert>> {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        self.alert_subscribers
+            .lock()
+            .map_err(|_| io::Error::other("Can not lock inner data, this is a thread safe error"))?
+            .push(sender);
+        Ok(receiver)
+    }
+
+    /// Subscribe changes and deletions made by others to routes this manager installed
+    ///
+    /// Changes made through this manager are not reported, neither are routes added by others
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// let tampered = manager.subscribe_tamper().unwrap();
+    /// for event in tampered {
+    ///     eprintln!("{event}");
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn subscribe_tamper(&self) -> io::Result<Receiver<OwnedRouteEvent>> {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        self.tamper_subscribers
+            .lock()
+            .map_err(|_| io::Error::other("Can not lock inner data, this is a thread safe error"))?
+            .push(sender);
+        Ok(receiver)
+    }
+
+    /// Subscribe routing table change event tagged with whether this manager caused it
+    ///
+    /// Changes made through the manager are correlated with the notifications backend reports
+    /// for them, so applications can ignore their own changes without heuristics
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// let events = manager.subscribe_tagged().unwrap();
+    /// for tagged in events.iter().filter(|t| t.origin == Origin::External) {
+    ///     println!("{}", tagged.event);
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn subscribe_tagged(&self) -> io::Result<Receiver<TaggedEvent>> {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        self.tagged_subscribers
+            .lock()
+            .map_err(|_| io::Error::other("Can not lock inner data, this is a thread safe error"))?
+            .push(sender);
+        Ok(receiver)
+    }
+
+    /// Subscribe routing table change event with a dedicated buffer, only receiving events
+    /// filter accepts
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn subscribe_filtered<F>(&self, filter: F) -> io::Result<Subscription>
+    where
+        F: Fn(&RouteEvent) -> bool + Send + 'static,
+    {
+        let (subscriber, subscription) = self.new_subscriber();
+        self.push_subscriber(subscriber.filter(Box::new(filter)))?;
+        Ok(subscription)
+    }
+
+    /// Subscribe routing table change event without the churn of loopback, multicast,
+    /// broadcast and link-local routes, see ```Route::is_noise```
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// for event in manager.subscribe_quiet().unwrap() {
+    ///     println!("{event}");
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn subscribe_quiet(&self) -> io::Result<Subscription> {
+        self.subscribe_filtered(|event| !event.route().is_some_and(Route::is_noise))
+    }
+
+    /// Subscribe routing table change event of IPv4 routes only
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn subscribe_route_change_v4(&self) -> io::Result<Subscription> {
+        self.subscribe_filtered(|event| event.route().is_none_or(|r| r.destination.is_ipv4()))
+    }
+
+    /// Subscribe routing table change event of IPv6 routes only, e.g. to follow autoconf
+    /// without IPv4 traffic
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn subscribe_route_change_v6(&self) -> io::Result<Subscription> {
+        self.subscribe_filtered(|event| event.route().is_none_or(|r| r.destination.is_ipv6()))
+    }
+
+    /// Subscribe routing table change event of routes whose destination equals or lies within
+    /// `network/len`, `0.0.0.0/0` delivers every IPv4 event
+    ///
+    /// # Errors
+    /// ErrorKind::InvalidInput when `network/len` is not a valid prefix, or when try to lock
+    /// Mutex and it return an error
+    pub fn subscribe_prefix(&self, network: IpAddr, len: u8) -> io::Result<Subscription> {
+        let prefix = Prefix::new(network, len)?;
+        self.subscribe_filtered(move |event| event.route().is_none_or(|r| prefix.covers(r)))
+    }
+
+    /// Subscribe routing table change event of routes on the interface with ifindex only
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn subscribe_for_interface(&self, ifindex: u32) -> io::Result<Subscription> {
+        self.subscribe_for_interfaces(&[ifindex])
+    }
+
+    /// Subscribe routing table change event of routes on any of the interfaces, e.g. a tunnel
+    /// adapter and the physical uplink
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn subscribe_for_interfaces(&self, ifindexes: &[u32]) -> io::Result<Subscription> {
+        let ifindexes = ifindexes.to_vec();
+        self.subscribe_filtered(move |event| match event.route() {
+            Some(route) => route
+                .ifindex
+                .is_some_and(|ifindex| ifindexes.contains(&ifindex)),
+            None => true,
+        })
+    }
+
+    /// Subscribe routing table change event of routes on the interface with luid only
+    ///
+    /// Routes carrying only an ifindex are matched through the interface cache
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn subscribe_for_interface_luid(&self, luid: u64) -> io::Result<Subscription> {
+        let ifindex = self.interface_by_luid(luid).ok().map(|i| i.ifindex);
+        self.subscribe_filtered(move |event| {
+            let Some(route) = event.route() else {
+                return true;
+            };
+            route.luid == Some(luid)
+                || (route.luid.is_none() && ifindex.is_some() && route.ifindex == ifindex)
+        })
+    }
+
+    fn register_subscriber(
+        &self,
+        capacity: Option<usize>,
+        policy: OverflowPolicy,
+    ) -> io::Result<Subscription> {
+        let (subscriber, subscription) = Subscriber::new(capacity, policy);
+        self.push_subscriber(subscriber)?;
+        Ok(subscription)
+    }
+
+    /// Subscriber with the buffer configured by ```RouteManagerBuilder::subscriber_buffer```
+    pub(crate) fn new_subscriber(&self) -> (Subscriber, Subscription) {
+        let (capacity, policy) = self.subscriber_buffer;
+        Subscriber::new(capacity, policy)
+    }
+
+    pub(crate) fn push_subscriber(&self, subscriber: Subscriber) -> io::Result<()> {
+        self.dedicated_subscribers
+            .lock()
+            .map_err(|_| io::Error::other("Can not lock inner data, this is a thread safe error"))?
+            .push(subscriber);
+        Ok(())
+    }
+
+    /// Get system routing table, include IPv6 and IPv4 routes
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn routes(&self) -> io::Result<Vec<Route>> {
+        if !self.listening {
+            return self.operator.read_all_routes();
+        }
+        if let Ok(guard) = self.routes.lock() {
+            Ok(guard.borrow_mut().clone())
+        } else {
+            Err(io::Error::other(
+                "Can not lock inner data, this is a thread safe error",
+            ))
+        }
+    }
+
+    /// Routes of one family, filtered while cached table is locked instead of copying it all
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn routes_by_family(&self, family: Family) -> io::Result<Vec<Route>> {
+        self.scan_routes(|routes| {
+            routes
+                .iter()
+                .filter(|r| r.family() == family)
+                .cloned()
+                .collect()
+        })
+    }
+
+    /// IPv4 routes, see ```routes_by_family```
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn routes_v4(&self) -> io::Result<Vec<Route>> {
+        self.routes_by_family(Family::V4)
+    }
+
+    /// IPv6 routes, see ```routes_by_family```
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn routes_v6(&self) -> io::Result<Vec<Route>> {
+        self.routes_by_family(Family::V6)
+    }
+
+    /// Stable hash of the current routing table, independent of route order
+    ///
+    /// Compare it with a previously returned value to find out whether anything changed
+    /// before requesting the full table with ```routes```
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn routes_fingerprint(&self) -> io::Result<u64> {
+        self.scan_routes(fingerprint)
+    }
+
+    /// Route to exactly `destination/prefix`, the one with the lowest route metric when
+    /// several interfaces or gateways have one, looked up in cached table, or in system when
+    /// manager does not listen
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// if let Some(route) = manager.find_route("0.0.0.0".parse().unwrap(), 0).unwrap() {
+    ///     println!("default gateway is {}", route.gateway);
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn find_route(&self, destination: IpAddr, prefix: u8) -> io::Result<Option<Route>> {
+        self.scan_routes(|routes| {
+            routes
+                .iter()
+                .filter(|r| r.destination == destination && r.prefix == prefix)
+                .min_by_key(|r| r.metric)
+                .cloned()
+        })
+    }
+
+    /// Whether an entry with destination, prefix and gateway of `route` is in routing table,
+    /// interface and luid are compared only when set on `route`
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn route_exists(&self, route: &Route) -> io::Result<bool> {
+        self.scan_routes(|routes| routes.iter().any(|r| r.is_same_entry(route)))
+    }
+
+    /// Longest prefix match for ip over cached table, lowest route metric wins a tie
+    ///
+    /// Cached routes are indexed by prefix, so a lookup takes the same time with thousands of
+    /// routes as with a few. When manager does not listen, system table is read and scanned
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// if let Some(route) = manager.lookup("10.1.2.3".parse().unwrap()).unwrap() {
+    ///     println!("10.1.2.3 matches {}", route.cidr());
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn lookup(&self, ip: IpAddr) -> io::Result<Option<Route>> {
+        if !self.listening {
+            return self.scan_routes(|routes| trace::lookup(routes, ip).cloned());
+        }
+        let index = self.index.lock().map_err(|_| {
+            io::Error::other("Can not lock inner data, this is a thread safe error")
+        })?;
+        Ok(index.lookup(ip).cloned())
+    }
+
+    /// Routes whose network equals or contains prefix, the supernets traffic to prefix
+    /// would otherwise follow, shortest prefix first
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// let split = Prefix::new("10.8.0.0".parse().unwrap(), 16).unwrap();
+    /// for route in manager.routes_covering(split).unwrap() {
+    ///     println!("{} currently carries {}", route.cidr(), split);
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn routes_covering(&self, prefix: Prefix) -> io::Result<Vec<Route>> {
+        if !self.listening {
+            return self.scan_routes(|routes| {
+                let mut found: Vec<Route> = routes
+                    .iter()
+                    .filter(|r| prefix.covered_by(r))
+                    .cloned()
+                    .collect();
+                found.sort_by_key(|r| r.prefix);
+                found
+            });
+        }
+        let index = self.index.lock().map_err(|_| {
+            io::Error::other("Can not lock inner data, this is a thread safe error")
+        })?;
+        Ok(index.covering(&prefix).into_iter().cloned().collect())
+    }
+
+    /// Routes whose network equals or lies within prefix, the subnets that take precedence
+    /// over a route to prefix
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn routes_within(&self, prefix: Prefix) -> io::Result<Vec<Route>> {
+        if !self.listening {
+            return self.scan_routes(|routes| {
+                routes
+                    .iter()
+                    .filter(|r| prefix.covers(r))
+                    .cloned()
+                    .collect()
+            });
+        }
+        let index = self.index.lock().map_err(|_| {
+            io::Error::other("Can not lock inner data, this is a thread safe error")
+        })?;
+        Ok(index.within(&prefix).into_iter().cloned().collect())
+    }
+
+    /// Read the current system row of `destination/prefix` on interface ifindex, on Windows
+    /// through GetIpForwardEntry2, e.g. to see the metric system assigned
+    ///
+    /// The row's gateway is taken from cached table, lowest route metric first when the
+    /// entry exists with several gateways. Without a cached entry, or when manager does not
+    /// listen, the on-link entry (unspecified gateway) is read
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// let route = manager.get_route("10.0.0.0".parse().unwrap(), 8, 12).unwrap();
+    /// println!("metric is {:?}", route.metric);
+    /// ```
+    ///
+    /// # Errors
+    /// ErrorKind::NotFound when system has no such row, or when system api return error
+    pub fn get_route(&self, destination: IpAddr, prefix: u8, ifindex: u32) -> io::Result<Route> {
+        let key = Route::new(destination, prefix).ifindex(ifindex);
+        let cached = if self.listening {
+            self.scan_routes(|routes| {
+                routes
+                    .iter()
+                    .filter(|r| {
+                        r.destination == destination
+                            && r.prefix == prefix
+                            && r.ifindex == Some(ifindex)
+                    })
+                    .min_by_key(|r| r.metric)
+                    .map(|r| r.gateway)
+            })?
+        } else {
+            None
+        };
+        let key = match cached {
+            Some(gateway) => key.gateway(gateway),
+            None => key,
+        };
+        self.operator.read_route(&key)
+    }
+
+    /// Read the full routing table from system and replace the cache with it, in case
+    /// notifications were missed and the cache drifted. Return the events that turn the old
+    /// cache into the new one: deletions, then changes and additions
+    ///
+    /// With `notify`, those events are also processed like system events: delivered to
+    /// subscribers, watchers and history. Without a listener there is no cache and nothing
+    /// is returned
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// let drift = manager.resync(true).unwrap();
+    /// if !drift.is_empty() {
+    ///     println!("cache was off by {} events", drift.len());
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    /// When system api return error or try to lock Mutex and it return an error,
+    /// ErrorKind::Interrupted when events kept being applied while the table was read
+    pub fn resync(&self, notify: bool) -> io::Result<Vec<RouteEvent>> {
+        if !self.listening {
+            return Ok(Vec::new());
+        }
+        let (events, entries) = {
+            // read without the cache lock so polls are not stalled by a slow read, a snapshot
+            // taken while an event was applied may miss it and is read again
+            let mut attempts = 0;
+            let (guard, fresh) = loop {
+                let before = self.applied.load(Ordering::Acquire);
+                let fresh = cacheable(self.operator.read_all_routes()?);
+                let guard = self
+                    .lock_routes()
+                    .map_err(|e| io::Error::other(e.to_string()))?;
+                if self.applied.load(Ordering::Acquire) == before {
+                    break (guard, fresh);
+                }
+                attempts += 1;
+                if attempts == RESYNC_ATTEMPTS {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Interrupted,
+                        "route table kept changing while it was read",
+                    ));
+                }
+            };
+            let entries = fresh.len();
+            let mut routes = guard.borrow_mut();
+            let events = diff(&routes, &fresh);
+            *self.index.lock().map_err(|_| {
+                io::Error::other("Can not lock inner data, this is a thread safe error")
+            })? = RouteTrie::new(&fresh);
+            *routes = fresh;
+            (events, entries)
+        };
+        if notify {
+            let timestamp = Timestamp::now();
+            for event in &events {
+                self.dispatch(event, timestamp, entries)
+                    .map_err(|e| io::Error::other(e.to_string()))?;
+            }
+        }
+        Ok(events)
+    }
+
+    /// Run `scan` over cached table without copying it, or over system table when manager
+    /// does not listen
+    fn scan_routes<T>(&self, scan: impl FnOnce(&[Route]) -> T) -> io::Result<T> {
+        if !self.listening {
+            return Ok(scan(&self.operator.read_all_routes()?));
+        }
+        let guard = self.routes.lock().map_err(|_| {
+            io::Error::other("Can not lock inner data, this is a thread safe error")
+        })?;
+        let routes = guard.borrow();
+        Ok(scan(&routes))
+    }
+
+    /// Every route for destination and prefix across interfaces, sorted by effective metric
+    /// (route metric plus interface metric), the first one is flagged as selected
+    ///
+    /// Explains why traffic is not using the expected path
+    ///
+    /// # Errors
+    /// When reading routes or interface metrics fails
+    pub fn competitors(&self, prefix: Prefix) -> io::Result<Vec<Competitor>> {
+        self.rank(self.routes()?, prefix.network(), prefix.prefix_len())
+    }
+
+    fn rank(
+        &self,
+        routes: Vec<Route>,
+        destination: IpAddr,
+        prefix: u8,
+    ) -> io::Result<Vec<Competitor>> {
+        let mut competitors = Vec::new();
+        for route in routes {
+            if route.destination != destination || route.prefix != prefix {
+                continue;
+            }
+            let interface_metric = match route.ifindex {
+                Some(idx) => self.interface_metric(route.family(), idx)?,
+                None => 0,
+            };
+            competitors.push(Competitor {
+                effective_metric: route.metric.unwrap_or(0).saturating_add(interface_metric),
+                interface_metric,
+                route,
+                selected: false,
+            });
+        }
+        competitors.sort_by_key(|c| c.effective_metric);
+        if let Some(winner) = competitors.first_mut() {
+            winner.selected = true;
+        }
+        Ok(competitors)
+    }
+
+    /// Add a new route to system's routing table
+    ///
+    /// # NOTICE
+    ///
+    /// if ```add_route``` is called by a user that is not a administrator or root, the function will fail and return ERROR_ACCESS_DENIED
+    ///
+    /// # Errors
+    /// when system api return error
+    pub fn add_route(&self, route: &Route) -> io::Result<()> {
+        self.install(route)?;
+        Ok(())
+    }
+
+    /// Add a new route with metric chosen by `metric`, return the route as submitted
+    ///
+    /// With ```Metric::Auto``` the effective metric (route metric plus interface metric) of every
+    /// route with the same destination and prefix is compared, and a route metric is picked that
+    /// makes the new route preferred or less preferred than all of them. With
+    /// ```Metric::LikeWindows``` the metric Windows would give an interface of the same link
+    /// speed is used. When ifindex is not set, the best interface for gateway is used
+    ///
+    /// # Errors
+    /// when system api return error, or ErrorKind::InvalidInput when new route can not be
+    /// preferred because its interface metric is not lower than competitors' effective metric
+    pub fn add_route_with_metric(&self, route: &Route, metric: Metric) -> io::Result<Route> {
+        let route = match metric {
+            Metric::Fixed(metric) => route.clone().metric(metric),
+            Metric::LikeWindows => {
+                let ifindex = self.route_ifindex(route)?;
+                let speed = self.operator.interface_link_speed(ifindex)?;
+                route.clone().ifindex(ifindex).metric(windows_metric(speed))
+            }
+            Metric::Auto(preference) => {
+                let ifindex = self.route_ifindex(route)?;
+                let family = route.family();
+                let competitors: Vec<u32> = self
+                    .rank(
+                        self.operator.read_all_routes()?,
+                        route.destination,
+                        route.prefix,
+                    )?
+                    .iter()
+                    .map(|c| c.effective_metric)
+                    .collect();
+                let interface_metric = self.interface_metric(family, ifindex)?;
+                let metric =
+                    auto_metric(preference, interface_metric, &competitors).ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!(
+                                "interface {} metric {} is too high to prefer {}",
+                                ifindex,
+                                interface_metric,
+                                route.cidr()
+                            ),
+                        )
+                    })?;
+                route.clone().ifindex(ifindex).metric(metric)
+            }
+        };
+        self.install(&route)
+    }
+
+    /// Interface of route, the best interface for gateway when not set
+    pub(crate) fn route_ifindex(&self, route: &Route) -> io::Result<u32> {
+        match route.ifindex {
+            Some(ifindex) => Ok(ifindex),
+            None => self.operator.best_interface(route.gateway),
+        }
+    }
+
+    /// Add a new route and return it as installed by system
+    ///
+    /// When neither ifindex nor luid is set the best interface for gateway is chosen, the
+    /// returned route is read back from system so it carries the actual ifindex, luid and metric
+    ///
+    /// # Errors
+    /// when system api return error
+    pub fn add_route_resolved(&self, route: &Route) -> io::Result<Route> {
+        let submitted = self.install(route)?;
+        self.operator.read_route(&submitted)
+    }
+
+    /// Add a new route, then read it back and confirm destination, prefix, gateway and metric
+    /// landed as requested, return the route as installed
+    ///
+    /// # Errors
+    /// When system api return error, or ErrorKind::InvalidData when system normalized the
+    /// route differently, the installed route is left in place in that case
+    pub fn add_route_verified(&self, route: &Route) -> io::Result<Route> {
+        let installed = self.add_route_resolved(route)?;
+        let mut mismatches = Vec::new();
+        if installed.destination != route.destination || installed.prefix != route.prefix {
+            mismatches.push(format!(
+                "destination {} installed as {}",
+                route.cidr(),
+                installed.cidr()
+            ));
+        }
+        if installed.gateway != route.gateway {
+            mismatches.push(format!(
+                "gateway {} installed as {}",
+                route.gateway, installed.gateway
+            ));
+        }
+        if route.metric.is_some() && installed.metric != route.metric {
+            mismatches.push(format!(
+                "metric {:?} installed as {:?}",
+                route.metric, installed.metric
+            ));
+        }
+        if !mismatches.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("route {} mismatch: {}", route, mismatches.join(", ")),
+            ));
+        }
+        Ok(installed)
+    }
+
+    /// Add a new route, an already existing entry is treated as success
+    ///
+    /// With `verify_existing` the existing entry must also carry the requested metric
+    /// (and ifindex when set), otherwise ErrorKind::AlreadyExists is still returned
+    ///
+    /// # Errors
+    /// when system api return error
+    pub fn add_route_idempotent(&self, route: &Route, verify_existing: bool) -> io::Result<()> {
+        self.ensure(route, verify_existing)?;
+        Ok(())
+    }
+
+    /// Add route unless an identical one, with the same gateway, metric and ifindex when they
+    /// are set, already exists, e.g. when a reconnecting VPN client adds its routes again
+    ///
+    /// Return whether route was added
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// let route = Route::new("10.8.0.0".parse().unwrap(), 16).gateway("10.8.0.1".parse().unwrap());
+    /// if !manager.ensure_route(&route).unwrap() {
+    ///     println!("{route} was already there");
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    /// ErrorKind::AlreadyExists when the existing entry has different properties, or when
+    /// system api return error
+    pub fn ensure_route(&self, route: &Route) -> io::Result<bool> {
+        self.ensure(route, true)
+    }
+
+    fn ensure(&self, route: &Route, verify_existing: bool) -> io::Result<bool> {
+        match self.install(route) {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                if !verify_existing {
+                    return Ok(false);
+                }
+                let matches = self.operator.read_all_routes()?.iter().any(|r| {
+                    r.destination == route.destination
+                        && r.prefix == route.prefix
+                        && r.gateway == route.gateway
+                        && (route.ifindex.is_none() || r.ifindex == route.ifindex)
+                        && (route.metric.is_none() || r.metric == route.metric)
+                });
+                if matches {
+                    Ok(false)
+                } else {
+                    Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("route {} exists with different properties", route),
+                    ))
+                }
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Add route, or modify the existing entry for the same destination, prefix, gateway and
+    /// interface in place when there is one, so its metric can be set without delete and add
+    ///
+    /// Entries with another gateway are separate rows and stay, see ```add_route_force``` to
+    /// replace them
+    ///
+    /// # Errors
+    /// when system api return error
+    pub fn upsert_route(&self, route: &Route) -> io::Result<()> {
+        match self.install(route) {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                let ifindex = self.route_ifindex(route)?;
+                self.update_route(&route.clone().ifindex(ifindex))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Add a new route after removing every existing entry for the same destination and
+    /// prefix on the same interface, so that the new route wins regardless of current state.
+    /// When route has neither ifindex nor luid, entries on all interfaces are removed
+    ///
+    /// Return evicted routes
+    ///
+    /// # Errors
+    /// when system api return error, evicted routes are not restored
+    pub fn add_route_force(&self, route: &Route) -> io::Result<Vec<Route>> {
+        let evicted: Vec<Route> = self
+            .operator
+            .read_all_routes()?
+            .into_iter()
+            .filter(|r| {
+                r.destination == route.destination
+                    && r.prefix == route.prefix
+                    && (route.ifindex.is_none() || r.ifindex == route.ifindex)
+                    && (route.luid.is_none() || r.luid == route.luid)
+            })
+            .collect();
+        for r in &evicted {
+            self.remove(r)?;
+        }
+        self.install(route)?;
+        Ok(evicted)
+    }
+
+    /// Remove route from system's routing table
+    ///
+    /// # NOTICE
+    ///
+    /// if ```delete_route``` is called by a user that is not a administrator or root, the function will fail and return ERROR_ACCESS_DENIED
+    ///
+    /// # Errors
+    /// when system api return error
+    pub fn delete_route(&self, route: &Route) -> io::Result<()> {
+        self.remove(route)?;
+        Ok(())
+    }
+
+    /// Change metric of the system route matching destination, prefix and interface in place,
+    /// without deleting and re-adding it. All entries that differ only by gateway are updated
+    ///
+    /// # Errors
+    /// when no route matches, ErrorKind::NotFound is returned, or when system api return error
+    pub fn set_metric(&self, prefix: Prefix, ifindex: u32, metric: u32) -> io::Result<()> {
+        let matched: Vec<Route> = self
+            .operator
+            .read_all_routes()?
+            .into_iter()
+            .filter(|r| prefix.matches(r) && r.ifindex == Some(ifindex))
+            .collect();
+        if matched.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no route to {} on interface {}", prefix, ifindex),
+            ));
+        }
+        for route in matched {
+            self.update_route(&route.metric(metric))?;
+        }
+        Ok(())
+    }
+
+    /// Modify the existing system entry matching route's destination, prefix, gateway and
+    /// interface in place, currently its metric, so there is no window in which the route
+    /// is missing as with delete and add
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// let route = Route::new("10.0.0.0".parse().unwrap(), 8)
+    ///     .gateway("192.168.1.1".parse().unwrap())
+    ///     .ifindex(12);
+    /// manager.update_route(&route.metric(5)).unwrap();
+    /// ```
+    ///
+    /// # Errors
+    /// when no such entry exists or system api return error
+    pub fn update_route(&self, route: &Route) -> io::Result<()> {
+        if self.dry_run {
+            return self.plan(route, RouteEvent::Change).map(|_| ());
+        }
+        self.operator.update_route(route)?;
+        // keep owned record in line so the change is not taken for tampering
+        for owned in self.lock_owned()?.iter_mut() {
+            if owned.is_same_entry(route) {
+                owned.metric = route.metric;
+            }
+        }
+        self.note_own(RouteEvent::Change(route.clone()));
+        Ok(())
+    }
+
+    /// Remove every system route with given destination and prefix, regardless of its
+    /// gateway and interface, return routes that were removed
+    ///
+    /// # Errors
+    /// when no route matches, ErrorKind::NotFound is returned, or when system api return error
+    pub fn delete_route_by_prefix(&self, prefix: Prefix) -> io::Result<Vec<Route>> {
+        let matched = self.delete_routes_matching(|r| prefix.matches(r))?;
+        if matched.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no route to {}", prefix),
+            ));
+        }
+        Ok(matched)
+    }
+
+    /// Remove every system route for which `predicate` returns true, return routes that were
+    /// removed, empty when nothing matched
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// // clean up everything left behind by a dead tun interface
+    /// let removed = manager.delete_routes_matching(|r| r.ifindex == Some(42)).unwrap();
+    /// println!("removed {} routes", removed.len());
+    /// ```
+    ///
+    /// # Errors
+    /// when system api return error, routes removed before the failing one stay removed
+    pub fn delete_routes_matching<F>(&self, predicate: F) -> io::Result<Vec<Route>>
+    where
+        F: Fn(&Route) -> bool,
+    {
+        let matched: Vec<Route> = self
+            .operator
+            .read_all_routes()?
+            .into_iter()
+            .filter(|r| predicate(r))
+            .collect();
+        for route in &matched {
+            self.remove(route)?;
+        }
+        Ok(matched)
+    }
+
+    /// Same as ```delete_route_by_prefix``` when only destination and prefix length are known,
+    /// interface and gateway of the system rows are looked up first
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// let removed = manager
+    ///     .delete_route_by_destination("10.8.0.0".parse().unwrap(), 16)
+    ///     .unwrap();
+    /// println!("removed {} routes", removed.len());
+    /// ```
+    ///
+    /// # Errors
+    /// ErrorKind::InvalidInput when `destination/prefix` is not a valid prefix, when no route
+    /// matches, ErrorKind::NotFound is returned, or when system api return error
+    pub fn delete_route_by_destination(
+        &self,
+        destination: IpAddr,
+        prefix: u8,
+    ) -> io::Result<Vec<Route>> {
+        self.delete_route_by_prefix(Prefix::new(destination, prefix)?)
+    }
+
+    /// Remove the default route of `family`, optionally only the one on interface `ifindex`
+    ///
+    /// Refuses with ErrorKind::InvalidInput when removal would leave system without any default
+    /// route of that family, pass `allow_no_default` to override the check.
+    /// Return routes that were removed
+    ///
+    /// # Errors
+    /// when no default route matches, refused by the guard or system api return error
+    pub fn delete_default_route(
+        &self,
+        family: Family,
+        ifindex: Option<u32>,
+        allow_no_default: bool,
+    ) -> io::Result<Vec<Route>> {
+        let defaults: Vec<Route> = self
+            .operator
+            .read_all_routes()?
+            .into_iter()
+            .filter(|r| r.is_default() && r.family() == family)
+            .collect();
+        let (matched, remaining): (Vec<Route>, Vec<Route>) = defaults
+            .into_iter()
+            .partition(|r| ifindex.is_none() || r.ifindex == ifindex);
+        if matched.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "can not found default route",
+            ));
+        }
+        if remaining.is_empty() && !allow_no_default {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "refuse to delete the last default route",
+            ));
+        }
+        for route in &matched {
+            self.remove(route)?;
+        }
+        Ok(matched)
+    }
+
+    /// return default route
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn default_route(&self) -> io::Result<Option<Route>> {
+        Ok(self.routes()?.into_iter().find(|route| {
+            (route.destination == Ipv4Addr::UNSPECIFIED
+                || route.destination == Ipv6Addr::UNSPECIFIED)
+                && route.gateway != IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+                && route.gateway != IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+                && route.prefix == 0
+        }))
+    }
+
+    /// Link layer address of route's gateway, read from the neighbor table and resolved
+    /// when absent, `None` when gateway does not answer address resolution
+    ///
+    /// # Errors
+    /// When route has no gateway or backend fails to query the neighbor table
+    pub fn resolve_next_hop(&self, route: &Route) -> io::Result<Option<MacAddr>> {
+        if route.gateway.is_unspecified() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "route has no gateway",
+            ));
+        }
+        let ifindex = self.route_ifindex(route)?;
+        self.operator.resolve_neighbor(route.gateway, ifindex)
+    }
+
+    /// Like ```resolve_next_hop``` but solicits the gateway unless its neighbor entry was
+    /// confirmed reachable, so a gateway that died behind a stale entry yields `None`
+    ///
+    /// # Errors
+    /// When route has no gateway or backend fails to query the neighbor table
+    pub fn probe_next_hop(&self, route: &Route) -> io::Result<Option<MacAddr>> {
+        if route.gateway.is_unspecified() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "route has no gateway",
+            ));
+        }
+        let ifindex = self.route_ifindex(route)?;
+        self.operator.probe_neighbor(route.gateway, ifindex)
+    }
+
+    /// Check that route's gateway lies within a subnet connected to its interface, catching
+    /// routes that install fine but can never forward. Routes without gateway and link-local
+    /// gateways always pass. When ifindex is not set, the best interface for gateway is used
+    ///
+    /// Enable ```RouteManagerBuilder::validate_gateway``` to run it before every add
+    ///
+    /// # Errors
+    /// ErrorKind::InvalidInput when gateway is off-link, or when backend fails to read
+    /// interface addresses
+    pub fn check_gateway(&self, route: &Route) -> io::Result<()> {
+        if is_on_link(route.gateway) {
+            return Ok(());
+        }
+        let ifindex = match route.ifindex {
+            Some(ifindex) => ifindex,
+            None => self.operator.best_interface(route.gateway)?,
+        };
+        verify_gateway(
+            route.gateway,
+            ifindex,
+            &self.operator.interface_prefixes(ifindex)?,
+        )
+    }
+
+    pub(crate) fn validates_gateway(&self) -> bool {
+        self.validate_gateway
+    }
+
+    /// Subscribe to dial-up, RAS and VPN adapter connection state changes, the moments
+    /// routes usually need to be reconfigured
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// for event in manager.connection_events().unwrap() {
+    ///     println!("{event}");
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    /// When backend can not watch adapters
+    pub fn connection_events(&self) -> io::Result<Receiver<ConnectionEvent>> {
+        self.operator.connection_events()
+    }
+
+    /// Block until a route matching predicate is in the table, e.g. until a VPN tunnel route is
+    /// installed, and return it
+    ///
+    /// Events are polled meanwhile, so the wait works whether or not another thread drives
+    /// the manager. Without listener the table is read again periodically. Cancelling `cancel`
+    /// aborts the wait within one poll slice
+    ///
+    /// ```rust no_run
+    /// use std::time::Duration;
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// let tunnel = manager
+    ///     .wait_for_route(
+    ///         |r| r.ifindex == Some(42),
+    ///         Duration::from_secs(30),
+    ///         &CancelToken::new(),
+    ///     )
+    ///     .unwrap();
+    /// println!("{tunnel}");
+    /// ```
+    ///
+    /// # Errors
+    /// ErrorKind::TimedOut when no such route appeared within timeout,
+    /// ErrorKind::Interrupted when cancelled, or when polling or reading the table fails
+    pub fn wait_for_route<F>(
+        &self,
+        predicate: F,
+        timeout: Duration,
+        cancel: &CancelToken,
+    ) -> io::Result<Route>
+    where
+        F: Fn(&Route) -> bool,
+    {
+        let deadline = Instant::now() + timeout;
+        // subscribe first so a route added right after reading the table is not missed
+        let subscription = self.subscribe()?;
+        loop {
+            if let Some(route) = self.routes()?.into_iter().find(|r| predicate(r)) {
+                return Ok(route);
+            }
+            loop {
+                let found =
+                    std::iter::from_fn(|| subscription.try_recv().ok()).find_map(
+                        |event| match event {
+                            RouteEvent::Add(route) | RouteEvent::Change(route)
+                                if predicate(&route) =>
+                            {
+                                Some(route)
+                            }
+                            _ => None,
+                        },
+                    );
+                if let Some(route) = found {
+                    return Ok(route);
+                }
+                cancel.check()?;
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "no matching route appeared",
+                    ));
+                }
+                let slice = remaining.min(WAIT_SLICE);
+                if !self.listening {
+                    crossbeam_channel::select! {
+                        recv(cancel.receiver()) -> _ => {}
+                        default(slice) => {}
+                    }
+                    break;
+                }
+                match self.poll_timeout(slice) {
+                    Ok(_) | Err(PollError::Lagged { .. }) => {}
+                    Err(e) => return Err(io::Error::other(e)),
+                }
+            }
+        }
+    }
+
+    /// Wait until duplicate address detection completed for every unicast address of family,
+    /// both families when `None`, so routes relying on a freshly configured source address
+    /// are not added while it is still tentative. Cancelling `cancel` aborts the wait at once
+    ///
+    /// ```rust no_run
+    /// use std::time::Duration;
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// manager
+    ///     .wait_stable_addresses(Some(Family::V6), Duration::from_secs(5), &CancelToken::new())
+    ///     .unwrap();
+    /// ```
+    ///
+    /// # Errors
+    /// ErrorKind::TimedOut when addresses are still tentative after timeout,
+    /// ErrorKind::Interrupted when cancelled, or when backend can not watch the address table
+    pub fn wait_stable_addresses(
+        &self,
+        family: Option<Family>,
+        timeout: Duration,
+        cancel: &CancelToken,
+    ) -> io::Result<()> {
+        self.operator.wait_stable_addresses(family, timeout, cancel)
+    }
+
+    /// IP configuration of family on interface ifindex: MTU, duplicate address detection,
+    /// router discovery and neighbor reachability settings
+    ///
+    /// # Errors
+    /// When interface does not exist or backend has no interface configuration
+    pub fn interface_config(&self, family: Family, ifindex: u32) -> io::Result<InterfaceConfig> {
+        self.operator.interface_config(family, ifindex)
+    }
+
+    /// Apply writable fields of config, usually after modifying a value read by
+    /// ```interface_config```
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// let mut config = manager.interface_config(Family::V4, 12).unwrap();
+    /// config.mtu = 1400;
+    /// manager.set_interface_config(&config).unwrap();
+    /// ```
+    ///
+    /// # Errors
+    /// When system rejects a value or backend has no interface configuration
+    pub fn set_interface_config(&self, config: &InterfaceConfig) -> io::Result<()> {
+        let result = self.operator.set_interface_config(config);
+        self.refresh_interfaces();
+        result
+    }
+
+    fn lock_interfaces(&self) -> io::Result<MutexGuard<'_, InterfaceCache>> {
+        self.interfaces
+            .lock()
+            .map_err(|_| io::Error::other("Can not lock inner data, this is a thread safe error"))
+    }
+
+    /// Identity of interface ifindex, from the shared interface cache
+    ///
+    /// # Errors
+    /// ErrorKind::NotFound when there is no such interface, or when backend has no interface
+    /// information
+    pub fn interface(&self, ifindex: u32) -> io::Result<InterfaceInfo> {
+        self.find_interface(|i| i.ifindex == ifindex, || format!("interface {ifindex}"))
+    }
+
+    /// Identity of the interface with luid, from the shared interface cache
+    ///
+    /// # Errors
+    /// ErrorKind::NotFound when there is no such interface, or when backend has no interface
+    /// information
+    pub fn interface_by_luid(&self, luid: u64) -> io::Result<InterfaceInfo> {
+        self.find_interface(|i| i.luid == luid, || format!("interface luid {luid}"))
+    }
+
+    /// Identity of the interface with alias, compared case insensitively, from the shared
+    /// interface cache
+    ///
+    /// # Errors
+    /// ErrorKind::NotFound when there is no such interface, or when backend has no interface
+    /// information
+    pub fn interface_by_alias(&self, alias: &str) -> io::Result<InterfaceInfo> {
+        self.find_interface(
+            |i| i.alias.eq_ignore_ascii_case(alias),
+            || format!("interface {alias:?}"),
+        )
+    }
+
+    pub(crate) fn find_interface(
+        &self,
+        predicate: impl Fn(&InterfaceInfo) -> bool,
+        name: impl FnOnce() -> String,
+    ) -> io::Result<InterfaceInfo> {
+        self.lock_interfaces()?
+            .find(&self.operator, predicate)?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no {}", name())))
+    }
+
+    /// Aliases of all interfaces, from the shared interface cache
+    ///
+    /// # Errors
+    /// When backend has no interface information
+    pub fn interface_names(&self) -> io::Result<InterfaceNames> {
+        let mut cache = self.lock_interfaces()?;
+        Ok(InterfaceNames::from_interfaces(
+            cache.interfaces(&self.operator)?,
+        ))
+    }
+
+    /// Route metric plus metric of its interface, what system ranks routes of a prefix by.
+    /// Interfaces whose metric can not be read add nothing
+    fn effective_metric(&self, route: &Route) -> u32 {
+        let interface_metric = route
+            .ifindex
+            .and_then(|idx| self.interface_metric(route.family(), idx).ok())
+            .unwrap_or(0);
+        route.metric.unwrap_or(0).saturating_add(interface_metric)
+    }
+
+    /// Metric of interface ifindex for family, from the shared interface cache
+    ///
+    /// # Errors
+    /// When interface does not exist or backend fails to read it
+    pub fn interface_metric(&self, family: Family, ifindex: u32) -> io::Result<u32> {
+        self.lock_interfaces()?
+            .metric(&self.operator, family, ifindex)
+    }
+
+    /// Forget cached interface identities and metrics, next lookups read them again. Needed
+    /// after interfaces were reconfigured outside this manager
+    pub fn refresh_interfaces(&self) {
+        if let Ok(mut cache) = self.interfaces.lock() {
+            cache.clear();
+        }
+    }
+
+    /// Whether interface ifindex sends IPv6 router advertisements
+    ///
+    /// # Errors
+    /// When interface does not exist or backend has no interface configuration
+    pub fn advertising(&self, ifindex: u32) -> io::Result<bool> {
+        Ok(self
+            .operator
+            .interface_config(Family::V6, ifindex)?
+            .advertising_enabled)
+    }
+
+    /// Enable or disable IPv6 router advertisements on interface ifindex, routes published
+    /// on it are advertised to the link when enabled and forwarding is enabled
+    ///
+    /// # Errors
+    /// When interface does not exist or backend has no interface configuration
+    pub fn set_advertising(&self, ifindex: u32, enabled: bool) -> io::Result<()> {
+        let mut config = self.operator.interface_config(Family::V6, ifindex)?;
+        config.advertising_enabled = enabled;
+        self.operator.set_interface_config(&config)
+    }
+
+    /// Interface index and source address to pin traffic to destination to its current path,
+    /// for `IP_UNICAST_IF` / `IPV6_UNICAST_IF` and `bind()`
+    ///
+    /// Interface is the one of ```best_route```, system does not resolve next hops
+    /// recursively: traffic leaves on the selected route's own interface
+    ///
+    /// `IP_UNICAST_IF` expects the index in network byte order, `IPV6_UNICAST_IF` in host order
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// let (ifindex, source) = manager
+    ///     .interface_for_binding("8.8.8.8".parse().unwrap())
+    ///     .unwrap();
+    /// let socket = std::net::UdpSocket::bind((source, 0)).unwrap();
+    /// ```
+    ///
+    /// # Errors
+    /// ErrorKind::NotFound when destination is unreachable or outgoing interface has no
+    /// address of destination's family
+    pub fn interface_for_binding(&self, destination: IpAddr) -> io::Result<(u32, IpAddr)> {
+        let route = self.best_route(destination)?;
+        let ifindex = match route.ifindex {
+            Some(ifindex) => ifindex,
+            None => self.route_ifindex(&route)?,
+        };
+        let addresses = self.operator.interface_addresses(ifindex)?;
+        match trace::select_source(&addresses, destination) {
+            Some(source) => Ok((ifindex, source)),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("interface {ifindex} has no address to reach {destination}"),
+            )),
+        }
+    }
+
+    /// Route system would use to reach destination, with the interface and next hop it
+    /// selected, on Windows through GetBestRoute2
+    ///
+    /// When backend has no route lookup, the cached table is searched instead: longest prefix
+    /// match, lowest metric wins a tie
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// let route = manager.best_route("8.8.8.8".parse().unwrap()).unwrap();
+    /// println!("via {} on interface {:?}", route.gateway, route.ifindex);
+    /// ```
+    ///
+    /// # Errors
+    /// ErrorKind::NotFound when no route reaches destination, or when system api return error
+    pub fn best_route(&self, destination: IpAddr) -> io::Result<Route> {
+        match self.operator.best_route(destination) {
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+                self.lookup(destination)?.ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("no route to {destination}"),
+                    )
+                })
+            }
+            result => result,
+        }
+    }
+
+    /// Watch the route selected for destination, a change is delivered only when an event
+    /// processed by ```poll``` changes the selection, e.g. to know when to rebind sockets
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// let changes = manager.watch_best_route("8.8.8.8".parse().unwrap()).unwrap();
+    /// for change in changes {
+    ///     println!("now via {:?}", change.current);
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn watch_best_route(&self, destination: IpAddr) -> io::Result<Receiver<BestRouteChange>> {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let mut watchers = self.route_watchers.lock().map_err(|_| {
+            io::Error::other("Can not lock inner data, this is a thread safe error")
+        })?;
+        // selection is tracked against cached table, the one events are applied to
+        let selected = self.scan_routes(|routes| trace::lookup(routes, destination).cloned())?;
+        watchers.push(BestRouteWatcher::new(destination, selected, sender));
+        Ok(receiver)
+    }
+
+    /// Watch the effective default route of family, the default route with the lowest
+    /// effective metric (route metric plus interface metric), a change is delivered with the old and new route only when its gateway, metric or
+    /// interface changes or it is replaced
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// for change in manager.watch_default_route(Family::V4).unwrap() {
+    ///     println!("default route {:?} -> {:?}", change.previous, change.current);
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn watch_default_route(&self, family: Family) -> io::Result<Receiver<BestRouteChange>> {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let mut watchers = self.route_watchers.lock().map_err(|_| {
+            io::Error::other("Can not lock inner data, this is a thread safe error")
+        })?;
+        let selected =
+            trace::lookup_default(&self.routes()?, family, |r| self.effective_metric(r)).cloned();
+        watchers.push(BestRouteWatcher::default_route(family, selected, sender));
+        Ok(receiver)
+    }
+
+    /// Watch the route and interface system selects for destination, e.g. a VPN server: a
+    /// change is delivered when a processed event changes ```best_route``` or the interface
+    /// packets leave through
+    ///
+    /// Watcher can be created while destination is unreachable, e.g. before a VPN is up,
+    /// the selection is `None` until a route appears
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// let server = "203.0.113.7".parse().unwrap();
+    /// for change in manager.watch_resolved_route(server).unwrap() {
+    ///     let ifindex = change.current.and_then(|r| r.ifindex);
+    ///     println!("{} now leaves through {:?}", server, ifindex);
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    /// When route lookup fails for another reason than an unreachable destination, or when
+    /// try to lock Mutex and it return an error
+    pub fn watch_resolved_route(
+        &self,
+        destination: IpAddr,
+    ) -> io::Result<Receiver<ResolvedRouteChange>> {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let mut watchers = self.resolved_watchers.lock().map_err(|_| {
+            io::Error::other("Can not lock inner data, this is a thread safe error")
+        })?;
+        let selected = self.resolved_route(destination)?;
+        watchers.push(ResolvedRouteWatcher::new(destination, selected, sender));
+        Ok(receiver)
+    }
+
+    /// ```best_route``` with its interface resolved, `None` when destination is unreachable
+    fn resolved_route(&self, destination: IpAddr) -> io::Result<Option<Route>> {
+        let mut route = match self.best_route(destination) {
+            Ok(route) => route,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        if route.ifindex.is_none() && route.luid.is_none() {
+            route.ifindex = Some(self.route_ifindex(&route)?);
+        }
+        Ok(Some(route))
+    }
+
+    /// Walk the routing table for destination like a packet would: the matching route,
+    /// whether its next hop is on-link or needs another lookup, and the resulting
+    /// interface and source address
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// println!("{}", manager.trace("8.8.8.8".parse().unwrap()).unwrap());
+    /// ```
+    ///
+    /// # Errors
+    /// When reading routes fails or next hops form a loop
+    pub fn trace(&self, destination: IpAddr) -> io::Result<Trace> {
+        let mut result = trace::trace(&self.routes()?, destination)?;
+        if let Some(ifindex) = result.ifindex {
+            result.source = self
+                .operator
+                .interface_addresses(ifindex)
+                .ok()
+                .and_then(|addresses| trace::select_source(&addresses, destination));
+        }
+        Ok(result)
+    }
+}
+
+/// Blocking iterator over events processed by manager, created by ```RouteManager::events```
+pub struct Events<'a, O: SystemRouteOperate = DefaultOperator> {
+    manager: &'a RouteManager<O>,
+}
+
+impl<O: SystemRouteOperate> Iterator for Events<'_, O> {
+    type Item = RouteEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.manager.poll_event() {
+            Ok(event) => Some(event),
+            Err(PollError::Lagged { event, .. }) => Some(event.event),
+            Err(_) => None,
+        }
+    }
+}
+
+/// Route keeps a raw prefix length, reject one longer than its family allows before it
+/// reaches backend
+fn check_prefix_len(route: &Route) -> io::Result<()> {
+    Prefix::truncate(route.destination, route.prefix).map(|_| ())
+}
+
+/// Rows a backend returned that fit the cache, a prefix longer than its family allows can
+/// come from mock, replayed or custom backends and is dropped
+fn cacheable(mut routes: Vec<Route>) -> Vec<Route> {
+    routes.retain(Route::has_valid_prefix);
+    routes
+}
+
+/// Apply event to cached table, return false when it was skipped because its route prefix
+/// is longer than its family allows
+fn apply(routes: &mut Vec<Route>, event: &RouteEvent) -> bool {
+    if event.route().is_some_and(|route| !route.has_valid_prefix()) {
+        return false;
+    }
+    match event {
+        RouteEvent::Add(route) => {
+            // entry may be cached already when ```resync``` ran before the event was polled
+            routes.retain(|v| {
+                (v.destination, v.prefix, v.gateway, v.ifindex)
+                    != (
+                        route.destination,
+                        route.prefix,
+                        route.gateway,
+                        route.ifindex,
+                    )
+            });
+            routes.push(route.clone());
+        }
+        RouteEvent::Delete(route) => {
+            if let Some(index) = routes.iter().position(|v| v == route) {
+                routes.remove(index);
+            }
+        }
+        RouteEvent::Change(route) => {
+            if let Some(index) = routes
+                .iter()
+                .position(|v| v.destination == route.destination && v.prefix == route.prefix)
+            {
+                routes.remove(index);
+                routes.push(route.clone());
+            }
+        }
+        RouteEvent::Initial => {}
+    }
+    true
+}
+
+/// Events turning table `old` into `new`, deletions first, then changes and additions
+fn diff(old: &[Route], new: &[Route]) -> Vec<RouteEvent> {
+    let key = |r: &Route| (r.destination, r.prefix, r.gateway, r.ifindex);
+    let current: HashMap<_, &Route> = new.iter().map(|r| (key(r), r)).collect();
+    let previous: HashMap<_, &Route> = old.iter().map(|r| (key(r), r)).collect();
+    let mut events: Vec<RouteEvent> = old
+        .iter()
+        .filter(|r| !current.contains_key(&key(r)))
+        .map(|r| RouteEvent::Delete(r.clone()))
+        .collect();
+    for route in new {
+        match previous.get(&key(route)) {
+            Some(cached) if *cached != route => events.push(RouteEvent::Change(route.clone())),
+            Some(_) => {}
+            None => events.push(RouteEvent::Add(route.clone())),
+        }
+    }
+    events
+}
+
+impl<O: SystemRouteOperate> Drop for RouteManager<O> {
+    fn drop(&mut self) {
+        if self.cleanup_on_drop {
+            let _ = self.remove_owned_routes();
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test_manager {
+    use std::time::Duration;
+
+    use crate::{
+        mock::MockOperator, CancelToken, Family, MacAddr, Metric, Origin, OverflowPolicy,
+        OwnedRouteEvent, PollError, Prefix, Route, RouteEvent, RouteManager, RouteManagerBuilder,
+        TableAlert,
+    };
+
+    fn route(dst: &str, prefix: u8) -> Route {
+        Route::new(dst.parse().unwrap(), prefix).gateway("192.168.1.1".parse().unwrap())
+    }
+
+    #[test]
+    fn test_with_backend() {
+        let manager =
+            RouteManager::with_backend(MockOperator::new(vec![route("0.0.0.0", 0).ifindex(1)]))
+                .unwrap();
+        assert_eq!(1, manager.routes().unwrap().len());
+
+        manager.add_route(&route("10.0.0.0", 8)).unwrap();
+        let event = manager.poll_event().unwrap();
+        assert_eq!(RouteEvent::Add(route("10.0.0.0", 8).ifindex(1)), event);
+        assert_eq!(2, manager.routes().unwrap().len());
+        assert_eq!(2, manager.backend().table.lock().unwrap().len());
+    }
+
+    #[test]
+    fn test_subscribe_scoped() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        let _kept = manager.subscribe().unwrap();
+        let event = manager
+            .subscribe_scoped(|events| {
+                manager.add_route(&route("10.0.0.0", 8)).unwrap();
+                manager.poll().unwrap();
+                events.try_recv().unwrap()
+            })
+            .unwrap();
+        assert_eq!(RouteEvent::Add(route("10.0.0.0", 8).ifindex(1)), event);
+        assert_eq!(1, manager.dedicated_subscribers.lock().unwrap().len());
+    }
+
+    #[test]
+    fn test_subscribe_route_change_family() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        let v4 = manager.subscribe_route_change_v4().unwrap();
+        let v6 = manager.subscribe_route_change_v6().unwrap();
+        manager.add_route(&route("10.0.0.0", 8)).unwrap();
+        manager.add_route(&route("fd00::", 8)).unwrap();
+        manager.poll().unwrap();
+        manager.poll().unwrap();
+        assert!(v4
+            .try_recv()
+            .unwrap()
+            .route()
+            .unwrap()
+            .destination
+            .is_ipv4());
+        assert!(v4.try_recv().is_err());
+        assert!(v6
+            .try_recv()
+            .unwrap()
+            .route()
+            .unwrap()
+            .destination
+            .is_ipv6());
+        assert!(v6.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_subscribe_for_interface() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        let by_index = manager.subscribe_for_interface(2).unwrap();
+        let by_luid = manager.subscribe_for_interface_luid(1002).unwrap();
+        let both = manager.subscribe_for_interfaces(&[1, 2]).unwrap();
+        manager.add_route(&route("10.0.0.0", 8).ifindex(1)).unwrap();
+        manager
+            .add_route(&route("10.1.0.0", 16).ifindex(2))
+            .unwrap();
+        manager
+            .add_route(&route("10.2.0.0", 16).ifindex(3))
+            .unwrap();
+        for _ in 0..3 {
+            manager.poll().unwrap();
+        }
+        for subscription in [&by_index, &by_luid] {
+            assert_eq!(
+                Some(2),
+                subscription.try_recv().unwrap().route().unwrap().ifindex
+            );
+            assert!(subscription.try_recv().is_err());
+        }
+        assert_eq!(2, both.iter().take(2).count());
+        assert!(both.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_subscribe_prefix() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        let private = manager
+            .subscribe_prefix("10.0.0.0".parse().unwrap(), 8)
+            .unwrap();
+        assert!(manager
+            .subscribe_prefix("10.0.0.1".parse().unwrap(), 8)
+            .is_err());
+        manager.add_route(&route("0.0.0.0", 0)).unwrap();
+        manager.add_route(&route("10.1.0.0", 16)).unwrap();
+        manager.add_route(&route("192.168.0.0", 16)).unwrap();
+        for _ in 0..3 {
+            manager.poll().unwrap();
+        }
+        assert_eq!(
+            "10.1.0.0",
+            private
+                .try_recv()
+                .unwrap()
+                .route()
+                .unwrap()
+                .destination
+                .to_string()
+        );
+        assert!(private.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_subscriber_buffer() {
+        let manager = RouteManager::builder()
+            .subscriber_buffer(1, OverflowPolicy::DropOldest)
+            .build_with_backend(MockOperator::new(vec![]))
+            .unwrap();
+        let subscription = manager.subscribe_route_change();
+        manager.add_route(&route("10.0.0.0", 8)).unwrap();
+        manager.add_route(&route("10.1.0.0", 16)).unwrap();
+        manager.poll().unwrap();
+        assert!(matches!(
+            manager.poll(),
+            Err(PollError::Lagged { dropped: 1, .. })
+        ));
+        assert_eq!(
+            "10.1.0.0",
+            subscription
+                .try_recv()
+                .unwrap()
+                .route()
+                .unwrap()
+                .destination
+                .to_string()
+        );
+        assert!(subscription.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_coalesce() {
+        let manager = RouteManager::builder()
+            .coalesce(Duration::from_millis(50))
+            .build_with_backend(MockOperator::new(vec![route("10.0.0.0", 8).metric(1)]))
+            .unwrap();
+        for metric in 2..5 {
+            manager
+                .backend()
+                .emit(RouteEvent::Change(route("10.0.0.0", 8).metric(metric)));
+        }
+        manager
+            .backend()
+            .emit(RouteEvent::Add(route("10.1.0.0", 16)));
+        assert_eq!(
+            RouteEvent::Change(route("10.0.0.0", 8).metric(4)),
+            manager.poll_event().unwrap()
+        );
+        assert_eq!(
+            RouteEvent::Add(route("10.1.0.0", 16)),
+            manager.poll_event().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_event_buffer() {
+        for builder in [
+            RouteManager::builder().event_buffer(0, OverflowPolicy::DropNewest),
+            RouteManager::builder().subscriber_buffer(0, OverflowPolicy::DropOldest),
+        ] {
+            let err = builder
+                .build_with_backend(MockOperator::new(vec![]))
+                .err()
+                .unwrap();
+            assert_eq!(std::io::ErrorKind::InvalidInput, err.kind());
+        }
+
+        let manager = RouteManager::builder()
+            .coalesce(Duration::from_millis(10))
+            .event_buffer(1, OverflowPolicy::DropNewest)
+            .build_with_backend(MockOperator::new(vec![]))
+            .unwrap();
+        let subscription = manager.subscribe().unwrap();
+        for prefix in [8, 16, 24] {
+            manager
+                .backend()
+                .emit(RouteEvent::Add(route("10.0.0.0", prefix)));
+        }
+        std::thread::sleep(Duration::from_millis(100));
+        manager.poll().unwrap();
+        assert_eq!(3, manager.routes().unwrap().len());
+        assert_eq!(
+            3,
+            std::iter::from_fn(|| subscription.try_recv().ok()).count()
+        );
+    }
+
+    #[test]
+    fn test_subscribe_tagged() {
+        let manager = RouteManager::builder()
+            .suppress_own_events(true)
+            .build_with_backend(MockOperator::new(vec![]))
+            .unwrap();
+        let tagged = manager.subscribe_tagged().unwrap();
+        let plain = manager.subscribe_route_change();
+        manager.add_route(&route("10.0.0.0", 8)).unwrap();
+        manager
+            .backend()
+            .emit(RouteEvent::Add(route("10.1.0.0", 16).ifindex(2)));
+        manager.poll().unwrap();
+        manager.poll().unwrap();
+        let origins: Vec<Origin> = tagged.try_iter().map(|t| t.origin).collect();
+        assert_eq!(vec![Origin::SelfOriginated, Origin::External], origins);
+        assert_eq!(
+            RouteEvent::Add(route("10.1.0.0", 16).ifindex(2)),
+            plain.try_recv().unwrap()
+        );
+        assert!(plain.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_initial_event() {
+        let manager =
+            RouteManager::with_backend(MockOperator::new(vec![route("10.0.0.0", 8)])).unwrap();
+        let v6 = manager.subscribe_route_change_v6().unwrap();
+        manager.backend().emit(RouteEvent::Initial);
+        assert_eq!(RouteEvent::Initial, manager.poll_event().unwrap());
+        assert_eq!(RouteEvent::Initial, v6.try_recv().unwrap());
+        assert_eq!(1, manager.routes().unwrap().len());
+        assert_eq!("INITIAL", RouteEvent::Initial.to_string());
+    }
+
+    #[test]
+    fn test_shutdown_unblocks_poll() {
+        let manager =
+            std::sync::Arc::new(RouteManager::with_backend(MockOperator::new(vec![])).unwrap());
+        let polling = manager.clone();
+        let thread = std::thread::spawn(move || polling.poll());
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!manager.is_shut_down());
+        manager.shutdown().unwrap();
+        assert!(matches!(
+            thread.join().unwrap(),
+            Err(PollError::Disconnected)
+        ));
+        assert!(manager.is_shut_down());
+        assert!(matches!(manager.poll(), Err(PollError::Disconnected)));
+    }
+
+    #[test]
+    fn test_try_poll() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        assert_eq!(None, manager.try_poll().unwrap());
+        assert_eq!(
+            None,
+            manager.poll_timeout(Duration::from_millis(10)).unwrap()
+        );
+        manager.add_route(&route("10.0.0.0", 8)).unwrap();
+        assert_eq!(
+            Some(RouteEvent::Add(route("10.0.0.0", 8).ifindex(1))),
+            manager.poll_timeout(Duration::from_secs(1)).unwrap()
+        );
+        manager.add_route(&route("10.1.0.0", 16)).unwrap();
+        assert!(manager.try_poll().unwrap().is_some());
+        manager.shutdown().unwrap();
+        assert!(matches!(manager.try_poll(), Err(PollError::Disconnected)));
+    }
+
+    #[test]
+    fn test_poll_all() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        let subscription = manager.subscribe().unwrap();
+        assert!(manager.drain().unwrap().is_empty());
+        for prefix in 8..12 {
+            manager.add_route(&route("10.0.0.0", prefix)).unwrap();
+        }
+        manager.delete_route(&route("10.0.0.0", 8)).unwrap();
+        let events = manager.poll_all().unwrap();
+        assert_eq!(5, events.len());
+        assert_eq!(3, manager.routes().unwrap().len());
+        assert_eq!(5, subscription.iter().take(5).count());
+        manager.add_route(&route("10.0.0.0", 12)).unwrap();
+        assert_eq!(1, manager.drain().unwrap().len());
+    }
+
+    #[test]
+    fn test_wait_for_route() {
+        let manager = std::sync::Arc::new(
+            RouteManager::with_backend(MockOperator::new(vec![route("10.0.0.0", 8)])).unwrap(),
+        );
+        let token = CancelToken::new();
+        let present = manager
+            .wait_for_route(|r| r.prefix == 8, Duration::from_millis(10), &token)
+            .unwrap();
+        assert_eq!(8, present.prefix);
+        let err = manager
+            .wait_for_route(|r| r.prefix == 16, Duration::from_millis(10), &token)
+            .unwrap_err();
+        assert_eq!(std::io::ErrorKind::TimedOut, err.kind());
+
+        let adding = manager.clone();
+        let thread = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            adding
+                .backend()
+                .emit(RouteEvent::Add(route("10.1.0.0", 16)));
+        });
+        let tunnel = manager
+            .wait_for_route(|r| r.prefix == 16, Duration::from_secs(5), &token)
+            .unwrap();
+        assert_eq!("10.1.0.0/16", tunnel.cidr().to_string());
+        thread.join().unwrap();
+
+        let cancelling = token.clone();
+        let thread = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            cancelling.cancel();
+        });
+        let err = manager
+            .wait_for_route(|r| r.prefix == 24, Duration::from_secs(5), &token)
+            .unwrap_err();
+        assert_eq!(std::io::ErrorKind::Interrupted, err.kind());
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn test_skip_invalid_prefix() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![
+            route("10.0.0.0", 8),
+            route("10.0.0.0", 40),
+        ]))
+        .unwrap();
+        assert_eq!(vec![route("10.0.0.0", 8)], manager.routes().unwrap());
+
+        manager
+            .backend()
+            .emit(RouteEvent::Add(route("192.168.0.0", 33)));
+        manager.poll().unwrap();
+        assert_eq!(vec![route("10.0.0.0", 8)], manager.routes().unwrap());
+        assert!(manager
+            .lookup("192.168.0.1".parse().unwrap())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_watch_default_route() {
+        let default = |gateway: &str, metric: u32| {
+            Route::new("0.0.0.0".parse().unwrap(), 0)
+                .gateway(gateway.parse().unwrap())
+                .ifindex(1)
+                .metric(metric)
+        };
+        let manager =
+            RouteManager::with_backend(MockOperator::new(vec![default("192.168.1.1", 25)]))
+                .unwrap();
+        let changes = manager.watch_default_route(Family::V4).unwrap();
+        manager.add_route(&route("10.0.0.0", 8)).unwrap();
+        manager.add_route(&default("10.8.0.1", 5)).unwrap();
+        manager.poll().unwrap();
+        manager.poll().unwrap();
+        let change = changes.try_recv().unwrap();
+        assert_eq!(Some(default("192.168.1.1", 25)), change.previous);
+        assert_eq!(Some(default("10.8.0.1", 5)), change.current);
+        assert!(changes.try_recv().is_err());
+
+        // lower route metric, but interface 3 adds 30 and loses to 5 + 10
+        manager
+            .add_route(&default("10.9.0.1", 1).ifindex(3))
+            .unwrap();
+        manager.poll().unwrap();
+        assert!(changes.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_watch_resolved_route() {
+        let on_link = |dst: &str, prefix: u8, ifindex: u32| {
+            Route::new(dst.parse().unwrap(), prefix).ifindex(ifindex)
+        };
+        let manager = RouteManager::with_backend(MockOperator::new(vec![
+            on_link("192.168.1.0", 24, 1),
+            route("0.0.0.0", 0).ifindex(1),
+        ]))
+        .unwrap();
+        let changes = manager
+            .watch_resolved_route("203.0.113.7".parse().unwrap())
+            .unwrap();
+        manager
+            .backend()
+            .emit(RouteEvent::Add(on_link("10.99.0.0", 16, 2)));
+        manager.poll().unwrap();
+        assert!(changes.try_recv().is_err());
+
+        // gateway moving to interface 2 does not move traffic, it leaves on the route's own
+        manager
+            .backend()
+            .emit(RouteEvent::Add(on_link("192.168.1.1", 32, 2)));
+        manager.poll().unwrap();
+        assert!(changes.try_recv().is_err());
+
+        manager
+            .backend()
+            .emit(RouteEvent::Add(on_link("203.0.113.0", 24, 2)));
+        manager.poll().unwrap();
+        let change = changes.try_recv().unwrap();
+        assert_eq!(Some(1), change.previous.unwrap().ifindex);
+        assert_eq!(Some(2), change.current.unwrap().ifindex);
+    }
+
+    #[test]
+    fn test_watch_unreachable_route() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        let changes = manager
+            .watch_resolved_route("203.0.113.7".parse().unwrap())
+            .unwrap();
+        manager
+            .backend()
+            .emit(RouteEvent::Add(route("0.0.0.0", 0).ifindex(3)));
+        manager.poll().unwrap();
+        let change = changes.try_recv().unwrap();
+        assert_eq!(None, change.previous);
+        assert_eq!(Some(3), change.current.unwrap().ifindex);
+    }
+
+    #[test]
+    fn test_update_route() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        let tampered = manager.subscribe_tamper().unwrap();
+        manager.add_route(&route("10.0.0.0", 8).metric(10)).unwrap();
+        let installed = route("10.0.0.0", 8).ifindex(1).metric(10);
+        manager.update_route(&installed.clone().metric(3)).unwrap();
+        manager.poll().unwrap();
+        assert_eq!(
+            RouteEvent::Change(installed.clone().metric(3)),
+            manager.poll_event().unwrap()
+        );
+        assert_eq!(Some(3), manager.routes().unwrap()[0].metric);
+        assert!(tampered.try_recv().is_err());
+        assert!(manager
+            .update_route(&route("10.1.0.0", 16).ifindex(1))
+            .is_err());
+    }
+
+    #[test]
+    fn test_upsert_route() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        manager
+            .upsert_route(&route("10.0.0.0", 8).metric(10))
+            .unwrap();
+        manager
+            .upsert_route(&route("10.0.0.0", 8).metric(3))
+            .unwrap();
+        assert!(matches!(manager.poll_event().unwrap(), RouteEvent::Add(_)));
+        assert_eq!(
+            RouteEvent::Change(route("10.0.0.0", 8).ifindex(1).metric(3)),
+            manager.poll_event().unwrap()
+        );
+        assert_eq!(1, manager.routes().unwrap().len());
+    }
+
+    #[test]
+    fn test_ensure_route() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        let vpn = route("10.8.0.0", 16).ifindex(2).metric(5);
+        assert!(manager.ensure_route(&vpn).unwrap());
+        assert!(!manager.ensure_route(&vpn).unwrap());
+        let err = manager.ensure_route(&vpn.clone().metric(6)).unwrap_err();
+        assert_eq!(std::io::ErrorKind::AlreadyExists, err.kind());
+    }
+
+    #[test]
+    fn test_delete_route_by_destination() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![
+            route("10.8.0.0", 16).ifindex(2),
+            route("10.8.0.0", 16)
+                .ifindex(3)
+                .gateway("10.0.0.1".parse().unwrap()),
+            route("10.9.0.0", 16).ifindex(2),
+        ]))
+        .unwrap();
+        let removed = manager
+            .delete_route_by_destination("10.8.0.0".parse().unwrap(), 16)
+            .unwrap();
+        assert_eq!(2, removed.len());
+        assert_eq!(1, manager.backend().table.lock().unwrap().len());
+        assert!(manager
+            .delete_route_by_destination("10.8.0.1".parse().unwrap(), 16)
+            .is_err());
+    }
+
+    #[test]
+    fn test_delete_routes_matching() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![
+            route("10.8.0.0", 16).ifindex(2),
+            route("10.9.0.0", 16).ifindex(3),
+            route("10.10.0.0", 16).ifindex(2),
+        ]))
+        .unwrap();
+        let removed = manager
+            .delete_routes_matching(|r| r.ifindex == Some(2))
+            .unwrap();
+        assert_eq!(2, removed.len());
+        assert_eq!(1, manager.backend().table.lock().unwrap().len());
+        assert!(manager
+            .delete_routes_matching(|r| r.ifindex == Some(2))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let existing = route("10.0.0.0", 8).ifindex(1).metric(10);
+        let manager = RouteManager::builder()
+            .dry_run(true)
+            .build_with_backend(MockOperator::new(vec![existing.clone()]))
+            .unwrap();
+        assert!(manager.is_dry_run());
+
+        manager.add_route(&route("172.16.0.0", 12)).unwrap();
+        manager.update_route(&existing.clone().metric(5)).unwrap();
+        manager.delete_route(&existing).unwrap();
+        manager.add_route(&existing).unwrap();
+        let err = manager.add_route(&existing).unwrap_err();
+        assert_eq!(std::io::ErrorKind::AlreadyExists, err.kind());
+        let err = manager
+            .update_route(&route("172.16.0.0", 12).ifindex(2))
+            .unwrap_err();
+        assert_eq!(std::io::ErrorKind::NotFound, err.kind());
+        let err = manager
+            .delete_route(&route("192.168.0.0", 16).ifindex(1))
+            .unwrap_err();
+        assert_eq!(std::io::ErrorKind::NotFound, err.kind());
+        let err = manager
+            .add_route(&route("172.17.0.0", 16).ifindex(9))
+            .unwrap_err();
+        assert_eq!(std::io::ErrorKind::NotFound, err.kind());
+        let v6 = Route::new("fd00::".parse().unwrap(), 8).gateway("192.168.1.1".parse().unwrap());
+        let err = manager.add_route(&v6).unwrap_err();
+        assert_eq!(std::io::ErrorKind::InvalidInput, err.kind());
+
+        assert_eq!(
+            vec![
+                RouteEvent::Add(route("172.16.0.0", 12).ifindex(1)),
+                RouteEvent::Change(existing.clone().metric(5)),
+                RouteEvent::Delete(existing.clone().metric(5)),
+                RouteEvent::Add(existing.clone()),
+            ],
+            manager.planned_changes().unwrap()
+        );
+        assert_eq!(vec![existing], *manager.backend().table.lock().unwrap());
+    }
+
+    #[test]
+    fn test_find_route() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![
+            route("10.0.0.0", 8).ifindex(2).metric(20),
+            route("10.0.0.0", 8).ifindex(3).metric(5),
+            route("10.1.0.0", 16).ifindex(2),
+        ]))
+        .unwrap();
+        let found = manager
+            .find_route("10.0.0.0".parse().unwrap(), 8)
+            .unwrap()
+            .unwrap();
+        assert_eq!(Some(3), found.ifindex);
+        assert!(manager
+            .find_route("10.0.0.0".parse().unwrap(), 16)
+            .unwrap()
+            .is_none());
+
+        assert!(manager.route_exists(&route("10.1.0.0", 16)).unwrap());
+        assert!(manager
+            .route_exists(&route("10.1.0.0", 16).ifindex(2))
+            .unwrap());
+        assert!(!manager
+            .route_exists(&route("10.1.0.0", 16).ifindex(3))
+            .unwrap());
+    }
+
+    #[test]
+    fn test_best_route() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![
+            route("0.0.0.0", 0).ifindex(2),
+            route("10.0.0.0", 8).ifindex(3).metric(20),
+            route("10.0.0.0", 8).ifindex(4).metric(5),
+        ]))
+        .unwrap();
+        let best = manager.best_route("10.1.2.3".parse().unwrap()).unwrap();
+        assert_eq!(Some(4), best.ifindex);
+        assert_eq!(
+            "192.168.1.1".parse::<std::net::IpAddr>().unwrap(),
+            best.gateway
+        );
+        let best = manager.best_route("8.8.8.8".parse().unwrap()).unwrap();
+        assert_eq!(Some(2), best.ifindex);
+        let err = manager.best_route("fd00::1".parse().unwrap()).unwrap_err();
+        assert_eq!(std::io::ErrorKind::NotFound, err.kind());
+    }
+
+    #[test]
+    fn test_get_route() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![
+            route("10.0.0.0", 8).ifindex(2).metric(20),
+            Route::new("10.0.0.0".parse().unwrap(), 8)
+                .ifindex(3)
+                .metric(5),
+        ]))
+        .unwrap();
+        let row = manager
+            .get_route("10.0.0.0".parse().unwrap(), 8, 2)
+            .unwrap();
+        assert_eq!(Some(20), row.metric);
+        assert_eq!(
+            "192.168.1.1".parse::<std::net::IpAddr>().unwrap(),
+            row.gateway
+        );
+        let row = manager
+            .get_route("10.0.0.0".parse().unwrap(), 8, 3)
+            .unwrap();
+        assert_eq!(Some(5), row.metric);
+        let err = manager
+            .get_route("10.0.0.0".parse().unwrap(), 8, 4)
+            .unwrap_err();
+        assert_eq!(std::io::ErrorKind::NotFound, err.kind());
+    }
+
+    #[test]
+    fn test_resync() {
+        let kept = route("10.0.0.0", 8).ifindex(1).metric(5);
+        let changed = route("10.1.0.0", 16).ifindex(1).metric(5);
+        let removed = route("10.2.0.0", 16).ifindex(1);
+        let manager = RouteManager::with_backend(MockOperator::new(vec![
+            kept.clone(),
+            changed.clone(),
+            removed.clone(),
+        ]))
+        .unwrap();
+        let subscription = manager.subscribe_route_change();
+
+        // system changed without the manager being told
+        let added = route("10.3.0.0", 16).ifindex(1);
+        *manager.backend().table.lock().unwrap() =
+            vec![kept.clone(), changed.clone().metric(7), added.clone()];
+        assert_eq!(3, manager.resync(false).unwrap().len());
+        assert!(subscription.try_recv().is_err());
+        assert!(manager.resync(true).unwrap().is_empty());
+
+        *manager.backend().table.lock().unwrap() = vec![kept.clone()];
+        let events = manager.resync(true).unwrap();
+        assert_eq!(
+            vec![
+                RouteEvent::Delete(changed.metric(7)),
+                RouteEvent::Delete(added)
+            ],
+            events
+        );
+        assert_eq!(vec![kept], manager.routes().unwrap());
+        assert_eq!(events[0], subscription.try_recv().unwrap());
+        assert_eq!(events[1], subscription.try_recv().unwrap());
+    }
+
+    #[test]
+    fn test_routes_by_family() {
+        let v4 = route("10.0.0.0", 8).ifindex(1);
+        let v6 = Route::new("fd00::".parse().unwrap(), 8).ifindex(1);
+        let manager =
+            RouteManager::with_backend(MockOperator::new(vec![v4.clone(), v6.clone()])).unwrap();
+        assert_eq!(vec![v4.clone()], manager.routes_v4().unwrap());
+        assert_eq!(vec![v6.clone()], manager.routes_v6().unwrap());
+        assert_eq!(vec![v6], manager.routes_by_family(Family::V6).unwrap());
+    }
+
+    #[test]
+    fn test_lookup() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![
+            route("0.0.0.0", 0).ifindex(1),
+            route("10.0.0.0", 8).ifindex(2),
+        ]))
+        .unwrap();
+        let addr = "10.1.2.3".parse().unwrap();
+        assert_eq!(Some(2), manager.lookup(addr).unwrap().unwrap().ifindex);
+
+        manager
+            .add_route(&route("10.1.0.0", 16).ifindex(3))
+            .unwrap();
+        manager.poll().unwrap();
+        assert_eq!(Some(3), manager.lookup(addr).unwrap().unwrap().ifindex);
+        manager
+            .delete_route(&route("10.1.0.0", 16).ifindex(3))
+            .unwrap();
+        manager.poll().unwrap();
+        assert_eq!(Some(2), manager.lookup(addr).unwrap().unwrap().ifindex);
+        assert!(manager
+            .lookup("fd00::1".parse().unwrap())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_routes_covering_and_within() {
+        let routes = vec![
+            route("0.0.0.0", 0).ifindex(1),
+            route("10.0.0.0", 8).ifindex(1),
+            route("10.8.1.0", 24).ifindex(2),
+            route("10.9.0.0", 16).ifindex(2),
+        ];
+        let split = Prefix::new("10.8.0.0".parse().unwrap(), 16).unwrap();
+        let manager = RouteManager::with_backend(MockOperator::new(routes.clone())).unwrap();
+        assert_eq!(
+            vec![routes[0].clone(), routes[1].clone()],
+            manager.routes_covering(split).unwrap()
+        );
+        assert_eq!(
+            vec![routes[2].clone()],
+            manager.routes_within(split).unwrap()
+        );
+
+        let manager = RouteManager::builder()
+            .listen(false)
+            .build_with_backend(MockOperator::new(routes.clone()))
+            .unwrap();
+        assert_eq!(2, manager.routes_covering(split).unwrap().len());
+        assert_eq!(
+            vec![routes[2].clone()],
+            manager.routes_within(split).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_subscribe_route_change_broadcast() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        let first = manager.subscribe_route_change();
+        let second = manager.subscribe_route_change();
+        manager.add_route(&route("10.0.0.0", 8)).unwrap();
+        manager.add_route(&route("10.1.0.0", 16)).unwrap();
+        manager.poll().unwrap();
+        manager.poll().unwrap();
+        for subscription in [&first, &second] {
+            assert!(subscription.try_recv().is_ok());
+            assert!(subscription.try_recv().is_ok());
+            assert!(subscription.try_recv().is_err());
+        }
+
+        second.unsubscribe();
+        manager.add_route(&route("10.2.0.0", 16)).unwrap();
+        manager.poll().unwrap();
+        assert!(first.try_recv().is_ok());
+        assert_eq!(1, manager.dedicated_subscribers.lock().unwrap().len());
+    }
+
+    #[test]
+    fn test_like_windows_metric() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        let fast = manager
+            .add_route_with_metric(&route("10.0.0.0", 8).ifindex(10), Metric::LikeWindows)
+            .unwrap();
+        assert_eq!(Some(25), fast.metric);
+        let best = manager
+            .add_route_with_metric(&route("10.1.0.0", 16), Metric::LikeWindows)
+            .unwrap();
+        assert_eq!((Some(1), Some(35)), (best.ifindex, best.metric));
+    }
+
+    #[test]
+    fn test_poll_error() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        let _subscription = manager
+            .subscribe_with(1, OverflowPolicy::DropNewest)
+            .unwrap();
+        manager.add_route(&route("10.0.0.0", 8)).unwrap();
+        manager.add_route(&route("10.1.0.0", 16)).unwrap();
+        manager.poll().unwrap();
+        match manager.poll_event() {
+            Err(PollError::Lagged { event, dropped }) => {
+                assert_eq!(1, dropped);
+                assert!(matches!(event.event, RouteEvent::Add(r) if r.prefix == 16));
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+        let err = manager.subscribe_with(0, OverflowPolicy::DropOldest).err();
+        assert_eq!(
+            Some(std::io::ErrorKind::InvalidInput),
+            err.map(|e| e.kind())
+        );
+
+        let manager = RouteManager::builder()
+            .listen(false)
+            .build_with_backend(MockOperator::new(vec![]))
+            .unwrap();
+        assert!(matches!(manager.poll(), Err(PollError::Disconnected)));
+    }
+
+    #[test]
+    fn test_interface_cache() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        assert_eq!(1002, manager.interface(2).unwrap().luid);
+        assert_eq!("Ethernet 3", manager.interface_by_luid(1003).unwrap().alias);
+        assert_eq!(1, manager.interface_by_alias("ethernet 1").unwrap().ifindex);
+        assert_eq!(
+            std::io::ErrorKind::NotFound,
+            manager.interface(9).unwrap_err().kind()
+        );
+        assert_eq!(
+            Some("Ethernet 2"),
+            manager.interface_names().unwrap().get(2)
+        );
+        assert_eq!(20, manager.interface_metric(Family::V4, 2).unwrap());
+    }
+
+    #[test]
+    fn test_subscribe_quiet() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        let quiet = manager.subscribe_quiet().unwrap();
+        manager.add_route(&route("169.254.0.0", 16)).unwrap();
+        manager.add_route(&route("10.0.0.0", 8)).unwrap();
+        manager.poll().unwrap();
+        manager.poll().unwrap();
+        assert_eq!(
+            "10.0.0.0/8",
+            quiet
+                .try_recv()
+                .unwrap()
+                .route()
+                .unwrap()
+                .cidr()
+                .to_string()
+        );
+        assert!(quiet.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_quiesce() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        let subscription = manager.subscribe().unwrap();
+        let seq = manager.last_sequence().unwrap();
+        manager
+            .quiesce(|| {
+                manager.add_route(&route("10.0.0.0", 8))?;
+                manager.add_route(&route("10.1.0.0", 16))
+            })
+            .unwrap();
+        manager.add_route(&route("10.2.0.0", 16)).unwrap();
+        for _ in 0..3 {
+            manager.poll().unwrap();
+        }
+        assert_eq!(3, manager.events_since(seq).unwrap().unwrap().len());
+        let event = subscription.try_recv().unwrap();
+        assert_eq!("10.2.0.0/16", event.route().unwrap().cidr().to_string());
+        assert!(subscription.try_recv().is_err());
+
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            manager.quiesce(|| panic!("inside quiet section"))
+        }));
+        assert!(panicked.is_err());
+        manager.add_route(&route("10.3.0.0", 16)).unwrap();
+        manager.poll().unwrap();
+        assert!(subscription.try_recv().is_ok());
+    }
+
+    #[test]
+    fn test_reject_prefix_len() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        let err = manager.add_route(&route("10.0.0.0", 40)).unwrap_err();
+        assert_eq!(std::io::ErrorKind::InvalidInput, err.kind());
+        let result = manager.add_routes(&[route("10.0.0.0", 8), route("10.1.0.0", 33)]);
+        assert_eq!(1, result.summary().failed);
+        let report = manager
+            .add_routes_bulk(
+                &[route("10.2.0.0", 16), route("10.3.0.0", 33)],
+                &crate::BulkOptions::new(),
+                |_| {},
+            )
+            .unwrap();
+        assert_eq!((1, 1), (report.installed, report.failed));
+        assert_eq!(2, manager.lock_owned().unwrap().len());
+    }
+
+    #[test]
+    fn test_subscribe_tamper() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        let tampered = manager.subscribe_tamper().unwrap();
+        let mine = route("10.0.0.0", 8).ifindex(1).metric(5);
+        manager.add_route(&mine).unwrap();
+        manager
+            .set_metric("10.0.0.0/8".parse().unwrap(), 1, 7)
+            .unwrap();
+        manager
+            .backend()
+            .emit(RouteEvent::Change(mine.clone().metric(50)));
+        manager.backend().emit(RouteEvent::Delete(mine.clone()));
+        for _ in 0..4 {
+            manager.poll().unwrap();
+        }
+        assert!(matches!(
+            tampered.try_recv(),
+            Ok(OwnedRouteEvent::Modified { owned, current })
+                if owned.metric == Some(7) && current.metric == Some(50)
+        ));
+        assert_eq!(
+            Ok(OwnedRouteEvent::Deleted(mine.metric(7))),
+            tampered.try_recv()
+        );
+        assert!(tampered.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_suspend() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        let subscription = manager.subscribe().unwrap();
+        let seq = manager.last_sequence().unwrap();
+        manager.suspend();
+        manager.add_route(&route("10.0.0.0", 8)).unwrap();
+        manager.poll().unwrap();
+        assert!(subscription.try_recv().is_err());
+        assert_eq!(1, manager.routes().unwrap().len());
+
+        manager.resume();
+        assert!(!manager.is_suspended());
+        assert_eq!(1, manager.events_since(seq).unwrap().unwrap().len());
+        manager
+            .delete_route(&route("10.0.0.0", 8).ifindex(1))
+            .unwrap();
+        manager.poll().unwrap();
+        assert!(matches!(subscription.try_recv(), Ok(RouteEvent::Delete(_))));
+    }
+
+    #[test]
+    fn test_cleanup_on_drop() {
+        let manager = RouteManager::builder()
+            .cleanup_on_drop(true)
+            .build_with_backend(MockOperator::new(vec![route("0.0.0.0", 0).ifindex(1)]))
+            .unwrap();
+        manager.add_route(&route("10.0.0.0", 8)).unwrap();
+        manager.add_route(&route("10.1.0.0", 16)).unwrap();
+        manager.delete_route(&route("10.1.0.0", 16)).unwrap();
+        manager.shutdown().unwrap();
+        let table = manager.backend().table.lock().unwrap().clone();
+        assert_eq!(vec![route("0.0.0.0", 0).ifindex(1)], table);
+    }
+
+    #[test]
+    fn test_resolve_next_hop() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        let mac = manager.resolve_next_hop(&route("10.0.0.0", 8)).unwrap();
+        assert_eq!(Some(MacAddr([0, 0x1b, 0x21, 0, 0, 1])), mac);
+        let silent = route("10.0.0.0", 8).gateway("192.168.1.2".parse().unwrap());
+        assert_eq!(None, manager.resolve_next_hop(&silent).unwrap());
+        let on_link = Route::new("10.0.0.0".parse().unwrap(), 8);
+        assert!(manager.resolve_next_hop(&on_link).is_err());
+    }
+
+    #[test]
+    fn test_validate_gateway() {
+        let manager = RouteManager::builder()
+            .validate_gateway(true)
+            .build_with_backend(MockOperator::new(vec![]))
+            .unwrap();
+        assert!(manager.add_route(&route("10.0.0.0", 8).ifindex(1)).is_ok());
+        let off_link = route("10.1.0.0", 16)
+            .gateway("172.16.0.1".parse().unwrap())
+            .ifindex(1);
+        let err = manager.add_route(&off_link).unwrap_err();
+        assert_eq!(std::io::ErrorKind::InvalidInput, err.kind());
+
+        let link_local = off_link.clone().gateway("169.254.0.1".parse().unwrap());
+        assert!(manager.check_gateway(&link_local).is_ok());
+        let on_link = Route::new("10.2.0.0".parse().unwrap(), 16).ifindex(1);
+        assert!(manager.check_gateway(&on_link).is_ok());
+    }
+
+    #[test]
+    fn test_wait_stable_addresses() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        let token = CancelToken::new();
+        assert!(manager
+            .wait_stable_addresses(Some(Family::V4), Duration::ZERO, &token)
+            .is_ok());
+        token.cancel();
+        let err = manager
+            .wait_stable_addresses(None, Duration::ZERO, &token)
+            .unwrap_err();
+        assert_eq!(std::io::ErrorKind::Interrupted, err.kind());
+    }
+
+    #[test]
+    fn test_trace() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![
+            route("0.0.0.0", 0).ifindex(1),
+            Route::new("192.168.1.0".parse().unwrap(), 24).ifindex(2),
+        ]))
+        .unwrap();
+        let result = manager.trace("8.8.8.8".parse().unwrap()).unwrap();
+        assert_eq!(Some(2), result.ifindex);
+        assert_eq!(Some("192.168.1.102".parse().unwrap()), result.source);
+    }
+
+    #[test]
+    fn test_advertising() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        assert!(!manager.advertising(3).unwrap());
+        manager.set_advertising(3, true).unwrap();
+        assert!(manager.advertising(3).unwrap());
+        assert!(!manager.advertising(4).unwrap());
+    }
+
+    #[test]
+    fn test_size_watermark() {
+        let manager = RouteManagerBuilder::new()
+            .size_watermark(1)
+            .build_with_backend(MockOperator::new(vec![route("0.0.0.0", 0)]))
+            .unwrap();
+        let alerts = manager.subscribe_alerts().unwrap();
+        manager
+            .backend()
+            .emit(RouteEvent::Add(route("10.0.0.0", 8)));
+        manager.poll().unwrap();
+        assert_eq!(
+            TableAlert::Size {
+                entries: 2,
+                limit: 1
+            },
+            alerts.try_recv().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_watch_best_route() {
+        let manager =
+            RouteManager::with_backend(MockOperator::new(vec![route("0.0.0.0", 0).ifindex(1)]))
+                .unwrap();
+        let changes = manager
+            .watch_best_route("10.1.2.3".parse().unwrap())
+            .unwrap();
+        manager
+            .backend()
+            .emit(RouteEvent::Add(route("172.16.0.0", 12).ifindex(2)));
+        manager.poll().unwrap();
+        assert!(changes.try_recv().is_err());
+
+        let vpn = route("10.0.0.0", 8).ifindex(3);
+        manager.backend().emit(RouteEvent::Add(vpn.clone()));
+        manager.poll().unwrap();
+        let change = changes.try_recv().unwrap();
+        assert_eq!(Some(route("0.0.0.0", 0).ifindex(1)), change.previous);
+        assert_eq!(Some(vpn), change.current);
+    }
+
+    #[test]
+    fn test_competitors() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![
+            route("0.0.0.0", 0).ifindex(1).metric(50),
+            route("0.0.0.0", 0).ifindex(3).metric(5),
+            route("10.0.0.0", 8).ifindex(2),
+        ]))
+        .unwrap();
+        let competitors = manager.competitors("0.0.0.0/0".parse().unwrap()).unwrap();
+        assert_eq!(2, competitors.len());
+        assert_eq!(Some(3), competitors[0].route.ifindex);
+        assert_eq!(35, competitors[0].effective_metric);
+        assert!(competitors[0].selected);
+        assert_eq!(60, competitors[1].effective_metric);
+        assert!(!competitors[1].selected);
+    }
+
+    #[test]
+    fn test_interface_for_binding() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![
+            route("0.0.0.0", 0).ifindex(1),
+            Route::new("192.168.1.0".parse().unwrap(), 24).ifindex(2),
+        ]))
+        .unwrap();
+        assert_eq!(
+            (1, "192.168.1.101".parse().unwrap()),
+            manager
+                .interface_for_binding("8.8.8.8".parse().unwrap())
+                .unwrap()
+        );
+        assert_eq!(
+            (2, "192.168.1.102".parse().unwrap()),
+            manager
+                .interface_for_binding("192.168.1.7".parse().unwrap())
+                .unwrap()
+        );
+        assert!(manager
+            .interface_for_binding("2001:db8::1".parse().unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn test_event_display() {
+        let route = Route::new("10.1.0.0".parse().unwrap(), 16)
+            .gateway("192.168.1.1".parse().unwrap())
+            .ifindex(12)
+            .metric(5);
+        assert_eq!(
+            "ADD 10.1.0.0/16 via 192.168.1.1 (if 12, metric 5)",
+            RouteEvent::Add(route).to_string()
+        );
+        let route = Route::new("fe80::".parse().unwrap(), 64);
+        assert_eq!(
+            "DELETE fe80::/64 via ::",
+            RouteEvent::Delete(route).to_string()
+        );
+    }
+}
@@ -0,0 +1,122 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::Route;
+
+/// Route metric to use when adding a route with ```RouteManager::add_route_with_metric```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    /// Use the value as is
+    Fixed(u32),
+    /// Pick a value relative to competing routes for the same destination and prefix
+    Auto(Preference),
+    /// Derive the value from interface link speed with the tiers Windows uses for its
+    /// automatic interface metric
+    LikeWindows,
+}
+
+/// Whether an automatically chosen metric should win or lose against competing routes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preference {
+    /// New route becomes the preferred one
+    Prefer,
+    /// New route is only used when competing routes are gone
+    Defer,
+}
+
+/// Route competing with others for the same destination and prefix, returned by
+/// ```RouteManager::competitors```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Competitor {
+    pub route: Route,
+    /// Metric of route's interface, 0 when route has no interface
+    pub interface_metric: u32,
+    /// Route metric plus interface metric, the lowest one wins
+    pub effective_metric: u32,
+    /// Whether route is the one currently used
+    pub selected: bool,
+}
+
+/// Choose route metric so that `interface_metric + metric` compares to competitors'
+/// effective metrics (route metric plus interface metric) as `preference` asks
+///
+/// Return `None` when new route can not be preferred because its interface metric alone
+/// is not lower than the best competitor
+pub(crate) fn auto_metric(
+    preference: Preference,
+    interface_metric: u32,
+    competitors: &[u32],
+) -> Option<u32> {
+    match preference {
+        Preference::Prefer => match competitors.iter().min() {
+            Some(best) => best.checked_sub(interface_metric)?.checked_sub(1),
+            None => Some(0),
+        },
+        Preference::Defer => match competitors.iter().max() {
+            Some(worst) => Some(worst.saturating_sub(interface_metric).saturating_add(1)),
+            None => Some(0),
+        },
+    }
+}
+
+/// Link speed tiers in bits per second and their metric, as Windows assigns automatic
+/// interface metric
+const LINK_SPEED_TIERS: [(u64, u32); 9] = [
+    (200_000_000_000, 5),
+    (80_000_000_000, 10),
+    (20_000_000_000, 15),
+    (10_000_000_000, 20),
+    (200_000_000, 25),
+    (80_000_000, 35),
+    (20_000_000, 45),
+    (4_000_000, 55),
+    (500_000, 65),
+];
+
+/// Metric Windows would assign automatically to an interface of link speed in bits per second
+pub(crate) fn windows_metric(link_speed: u64) -> u32 {
+    LINK_SPEED_TIERS
+        .iter()
+        .find(|(speed, _)| link_speed >= *speed)
+        .map_or(75, |(_, metric)| *metric)
+}
+
+#[cfg(test)]
+pub mod test_metric {
+    use super::{auto_metric, windows_metric, Preference};
+
+    #[test]
+    fn test_auto_metric() {
+        assert_eq!(Some(0), auto_metric(Preference::Prefer, 25, &[]));
+        assert_eq!(Some(9), auto_metric(Preference::Prefer, 25, &[35, 50]));
+        assert_eq!(None, auto_metric(Preference::Prefer, 35, &[35]));
+        assert_eq!(Some(26), auto_metric(Preference::Defer, 25, &[35, 50]));
+        assert_eq!(Some(1), auto_metric(Preference::Defer, 75, &[35]));
+    }
+
+    #[test]
+    fn test_windows_metric() {
+        assert_eq!(5, windows_metric(400_000_000_000));
+        assert_eq!(20, windows_metric(10_000_000_000));
+        assert_eq!(25, windows_metric(1_000_000_000));
+        assert_eq!(35, windows_metric(100_000_000));
+        assert_eq!(65, windows_metric(500_000));
+        assert_eq!(75, windows_metric(499_999));
+        assert_eq!(75, windows_metric(0));
+    }
+}
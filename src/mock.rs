@@ -0,0 +1,243 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! In memory backend used by tests
+
+use std::{io, net::IpAddr, sync::Mutex, time::Duration};
+
+use crossbeam_channel::{Receiver, Sender};
+
+use crate::{
+    CancelToken, Family, InterfaceConfig, InterfaceInfo, MacAddr, Prefix, Route, RouteEvent,
+    RouterDiscovery, SystemRouteOperate, TimedEvent,
+};
+
+/// Route via 192.168.1.1 on interface 1 with metric
+pub(crate) fn route(dst: &str, prefix: u8, metric: u32) -> Route {
+    Route::new(dst.parse().unwrap(), prefix)
+        .gateway("192.168.1.1".parse().unwrap())
+        .ifindex(1)
+        .metric(metric)
+}
+
+pub(crate) struct MockOperator {
+    pub(crate) table: Mutex<Vec<Route>>,
+    configs: Mutex<Vec<InterfaceConfig>>,
+    sender: Sender<TimedEvent>,
+    receiver: Receiver<TimedEvent>,
+}
+
+impl MockOperator {
+    pub(crate) fn new(table: Vec<Route>) -> Self {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        Self {
+            table: Mutex::new(table),
+            configs: Mutex::new(Vec::new()),
+            sender,
+            receiver,
+        }
+    }
+
+    /// Simulate a change made by someone else
+    pub(crate) fn emit(&self, event: RouteEvent) {
+        let mut table = self.table.lock().unwrap();
+        match &event {
+            RouteEvent::Add(route) => table.push(route.clone()),
+            RouteEvent::Delete(route) => table.retain(|r| !r.is_same_entry(route)),
+            RouteEvent::Change(route) => {
+                for r in table.iter_mut().filter(|r| r.is_same_entry(route)) {
+                    *r = route.clone();
+                }
+            }
+            RouteEvent::Initial => {}
+        }
+        self.sender.send(TimedEvent::now(event)).unwrap();
+    }
+
+    /// Entry matching route, missing interface matches any
+    fn find(&self, route: &Route) -> io::Result<Route> {
+        self.table
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|r| r.is_same_entry(route))
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not found"))
+    }
+}
+
+impl SystemRouteOperate for MockOperator {
+    fn init(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn event_receiver(&self) -> Receiver<TimedEvent> {
+        self.receiver.clone()
+    }
+
+    fn read_all_routes(&self) -> io::Result<Vec<Route>> {
+        Ok(self.table.lock().unwrap().clone())
+    }
+
+    fn add_route(&self, route: &Route) -> io::Result<Route> {
+        let route = match route.ifindex {
+            Some(_) => route.clone(),
+            None => route.clone().ifindex(self.best_interface(route.gateway)?),
+        };
+        let exists = self
+            .table
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|r| r.is_same_entry(&route));
+        if exists {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "exists"));
+        }
+        self.emit(RouteEvent::Add(route.clone()));
+        Ok(route)
+    }
+
+    fn best_interface(&self, _addr: IpAddr) -> io::Result<u32> {
+        Ok(1)
+    }
+
+    fn interface_metric(&self, _family: Family, ifindex: u32) -> io::Result<u32> {
+        Ok(ifindex * 10)
+    }
+
+    fn read_route(&self, route: &Route) -> io::Result<Route> {
+        // like GetIpForwardEntry2, which needs InterfaceIndex or InterfaceLuid
+        if route.ifindex.is_none() && route.luid.is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "route has no interface",
+            ));
+        }
+        self.find(route)
+    }
+
+    fn delete_route(&self, route: &Route) -> io::Result<()> {
+        let existing = self.find(route)?;
+        self.emit(RouteEvent::Delete(existing));
+        Ok(())
+    }
+
+    fn best_route(&self, destination: IpAddr) -> io::Result<Route> {
+        crate::trace::lookup(&self.table.lock().unwrap(), destination)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no route"))
+    }
+
+    fn resolve_neighbor(&self, addr: IpAddr, _ifindex: u32) -> io::Result<Option<MacAddr>> {
+        match addr {
+            IpAddr::V4(v4) if v4.octets()[3] == 1 => Ok(Some(MacAddr([0, 0x1b, 0x21, 0, 0, 1]))),
+            _ => Ok(None),
+        }
+    }
+
+    fn interfaces(&self) -> io::Result<Vec<InterfaceInfo>> {
+        // interfaces beyond the first three exist once a route is on them
+        let mut indexes: Vec<u32> = (1..=3).collect();
+        for ifindex in self.table.lock().unwrap().iter().filter_map(|r| r.ifindex) {
+            if !indexes.contains(&ifindex) {
+                indexes.push(ifindex);
+            }
+        }
+        Ok(indexes
+            .into_iter()
+            .map(|ifindex| InterfaceInfo {
+                ifindex,
+                luid: 1000 + ifindex as u64,
+                alias: format!("Ethernet {ifindex}"),
+                guid: format!("{{00000000-0000-0000-0000-{ifindex:012}}}"),
+            })
+            .collect())
+    }
+
+    fn interface_link_speed(&self, ifindex: u32) -> io::Result<u64> {
+        Ok(ifindex as u64 * 100_000_000)
+    }
+
+    fn interface_addresses(&self, ifindex: u32) -> io::Result<Vec<IpAddr>> {
+        Ok(vec![IpAddr::from([192, 168, 1, 100 + ifindex as u8])])
+    }
+
+    fn interface_prefixes(&self, ifindex: u32) -> io::Result<Vec<Prefix>> {
+        self.interface_addresses(ifindex)?
+            .into_iter()
+            .map(|addr| Prefix::truncate(addr, 24))
+            .collect()
+    }
+
+    fn wait_stable_addresses(
+        &self,
+        _family: Option<Family>,
+        _timeout: Duration,
+        cancel: &CancelToken,
+    ) -> io::Result<()> {
+        cancel.check()
+    }
+
+    fn interface_config(&self, family: Family, ifindex: u32) -> io::Result<InterfaceConfig> {
+        let configs = self.configs.lock().unwrap();
+        if let Some(config) = configs
+            .iter()
+            .find(|c| c.family == family && c.ifindex == ifindex)
+        {
+            return Ok(config.clone());
+        }
+        Ok(InterfaceConfig {
+            family,
+            ifindex,
+            metric: ifindex * 10,
+            use_automatic_metric: false,
+            mtu: 1500,
+            dad_transmits: 1,
+            router_discovery: RouterDiscovery::Enabled,
+            base_reachable_time: 30000,
+            retransmit_time: 1000,
+            path_mtu_discovery_timeout: 600000,
+            use_neighbor_unreachability_detection: true,
+            forwarding_enabled: false,
+            weak_host_send: false,
+            weak_host_receive: false,
+            advertising_enabled: false,
+            advertise_default_route: false,
+            min_router_advertisement_interval: 200000,
+            max_router_advertisement_interval: 600000,
+            reachable_time: 30000,
+            connected: true,
+            supports_neighbor_discovery: true,
+            supports_router_discovery: true,
+        })
+    }
+
+    fn set_interface_config(&self, config: &InterfaceConfig) -> io::Result<()> {
+        let mut configs = self.configs.lock().unwrap();
+        configs.retain(|c| c.family != config.family || c.ifindex != config.ifindex);
+        configs.push(config.clone());
+        Ok(())
+    }
+
+    fn update_route(&self, route: &Route) -> io::Result<()> {
+        let mut existing = self.read_route(route)?;
+        existing.metric = route.metric;
+        self.emit(RouteEvent::Change(existing));
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Conversions between [`Route`] and `net_route::Route`, enabled with the `net-route` feature
+//!
+//! Unspecified gateway of winroute maps to `None` gateway of net-route and back,
+//! fields net-route does not have on the target platform are dropped
+
+use crate::Route;
+
+impl From<&Route> for net_route::Route {
+    fn from(route: &Route) -> Self {
+        let mut converted = net_route::Route::new(route.destination, route.prefix);
+        if !route.gateway.is_unspecified() {
+            converted = converted.with_gateway(route.gateway);
+        }
+        if let Some(ifindex) = route.ifindex {
+            converted = converted.with_ifindex(ifindex);
+        }
+        #[cfg(any(target_os = "windows", target_os = "linux"))]
+        if let Some(metric) = route.metric {
+            converted = converted.with_metric(metric);
+        }
+        #[cfg(target_os = "windows")]
+        if let Some(luid) = route.luid {
+            converted = converted.with_luid(luid);
+        }
+        converted
+    }
+}
+
+impl From<Route> for net_route::Route {
+    fn from(route: Route) -> Self {
+        net_route::Route::from(&route)
+    }
+}
+
+impl From<&net_route::Route> for Route {
+    fn from(route: &net_route::Route) -> Self {
+        let mut converted = Route::new(route.destination, route.prefix);
+        if let Some(gateway) = route.gateway {
+            converted = converted.gateway(gateway);
+        }
+        converted.ifindex = route.ifindex;
+        #[cfg(any(target_os = "windows", target_os = "linux"))]
+        {
+            converted.metric = route.metric;
+        }
+        #[cfg(target_os = "windows")]
+        {
+            converted.luid = route.luid;
+        }
+        converted
+    }
+}
+
+impl From<net_route::Route> for Route {
+    fn from(route: net_route::Route) -> Self {
+        Route::from(&route)
+    }
+}
+
+#[cfg(test)]
+pub mod test_netroute {
+    use crate::Route;
+
+    #[test]
+    fn test_round_trip() {
+        let route = Route::new("10.0.0.0".parse().unwrap(), 8)
+            .gateway("192.168.1.1".parse().unwrap())
+            .ifindex(3)
+            .metric(5);
+        let converted = net_route::Route::from(&route);
+        assert_eq!(Some(route.gateway), converted.gateway);
+        assert_eq!(route, Route::from(converted));
+
+        let on_link = Route::new("fe80::".parse().unwrap(), 64).ifindex(3);
+        let converted = net_route::Route::from(&on_link);
+        assert_eq!(None, converted.gateway);
+        assert_eq!(on_link, Route::from(converted));
+    }
+}
@@ -0,0 +1,58 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io;
+
+use crate::{DefaultOperator, Route, RouteTableReader, SystemRouteOperate};
+
+#[cfg(windows)]
+fn system_operator() -> io::Result<DefaultOperator> {
+    Ok(crate::WindowsOperator::new())
+}
+
+#[cfg(not(windows))]
+fn system_operator() -> io::Result<DefaultOperator> {
+    Err(io::Error::other("None windows system not supported"))
+}
+
+/// Add a route to system's routing table without creating a [`RouteManager`](crate::RouteManager)
+///
+/// When neither ifindex nor luid is set, the best interface for gateway is used
+///
+/// # Errors
+/// when system api return error, ERROR_ACCESS_DENIED when not called by an administrator
+pub fn add_route(route: &Route) -> io::Result<()> {
+    system_operator()?.add_route(route)?;
+    Ok(())
+}
+
+/// Delete a route from system's routing table without creating a [`RouteManager`](crate::RouteManager)
+///
+/// # Errors
+/// when system api return error, ERROR_ACCESS_DENIED when not called by an administrator
+pub fn delete_route(route: &Route) -> io::Result<()> {
+    system_operator()?.delete_route(route)
+}
+
+/// Read system's routing table, include IPv6 and IPv4 routes
+///
+/// # Errors
+/// when system api return error
+pub fn list_routes() -> io::Result<Vec<Route>> {
+    RouteTableReader::new()?.routes()
+}
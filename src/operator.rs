@@ -0,0 +1,227 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{io, net::IpAddr, time::Duration};
+
+use crossbeam_channel::Receiver;
+
+use crate::{
+    CancelToken, ConnectionEvent, Family, InterfaceConfig, InterfaceInfo, MacAddr, Prefix, Route,
+    TimedEvent,
+};
+
+/// Backend that reads and modifies the system routing table on behalf of a
+/// [`RouteManager`](crate::RouteManager)
+///
+/// Implement it to run the manager against a simulated table, see ```RouteManager::with_backend```
+pub trait SystemRouteOperate: Send + Sync {
+    /// Start watching the routing table, called once when manager is created
+    fn init(&self) -> io::Result<()>;
+    /// Receiver of change events produced by the backend, stamped when backend was notified
+    fn event_receiver(&self) -> Receiver<TimedEvent>;
+    /// How many events backend discarded to its overflow policy since last call, manager
+    /// reads the table again when some were lost
+    fn take_lost_events(&self) -> usize {
+        0
+    }
+    /// Read every route of the routing table
+    fn read_all_routes(&self) -> io::Result<Vec<Route>>;
+    /// Install route, return it as submitted to system with interface resolved
+    fn add_route(&self, route: &Route) -> io::Result<Route>;
+    /// Install routes one after another, results are aligned with routes
+    ///
+    /// Override it when backend has a faster path than repeated ```add_route```
+    fn add_routes(&self, routes: &[Route]) -> Vec<io::Result<Route>> {
+        routes.iter().map(|route| self.add_route(route)).collect()
+    }
+    /// Index of the interface system would use to reach addr
+    fn best_interface(&self, addr: IpAddr) -> io::Result<u32>;
+    /// Interface metric of family on interface ifindex
+    fn interface_metric(&self, family: Family, ifindex: u32) -> io::Result<u32>;
+    /// Read the system entry matching route's destination, prefix, gateway and interface
+    fn read_route(&self, route: &Route) -> io::Result<Route>;
+    /// Delete the system entry matching route
+    fn delete_route(&self, route: &Route) -> io::Result<()>;
+    /// Modify mutable properties (metric) of the existing entry matching route in place
+    fn update_route(&self, route: &Route) -> io::Result<()>;
+    /// Route system would use to reach destination, with the interface and next hop it
+    /// selected
+    ///
+    /// Backends without a route lookup return an `Unsupported` error
+    fn best_route(&self, destination: IpAddr) -> io::Result<Route> {
+        let _ = destination;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "backend has no route lookup",
+        ))
+    }
+    /// Link layer address of neighbor addr on interface ifindex, resolving it when it is
+    /// not cached, `None` when neighbor does not answer
+    ///
+    /// Backends without a neighbor table return an `Unsupported` error
+    fn resolve_neighbor(&self, addr: IpAddr, ifindex: u32) -> io::Result<Option<MacAddr>> {
+        let _ = (addr, ifindex);
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "backend has no neighbor table",
+        ))
+    }
+    /// Like ```resolve_neighbor``` but only trusts entries confirmed reachable, soliciting
+    /// the neighbor again when its entry is stale, probing or unreachable
+    ///
+    /// Defaults to ```resolve_neighbor``` for backends without neighbor states
+    fn probe_neighbor(&self, addr: IpAddr, ifindex: u32) -> io::Result<Option<MacAddr>> {
+        self.resolve_neighbor(addr, ifindex)
+    }
+    /// Identities of all interfaces present in system
+    ///
+    /// Backends without interface information return an `Unsupported` error
+    fn interfaces(&self) -> io::Result<Vec<InterfaceInfo>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "backend has no interface information",
+        ))
+    }
+    /// Link speed of interface ifindex in bits per second
+    ///
+    /// Backends without interface information return an `Unsupported` error
+    fn interface_link_speed(&self, ifindex: u32) -> io::Result<u64> {
+        let _ = ifindex;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "backend has no interface information",
+        ))
+    }
+    /// Unicast addresses assigned to interface ifindex
+    ///
+    /// Backends without interface information return an `Unsupported` error
+    fn interface_addresses(&self, ifindex: u32) -> io::Result<Vec<IpAddr>> {
+        let _ = ifindex;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "backend has no interface addresses",
+        ))
+    }
+    /// Subnets interface ifindex is directly connected to, one per unicast address
+    ///
+    /// Backends without interface information return an `Unsupported` error
+    fn interface_prefixes(&self, ifindex: u32) -> io::Result<Vec<Prefix>> {
+        let _ = ifindex;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "backend has no interface addresses",
+        ))
+    }
+    /// Block until duplicate address detection finished for every unicast address of family,
+    /// both families when `None`, or until timeout elapses or cancel is cancelled
+    ///
+    /// Backends without interface information return an `Unsupported` error
+    fn wait_stable_addresses(
+        &self,
+        family: Option<Family>,
+        timeout: Duration,
+        cancel: &CancelToken,
+    ) -> io::Result<()> {
+        let _ = (family, timeout, cancel);
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "backend has no interface addresses",
+        ))
+    }
+    /// Receiver of dial-up, RAS and VPN adapter connection state changes, watching starts
+    /// with the first call
+    ///
+    /// Backends without adapter information return an `Unsupported` error
+    fn connection_events(&self) -> io::Result<Receiver<ConnectionEvent>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "backend has no adapter information",
+        ))
+    }
+    /// IP configuration of family on interface ifindex
+    ///
+    /// Backends without interface configuration return an `Unsupported` error
+    fn interface_config(&self, family: Family, ifindex: u32) -> io::Result<InterfaceConfig> {
+        let _ = (family, ifindex);
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "backend has no interface configuration",
+        ))
+    }
+    /// Write writable fields of config to its interface
+    ///
+    /// Backends without interface configuration return an `Unsupported` error
+    fn set_interface_config(&self, config: &InterfaceConfig) -> io::Result<()> {
+        let _ = config;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "backend has no interface configuration",
+        ))
+    }
+}
+
+/// Backend used by ```RouteManager::new```, the Windows IP Helper API
+#[cfg(windows)]
+pub type DefaultOperator = crate::windows::WindowsOperator;
+
+/// Backend used by ```RouteManager::new```, none exists on this platform
+#[cfg(not(windows))]
+pub type DefaultOperator = NoSystemOperator;
+
+/// Placeholder backend on non windows platforms, it can not be constructed
+#[cfg(not(windows))]
+pub enum NoSystemOperator {}
+
+#[cfg(not(windows))]
+impl SystemRouteOperate for NoSystemOperator {
+    fn init(&self) -> io::Result<()> {
+        match *self {}
+    }
+
+    fn event_receiver(&self) -> Receiver<TimedEvent> {
+        match *self {}
+    }
+
+    fn read_all_routes(&self) -> io::Result<Vec<Route>> {
+        match *self {}
+    }
+
+    fn add_route(&self, _route: &Route) -> io::Result<Route> {
+        match *self {}
+    }
+
+    fn best_interface(&self, _addr: IpAddr) -> io::Result<u32> {
+        match *self {}
+    }
+
+    fn interface_metric(&self, _family: Family, _ifindex: u32) -> io::Result<u32> {
+        match *self {}
+    }
+
+    fn read_route(&self, _route: &Route) -> io::Result<Route> {
+        match *self {}
+    }
+
+    fn delete_route(&self, _route: &Route) -> io::Result<()> {
+        match *self {}
+    }
+
+    fn update_route(&self, _route: &Route) -> io::Result<()> {
+        match *self {}
+    }
+}
@@ -0,0 +1,107 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+use crate::RouteEvent;
+
+/// How long an event caused by the manager waits for backend to report it
+const OWN_EVENT_TTL: Duration = Duration::from_secs(10);
+
+/// Who caused a route event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+    /// Caused by a change made through this manager
+    SelfOriginated,
+    /// Caused by someone else, e.g. another process or the system itself
+    External,
+}
+
+/// Route event with its origin, see ```RouteManager::subscribe_tagged```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaggedEvent {
+    pub event: RouteEvent,
+    pub origin: Origin,
+}
+
+impl Display for TaggedEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.origin {
+            Origin::SelfOriginated => write!(f, "{} (self)", self.event),
+            Origin::External => write!(f, "{} (external)", self.event),
+        }
+    }
+}
+
+/// Events manager caused that backend has not reported yet
+#[derive(Debug, Default)]
+pub(crate) struct OwnEvents {
+    // event, when it was noted and whether it was noted while quiescing
+    events: Vec<(RouteEvent, Instant, bool)>,
+}
+
+impl OwnEvents {
+    /// Remember event caused by manager, quiet ones are not delivered to subscribers
+    pub(crate) fn note(&mut self, event: RouteEvent, quiet: bool) {
+        self.expire();
+        self.events.push((event, Instant::now(), quiet));
+    }
+
+    /// Forget and return whether event was noted quiet, `None` when manager did not cause it
+    pub(crate) fn take(&mut self, event: &RouteEvent) -> Option<bool> {
+        if self.events.is_empty() {
+            return None;
+        }
+        self.expire();
+        let index = self.events.iter().position(|(noted, _, _)| {
+            std::mem::discriminant(noted) == std::mem::discriminant(event)
+                && noted
+                    .route()
+                    .zip(event.route())
+                    .is_some_and(|(noted, route)| noted.is_same_entry(route))
+        })?;
+        Some(self.events.remove(index).2)
+    }
+
+    // backend never reported them, e.g. route was changed again before notification
+    fn expire(&mut self) {
+        self.events
+            .retain(|(_, noted, _)| noted.elapsed() < OWN_EVENT_TTL);
+    }
+}
+
+#[cfg(test)]
+pub mod test_origin {
+    use super::OwnEvents;
+    use crate::{Route, RouteEvent};
+
+    #[test]
+    fn test_own_events() {
+        let route = Route::new("10.0.0.0".parse().unwrap(), 8);
+        let mut own = OwnEvents::default();
+        own.note(RouteEvent::Add(route.clone()), false);
+        own.note(RouteEvent::Delete(route.clone()), true);
+        assert_eq!(None, own.take(&RouteEvent::Change(route.clone())));
+        assert_eq!(Some(true), own.take(&RouteEvent::Delete(route.clone())));
+        assert_eq!(Some(false), own.take(&RouteEvent::Add(route.clone())));
+        assert_eq!(None, own.take(&RouteEvent::Add(route)));
+    }
+}
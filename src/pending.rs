@@ -0,0 +1,193 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{fmt::Display, io};
+
+use crate::{InterfaceInfo, Route, RouteEvent, RouteManager, SystemRouteOperate};
+
+/// Interface a pending route waits for
+#[cfg_attr(feature = "serializable", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InterfaceSelector {
+    Index(u32),
+    Luid(u64),
+    /// Interface alias, compared case insensitively
+    Alias(String),
+    /// Interface GUID, with or without braces, compared case insensitively
+    Guid(String),
+}
+
+impl InterfaceSelector {
+    /// Whether interface is the one selected
+    pub fn matches(&self, interface: &InterfaceInfo) -> bool {
+        match self {
+            InterfaceSelector::Index(ifindex) => interface.ifindex == *ifindex,
+            InterfaceSelector::Luid(luid) => interface.luid == *luid,
+            InterfaceSelector::Alias(alias) => interface.alias.eq_ignore_ascii_case(alias),
+            InterfaceSelector::Guid(guid) => {
+                let bare = |g: &str| g.trim_matches(|c| c == '{' || c == '}').to_string();
+                bare(&interface.guid).eq_ignore_ascii_case(&bare(guid))
+            }
+        }
+    }
+}
+
+impl Display for InterfaceSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InterfaceSelector::Index(ifindex) => write!(f, "interface {ifindex}"),
+            InterfaceSelector::Luid(luid) => write!(f, "interface luid {luid}"),
+            InterfaceSelector::Alias(alias) => write!(f, "interface {alias:?}"),
+            InterfaceSelector::Guid(guid) => write!(f, "interface {guid}"),
+        }
+    }
+}
+
+impl<O: SystemRouteOperate> RouteManager<O> {
+    /// Add route on the selected interface, or queue it until that interface appears when it
+    /// does not exist yet, e.g. a TUN adapter created after the application started
+    ///
+    /// Queued routes are installed by ```poll``` as soon as a route appears on the selected
+    /// interface, which Windows does once the interface gets an address. A queued route that
+    /// fails to install stays queued and is tried again on the next route of that interface
+    ///
+    /// Return the installed route, `None` when it was queued
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// let route = Route::new("10.8.0.0".parse().unwrap(), 16);
+    /// manager
+    ///     .add_route_pending(&route, InterfaceSelector::Alias("wg0".to_string()))
+    ///     .unwrap();
+    /// ```
+    ///
+    /// # Errors
+    /// When backend has no interface information, or interface exists and adding route fails
+    pub fn add_route_pending(
+        &self,
+        route: &Route,
+        selector: InterfaceSelector,
+    ) -> io::Result<Option<Route>> {
+        let found = self.find_interface(|i| selector.matches(i), || selector.to_string());
+        match found {
+            Ok(interface) => Ok(Some(
+                self.install(&route.clone().ifindex(interface.ifindex))?,
+            )),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                self.lock_pending()?.push((route.clone(), selector));
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Routes queued by ```add_route_pending``` that still wait for their interface
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn pending_routes(&self) -> io::Result<Vec<(Route, InterfaceSelector)>> {
+        Ok(self.lock_pending()?.clone())
+    }
+
+    /// Stop waiting for route queued by ```add_route_pending```, return whether it was queued
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn cancel_pending(&self, route: &Route) -> io::Result<bool> {
+        let mut pending = self.lock_pending()?;
+        let queued = pending.len();
+        pending.retain(|(r, _)| r != route);
+        Ok(pending.len() != queued)
+    }
+
+    /// Install queued routes waiting for the interface a newly added route is on
+    pub(crate) fn install_pending(&self, event: &RouteEvent) {
+        let RouteEvent::Add(Route {
+            ifindex: Some(ifindex),
+            ..
+        }) = event
+        else {
+            return;
+        };
+        let Ok(mut pending) = self.lock_pending() else {
+            return;
+        };
+        if pending.is_empty() {
+            return;
+        }
+        let Ok(interface) = self.interface(*ifindex) else {
+            return;
+        };
+        pending.retain(|(route, selector)| {
+            !selector.matches(&interface) || self.install(&route.clone().ifindex(*ifindex)).is_err()
+        });
+    }
+}
+
+#[cfg(test)]
+pub mod test_pending {
+    use super::InterfaceSelector;
+    use crate::{mock::MockOperator, InterfaceInfo, Route, RouteEvent, RouteManager};
+
+    #[test]
+    fn test_matches() {
+        let interface = InterfaceInfo {
+            ifindex: 7,
+            luid: 42,
+            alias: "wg0".to_string(),
+            guid: "{4D36E972-E325-11CE-BFC1-08002BE10318}".to_string(),
+        };
+        assert!(InterfaceSelector::Index(7).matches(&interface));
+        assert!(InterfaceSelector::Luid(42).matches(&interface));
+        assert!(InterfaceSelector::Alias("WG0".to_string()).matches(&interface));
+        let guid = "4d36e972-e325-11ce-bfc1-08002be10318".to_string();
+        assert!(InterfaceSelector::Guid(guid).matches(&interface));
+        assert!(!InterfaceSelector::Index(8).matches(&interface));
+    }
+
+    #[test]
+    fn test_add_route_pending() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        let route = Route::new("10.8.0.0".parse().unwrap(), 16);
+        let installed = manager
+            .add_route_pending(&route, InterfaceSelector::Alias("ethernet 2".to_string()))
+            .unwrap();
+        assert_eq!(Some(2), installed.unwrap().ifindex);
+
+        let tun = InterfaceSelector::Index(4);
+        assert_eq!(
+            None,
+            manager.add_route_pending(&route, tun.clone()).unwrap()
+        );
+        assert_eq!(
+            vec![(route.clone(), tun)],
+            manager.pending_routes().unwrap()
+        );
+        manager.poll().unwrap();
+
+        // interface 4 shows up with its on-link route
+        let link = Route::new("10.9.0.0".parse().unwrap(), 24).ifindex(4);
+        manager.backend().emit(RouteEvent::Add(link));
+        manager.poll().unwrap();
+        assert!(manager.pending_routes().unwrap().is_empty());
+        assert!(matches!(manager.poll_event(), Ok(RouteEvent::Add(r)) if r.ifindex == Some(4)));
+        assert!(!manager.cancel_pending(&route).unwrap());
+    }
+}
@@ -0,0 +1,440 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Forward route events to other processes over a Windows named pipe, enabled with the
+//! `pipe` feature
+//!
+//! Every event is framed as a 4 byte little endian length followed by the encoded event
+
+use std::io::{self, Read, Write};
+
+use crate::RouteEvent;
+
+/// Frames larger than this are rejected instead of allocated
+const MAX_FRAME: usize = 1 << 20;
+
+/// Encoding of events inside frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameFormat {
+    /// serde_json encoding, readable from any language
+    Json,
+    /// bincode encoding of [`RouteEvent::to_bytes`], needs the `binary` feature
+    #[cfg(feature = "binary")]
+    Binary,
+}
+
+impl FrameFormat {
+    fn encode(&self, event: &RouteEvent) -> io::Result<Vec<u8>> {
+        match self {
+            FrameFormat::Json => serde_json::to_vec(event).map_err(io::Error::from),
+            #[cfg(feature = "binary")]
+            FrameFormat::Binary => event.to_bytes(),
+        }
+    }
+
+    fn decode(&self, bytes: &[u8]) -> io::Result<RouteEvent> {
+        match self {
+            FrameFormat::Json => serde_json::from_slice(bytes).map_err(io::Error::from),
+            #[cfg(feature = "binary")]
+            FrameFormat::Binary => RouteEvent::from_bytes(bytes),
+        }
+    }
+}
+
+/// Write length prefixed events, every event is flushed
+pub struct FrameWriter<W: Write> {
+    writer: W,
+    format: FrameFormat,
+}
+
+impl<W: Write> FrameWriter<W> {
+    pub fn new(writer: W, format: FrameFormat) -> Self {
+        Self { writer, format }
+    }
+
+    /// Write one event
+    ///
+    /// # Errors
+    /// When event can not be encoded or writing fails, e.g. the reader disconnected
+    pub fn send(&mut self, event: &RouteEvent) -> io::Result<()> {
+        let payload = self.format.encode(event)?;
+        let len = u32::try_from(payload.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "event too large"))?;
+        self.writer.write_all(&len.to_le_bytes())?;
+        self.writer.write_all(&payload)?;
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Read events written by [`FrameWriter`]
+///
+/// ```rust no_run
+/// use winroute::*;
+///
+/// let reader = FrameReader::connect("winroute-events", FrameFormat::Json).unwrap();
+/// for event in reader {
+///     println!("{}", event.unwrap());
+/// }
+/// ```
+pub struct FrameReader<R: Read> {
+    reader: R,
+    format: FrameFormat,
+}
+
+impl FrameReader<std::fs::File> {
+    /// Connect to pipe `\\.\pipe\<name>` served by ```RouteManager::serve_pipe```
+    ///
+    /// # Errors
+    /// When pipe does not exist or all its instances are busy
+    #[cfg(windows)]
+    pub fn connect(name: &str, format: FrameFormat) -> io::Result<Self> {
+        let pipe = std::fs::OpenOptions::new()
+            .read(true)
+            .open(pipe_path(name))?;
+        Ok(Self::new(pipe, format))
+    }
+
+    #[cfg(not(windows))]
+    pub fn connect(_name: &str, _format: FrameFormat) -> io::Result<Self> {
+        Err(io::Error::other("None windows system not supported"))
+    }
+}
+
+impl<R: Read> FrameReader<R> {
+    pub fn new(reader: R, format: FrameFormat) -> Self {
+        Self { reader, format }
+    }
+
+    /// Read next event, `None` when writer closed the stream between frames
+    ///
+    /// # Errors
+    /// When reading fails, stream ends inside a frame or frame can not be decoded
+    pub fn recv(&mut self) -> io::Result<Option<RouteEvent>> {
+        let mut len = [0u8; 4];
+        match self.reader.read_exact(&mut len) {
+            Ok(()) => {}
+            // a closed pipe reports BrokenPipe instead of EOF
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::UnexpectedEof | io::ErrorKind::BrokenPipe
+                ) =>
+            {
+                return Ok(None)
+            }
+            Err(e) => return Err(e),
+        }
+        let len = u32::from_le_bytes(len) as usize;
+        if len > MAX_FRAME {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("frame of {} bytes exceeds {}", len, MAX_FRAME),
+            ));
+        }
+        let mut payload = vec![0u8; len];
+        self.reader.read_exact(&mut payload)?;
+        self.format.decode(&payload).map(Some)
+    }
+}
+
+impl<R: Read> Iterator for FrameReader<R> {
+    type Item = io::Result<RouteEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv().transpose()
+    }
+}
+
+#[cfg(windows)]
+fn pipe_path(name: &str) -> String {
+    format!(r"\\.\pipe\{}", name)
+}
+
+#[cfg(windows)]
+pub use server::PipeServer;
+
+#[cfg(windows)]
+mod server {
+    use std::{
+        fs::File,
+        io,
+        os::windows::{
+            ffi::OsStrExt,
+            io::{AsRawHandle, FromRawHandle},
+        },
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        thread::JoinHandle,
+    };
+
+    use crossbeam_channel::{Sender, TrySendError};
+    use winapi::um::{
+        handleapi::INVALID_HANDLE_VALUE,
+        ioapiset::CancelSynchronousIo,
+        namedpipeapi::{ConnectNamedPipe, CreateNamedPipeW, DisconnectNamedPipe},
+        winbase::{
+            FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_OUTBOUND, PIPE_REJECT_REMOTE_CLIENTS,
+            PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+        },
+    };
+
+    use super::{pipe_path, FrameFormat, FrameWriter};
+    use crate::{RouteEvent, RouteManager, SystemRouteOperate, ThreadOptions};
+
+    /// ERROR_PIPE_CONNECTED, client connected between create and connect
+    const PIPE_CONNECTED: i32 = 535;
+
+    /// Events queued for a client before it is considered stalled and disconnected
+    const CLIENT_QUEUE: usize = 1024;
+
+    /// Named pipe forwarding route events to every connected client, created by
+    /// ```RouteManager::serve_pipe```, stops serving when dropped
+    pub struct PipeServer {
+        name: String,
+        stopped: Arc<AtomicBool>,
+        stop: Option<Sender<()>>,
+        threads: Vec<JoinHandle<()>>,
+    }
+
+    impl PipeServer {
+        /// Pipe name clients pass to ```FrameReader::connect```
+        pub fn name(&self) -> &str {
+            &self.name
+        }
+    }
+
+    impl Drop for PipeServer {
+        fn drop(&mut self) {
+            self.stopped.store(true, Ordering::SeqCst);
+            self.stop.take();
+            // wake the accept thread blocked waiting for a client
+            let _ = std::fs::OpenOptions::new()
+                .read(true)
+                .open(pipe_path(&self.name));
+            for thread in self.threads.drain(..) {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    /// Connected client, its writer thread drains queue into pipe
+    struct Client {
+        queue: Sender<RouteEvent>,
+        pipe: Arc<File>,
+        thread: JoinHandle<()>,
+    }
+
+    impl Client {
+        fn spawn(pipe: File, format: FrameFormat, threads: &ThreadOptions) -> io::Result<Self> {
+            let pipe = Arc::new(pipe);
+            let (queue, events) = crossbeam_channel::bounded::<RouteEvent>(CLIENT_QUEUE);
+            let writing = pipe.clone();
+            let thread = threads.spawn("pipe-client", move || {
+                let mut writer = FrameWriter::new(&*writing, format);
+                while let Ok(event) = events.recv() {
+                    if writer.send(&event).is_err() {
+                        break;
+                    }
+                }
+            })?;
+            Ok(Self {
+                queue,
+                pipe,
+                thread,
+            })
+        }
+
+        /// Force client end closed and abort a write blocked on it, then wait for writer
+        fn close(self) {
+            drop(self.queue);
+            unsafe {
+                DisconnectNamedPipe(self.pipe.as_raw_handle() as _);
+                CancelSynchronousIo(self.thread.as_raw_handle() as _);
+            }
+            let _ = self.thread.join();
+        }
+    }
+
+    /// Create an outbound instance of pipe, `first` fails when another process already serves
+    /// a pipe with that name
+    fn create(name: &str, first: bool) -> io::Result<File> {
+        let path: Vec<u16> = std::ffi::OsStr::new(&pipe_path(name))
+            .encode_wide()
+            .chain(Some(0))
+            .collect();
+        let mut open_mode = PIPE_ACCESS_OUTBOUND;
+        if first {
+            open_mode |= FILE_FLAG_FIRST_PIPE_INSTANCE;
+        }
+        let handle = unsafe {
+            CreateNamedPipeW(
+                path.as_ptr(),
+                open_mode,
+                PIPE_TYPE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                PIPE_UNLIMITED_INSTANCES,
+                64 * 1024,
+                0,
+                0,
+                std::ptr::null_mut(),
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error());
+        }
+        // owns the handle from here, closing it on every return path
+        Ok(unsafe { File::from_raw_handle(handle as _) })
+    }
+
+    /// Wait for a client to connect to pipe instance
+    fn connect(pipe: &File) -> io::Result<()> {
+        if unsafe { ConnectNamedPipe(pipe.as_raw_handle() as _, std::ptr::null_mut()) } == 0 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() != Some(PIPE_CONNECTED) {
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    impl<O: SystemRouteOperate> RouteManager<O> {
+        /// Serve processed route events on pipe `\\.\pipe\<name>`, so a privileged process
+        /// running the manager can feed unprivileged ones reading with [`FrameReader`](super::FrameReader)
+        ///
+        /// Only local clients are accepted, pipe uses the default security descriptor which
+        /// lets any local user read. Every client has its own queue, a client that stops
+        /// reading is disconnected once its queue is full instead of stalling the others
+        ///
+        /// ```rust no_run
+        /// use winroute::*;
+        ///
+        /// let manager = RouteManager::new().unwrap();
+        /// let _server = manager.serve_pipe("winroute-events", FrameFormat::Json).unwrap();
+        /// loop {
+        ///     manager.poll().unwrap();
+        /// }
+        /// ```
+        ///
+        /// # Errors
+        /// When another process already serves pipe name, subscription can not be registered
+        /// or threads can not be spawned
+        pub fn serve_pipe(&self, name: &str, format: FrameFormat) -> io::Result<PipeServer> {
+            let mut pending = create(name, true)?;
+            let subscription = self.subscribe()?;
+            let clients: Arc<Mutex<Vec<Client>>> = Arc::default();
+            let stopped = Arc::new(AtomicBool::new(false));
+            let (stop, stop_receiver) = crossbeam_channel::bounded::<()>(0);
+
+            let accept_thread = {
+                let (name, clients, stopped) = (name.to_string(), clients.clone(), stopped.clone());
+                let threads = self.thread_options().clone();
+                self.thread_options().spawn("pipe-accept", move || {
+                    while connect(&pending).is_ok() {
+                        if stopped.load(Ordering::SeqCst) {
+                            break;
+                        }
+                        let Ok(next) = create(&name, false) else {
+                            break;
+                        };
+                        let pipe = std::mem::replace(&mut pending, next);
+                        let Ok(client) = Client::spawn(pipe, format, &threads) else {
+                            continue;
+                        };
+                        match clients.lock() {
+                            Ok(mut clients) => clients.push(client),
+                            Err(_) => client.close(),
+                        }
+                    }
+                })?
+            };
+            let forward_thread = self.thread_options().spawn("pipe", move || {
+                loop {
+                    crossbeam_channel::select! {
+                        recv(subscription.receiver()) -> event => {
+                            let Ok(event) = event else { break };
+                            let Ok(mut clients) = clients.lock() else { break };
+                            let mut stalled = Vec::new();
+                            for client in std::mem::take(&mut *clients) {
+                                match client.queue.try_send(event.clone()) {
+                                    Ok(()) => clients.push(client),
+                                    Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) => {
+                                        stalled.push(client)
+                                    }
+                                }
+                            }
+                            drop(clients);
+                            stalled.into_iter().for_each(Client::close);
+                        }
+                        recv(stop_receiver) -> _ => break,
+                    }
+                }
+                if let Ok(mut clients) = clients.lock() {
+                    clients.drain(..).for_each(Client::close);
+                }
+            })?;
+            Ok(PipeServer {
+                name: name.to_string(),
+                stopped,
+                stop: Some(stop),
+                threads: vec![accept_thread, forward_thread],
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test_pipe {
+    use super::{FrameFormat, FrameReader, FrameWriter};
+    use crate::{Route, RouteEvent};
+
+    #[test]
+    fn test_frames() {
+        let events = vec![
+            RouteEvent::Add(Route::new("10.0.0.0".parse().unwrap(), 8).ifindex(3)),
+            RouteEvent::Delete(Route::new("fe80::".parse().unwrap(), 64)),
+        ];
+        let mut formats = vec![FrameFormat::Json];
+        #[cfg(feature = "binary")]
+        formats.push(FrameFormat::Binary);
+        for format in formats {
+            let mut writer = FrameWriter::new(Vec::new(), format);
+            for event in &events {
+                writer.send(event).unwrap();
+            }
+            let bytes = writer.into_inner();
+            let read: Vec<RouteEvent> = FrameReader::new(bytes.as_slice(), format)
+                .collect::<Result<_, _>>()
+                .unwrap();
+            assert_eq!(events, read);
+
+            let truncated = &bytes[..bytes.len() - 1];
+            let mut reader = FrameReader::new(truncated, format);
+            assert!(reader.recv().unwrap().is_some());
+            assert!(reader.recv().is_err());
+        }
+
+        let header = u32::MAX.to_le_bytes();
+        let mut oversized = FrameReader::new(&header[..], FrameFormat::Json);
+        assert!(oversized.recv().is_err());
+    }
+}
@@ -0,0 +1,157 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{io, sync::Arc, thread::JoinHandle};
+
+use crossbeam_channel::Sender;
+
+use crate::{PollError, RouteManager, SystemRouteOperate};
+
+/// Thread driving ```RouteManager::poll```, created by ```RouteManager::spawn_poller```,
+/// stopped when dropped
+pub struct Poller {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<Result<(), PollError>>>,
+}
+
+impl Poller {
+    /// Whether thread ended, because backend went away or processing failed
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(JoinHandle::is_finished)
+    }
+
+    /// Stop polling and wait for the thread to end
+    ///
+    /// # Errors
+    /// ```PollError::Internal``` when processing an event failed before
+    pub fn shutdown(mut self) -> Result<(), PollError> {
+        self.stop.take();
+        self.wait()
+    }
+
+    /// Wait until thread ends on its own, when backend goes away or processing fails
+    ///
+    /// # Errors
+    /// ```PollError::Internal``` when processing an event failed
+    pub fn join(mut self) -> Result<(), PollError> {
+        self.wait()
+    }
+
+    fn wait(&mut self) -> Result<(), PollError> {
+        match self.thread.take().map(JoinHandle::join) {
+            Some(Ok(result)) => result,
+            Some(Err(_)) => Err(PollError::Internal("poller thread panicked".to_string())),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Poller {
+    fn drop(&mut self) {
+        // disconnecting wakes the poller thread immediately
+        self.stop.take();
+        let _ = self.wait();
+    }
+}
+
+impl<O: SystemRouteOperate + 'static> RouteManager<O> {
+    /// Start a thread that polls manager until the returned handle is shut down or dropped,
+    /// replacing the usual `loop { manager.poll() }` thread
+    ///
+    /// Lagging subscriptions do not stop it, it ends when backend goes away or processing an
+    /// event fails with ```PollError::Internal```
+    ///
+    /// ```rust no_run
+    /// use std::sync::Arc;
+    /// use winroute::*;
+    ///
+    /// let manager = Arc::new(RouteManager::new().unwrap());
+    /// let poller = manager.spawn_poller().unwrap();
+    /// for event in manager.subscribe().unwrap().iter().take(10) {
+    ///     println!("{event}");
+    /// }
+    /// poller.shutdown().unwrap();
+    /// ```
+    ///
+    /// # Errors
+    /// ErrorKind::Unsupported when listener is disabled, or when thread can not be spawned
+    pub fn spawn_poller(self: &Arc<Self>) -> io::Result<Poller> {
+        let backend = self
+            .backend_events()
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "route change listener is disabled",
+                )
+            })?
+            .clone();
+        let (stop, stopped) = crossbeam_channel::bounded::<()>(0);
+        let closed = self.closed().clone();
+        let manager = self.clone();
+        let thread = self.thread_options().spawn("poller", move || loop {
+            crossbeam_channel::select! {
+                recv(backend) -> timed => match timed {
+                    Ok(timed) => match manager.handle(timed) {
+                        Ok(_) | Err(PollError::Lagged { .. }) => {}
+                        Err(e) => return Err(e),
+                    },
+                    Err(_) => return Ok(()),
+                },
+                recv(stopped) -> _ => return Ok(()),
+                recv(closed) -> _ => return Ok(()),
+            }
+        })?;
+        Ok(Poller {
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod test_poller {
+    use std::{sync::Arc, time::Duration};
+
+    use crate::{mock::MockOperator, Route, RouteEvent, RouteManager};
+
+    #[test]
+    fn test_spawn_poller() {
+        let manager = Arc::new(RouteManager::with_backend(MockOperator::new(vec![])).unwrap());
+        let subscription = manager.subscribe().unwrap();
+        let poller = manager.spawn_poller().unwrap();
+        manager
+            .add_route(&Route::new("10.0.0.0".parse().unwrap(), 8))
+            .unwrap();
+        let event = subscription.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert!(matches!(event, RouteEvent::Add(_)));
+        assert!(!poller.is_finished());
+        poller.shutdown().unwrap();
+        assert_eq!(1, Arc::strong_count(&manager));
+
+        let poller = manager.spawn_poller().unwrap();
+        manager.shutdown().unwrap();
+        poller.join().unwrap();
+
+        let quiet = RouteManager::builder()
+            .listen(false)
+            .build_with_backend(MockOperator::new(vec![]))
+            .unwrap();
+        let quiet = Arc::new(quiet);
+        assert!(quiet.spawn_poller().is_err());
+    }
+}
@@ -0,0 +1,216 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io;
+
+use serde::Deserialize;
+
+use crate::{Prefix, Route, RouteManager, SystemRouteOperate};
+
+/// Fields of a `Get-NetRoute` object used by winroute, others are ignored
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct NetRoute {
+    destination_prefix: String,
+    next_hop: String,
+    // `Get-NetRoute` objects carry both, either one is enough
+    interface_index: Option<u32>,
+    #[serde(rename = "ifIndex")]
+    if_index: Option<u32>,
+    route_metric: Option<u32>,
+}
+
+impl TryFrom<NetRoute> for Route {
+    type Error = io::Error;
+
+    fn try_from(entry: NetRoute) -> Result<Self, Self::Error> {
+        let invalid = |field: &str, value: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid {} {:?}", field, value),
+            )
+        };
+        let prefix: Prefix = entry
+            .destination_prefix
+            .parse()
+            .map_err(|_| invalid("DestinationPrefix", &entry.destination_prefix))?;
+        let gateway = entry
+            .next_hop
+            .parse()
+            .map_err(|_| invalid("NextHop", &entry.next_hop))?;
+        let ifindex = entry
+            .interface_index
+            .or(entry.if_index)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing InterfaceIndex"))?;
+        let mut route = Route::from(prefix).gateway(gateway).ifindex(ifindex);
+        route.metric = entry.route_metric;
+        Ok(route)
+    }
+}
+
+/// `ConvertTo-Json` writes a lone object instead of an array of one
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(NetRoute),
+    Many(Vec<NetRoute>),
+}
+
+/// Parse output of `Get-NetRoute | ConvertTo-Json` into routes
+///
+/// DestinationPrefix, NextHop, InterfaceIndex and RouteMetric are read, an unspecified
+/// NextHop means the route has no gateway
+///
+/// # Errors
+/// ErrorKind::InvalidData when json is not `Get-NetRoute` output
+pub fn parse_net_route_json(json: &str) -> io::Result<Vec<Route>> {
+    let entries = match serde_json::from_str(json)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+    {
+        OneOrMany::One(entry) => vec![entry],
+        OneOrMany::Many(entries) => entries,
+    };
+    entries.into_iter().map(Route::try_from).collect()
+}
+
+/// Difference between routes reported by `Get-NetRoute` and winroute's view of the table,
+/// returned by ```RouteManager::compare_net_route```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NetRouteComparison {
+    /// Routes `Get-NetRoute` reports but winroute does not see
+    pub missing: Vec<Route>,
+    /// Routes winroute sees but `Get-NetRoute` does not report
+    pub unexpected: Vec<Route>,
+}
+
+impl NetRouteComparison {
+    /// Compare `expected` routes with `actual` ones, metric is compared only when both know it
+    pub fn new(expected: &[Route], actual: &[Route]) -> Self {
+        let matches = |a: &Route, b: &Route| {
+            a.is_same_entry(b) && (a.metric.is_none() || b.metric.is_none() || a.metric == b.metric)
+        };
+        Self {
+            missing: expected
+                .iter()
+                .filter(|e| !actual.iter().any(|a| matches(e, a)))
+                .cloned()
+                .collect(),
+            unexpected: actual
+                .iter()
+                .filter(|a| !expected.iter().any(|e| matches(e, a)))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Whether both views agree
+    pub fn is_consistent(&self) -> bool {
+        self.missing.is_empty() && self.unexpected.is_empty()
+    }
+}
+
+impl<O: SystemRouteOperate> RouteManager<O> {
+    /// Cross-check routes against output of `Get-NetRoute | ConvertTo-Json`
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let output = std::process::Command::new("powershell")
+    ///     .args(["-NoProfile", "-Command", "Get-NetRoute | ConvertTo-Json"])
+    ///     .output()
+    ///     .unwrap();
+    /// let manager = RouteManager::new().unwrap();
+    /// let comparison = manager
+    ///     .compare_net_route(&String::from_utf8_lossy(&output.stdout))
+    ///     .unwrap();
+    /// assert!(comparison.is_consistent());
+    /// ```
+    ///
+    /// # Errors
+    /// When json can not be parsed or routes can not be read
+    pub fn compare_net_route(&self, json: &str) -> io::Result<NetRouteComparison> {
+        let expected = parse_net_route_json(json)?;
+        Ok(NetRouteComparison::new(&expected, &self.routes()?))
+    }
+}
+
+#[cfg(test)]
+pub mod test_powershell {
+    use super::parse_net_route_json;
+    use crate::{mock::MockOperator, Route, RouteManager};
+
+    const JSON: &str = r#"[
+        {
+            "DestinationPrefix": "0.0.0.0/0",
+            "NextHop": "192.168.1.1",
+            "InterfaceIndex": 12,
+            "InterfaceAlias": "Ethernet",
+            "AddressFamily": 2,
+            "RouteMetric": 0
+        },
+        {
+            "DestinationPrefix": "fe80::/64",
+            "NextHop": "::",
+            "ifIndex": 12,
+            "RouteMetric": 256
+        }
+    ]"#;
+
+    #[test]
+    fn test_parse() {
+        let routes = parse_net_route_json(JSON).unwrap();
+        assert_eq!(2, routes.len());
+        assert_eq!(
+            Route::new("0.0.0.0".parse().unwrap(), 0)
+                .gateway("192.168.1.1".parse().unwrap())
+                .ifindex(12)
+                .metric(0),
+            routes[0]
+        );
+        assert!(routes[1].gateway.is_unspecified());
+
+        let one =
+            r#"{"DestinationPrefix": "10.0.0.0/8", "NextHop": "0.0.0.0", "InterfaceIndex": 3}"#;
+        assert_eq!(None, parse_net_route_json(one).unwrap()[0].metric);
+        let bad =
+            r#"{"DestinationPrefix": "10.0.0.1/8", "NextHop": "0.0.0.0", "InterfaceIndex": 3}"#;
+        assert!(parse_net_route_json(bad).is_err());
+
+        let both = r#"{"DestinationPrefix": "10.0.0.0/8", "NextHop": "0.0.0.0", "InterfaceIndex": 3, "ifIndex": 3}"#;
+        assert_eq!(Some(3), parse_net_route_json(both).unwrap()[0].ifindex);
+        let neither = r#"{"DestinationPrefix": "10.0.0.0/8", "NextHop": "0.0.0.0"}"#;
+        assert!(parse_net_route_json(neither).is_err());
+    }
+
+    #[test]
+    fn test_compare() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![
+            Route::new("0.0.0.0".parse().unwrap(), 0)
+                .gateway("192.168.1.1".parse().unwrap())
+                .ifindex(12),
+            Route::new("10.0.0.0".parse().unwrap(), 8).ifindex(12),
+        ]))
+        .unwrap();
+        let comparison = manager.compare_net_route(JSON).unwrap();
+        assert!(!comparison.is_consistent());
+        assert_eq!(1, comparison.missing.len());
+        assert_eq!(6, comparison.missing[0].version);
+        assert_eq!(1, comparison.unexpected.len());
+        assert_eq!(8, comparison.unexpected[0].prefix);
+    }
+}
@@ -0,0 +1,260 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    fmt::Display,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+use crate::{Family, Route};
+
+/// Destination network whose length is valid for its family and whose host bits are zero
+///
+/// Can only be built through checked constructors, so APIs taking a `Prefix` never see
+/// `10.0.0.0/33` or `10.0.0.1/8`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Prefix {
+    network: IpAddr,
+    len: u8,
+}
+
+impl Family {
+    /// Longest prefix length of the family, 32 for IPv4 and 128 for IPv6
+    pub fn max_prefix_len(&self) -> u8 {
+        match self {
+            Family::V4 => 32,
+            Family::V6 => 128,
+        }
+    }
+}
+
+impl Prefix {
+    /// Network `network/len`
+    ///
+    /// # Errors
+    /// ErrorKind::InvalidInput when len exceeds the family's maximum or network has host bits set
+    pub fn new(network: IpAddr, len: u8) -> io::Result<Self> {
+        let prefix = Self::truncate(network, len)?;
+        if prefix.network != network {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}/{} has host bits set", network, len),
+            ));
+        }
+        Ok(prefix)
+    }
+
+    /// Network containing `addr` with given length, host bits of `addr` are cleared
+    ///
+    /// # Errors
+    /// ErrorKind::InvalidInput when len exceeds the family's maximum
+    pub fn truncate(addr: IpAddr, len: u8) -> io::Result<Self> {
+        let max = Family::of(&addr).max_prefix_len();
+        if len > max {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("prefix length {} is longer than {} for {}", len, max, addr),
+            ));
+        }
+        let network = match addr {
+            IpAddr::V4(a) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(len)).unwrap_or(0);
+                IpAddr::V4(Ipv4Addr::from(u32::from(a) & mask))
+            }
+            IpAddr::V6(a) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(len)).unwrap_or(0);
+                IpAddr::V6(Ipv6Addr::from(u128::from(a) & mask))
+            }
+        };
+        Ok(Self { network, len })
+    }
+
+    /// Single host prefix, `/32` or `/128`
+    pub fn host(addr: IpAddr) -> Self {
+        Self {
+            network: addr,
+            len: Family::of(&addr).max_prefix_len(),
+        }
+    }
+
+    /// Default route prefix of the family, `0.0.0.0/0` or `::/0`
+    pub fn default_route(family: Family) -> Self {
+        let network = match family {
+            Family::V4 => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            Family::V6 => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        Self { network, len: 0 }
+    }
+
+    /// Network address
+    pub fn network(&self) -> IpAddr {
+        self.network
+    }
+
+    /// Prefix length
+    pub fn prefix_len(&self) -> u8 {
+        self.len
+    }
+
+    /// Address family of network
+    pub fn family(&self) -> Family {
+        Family::of(&self.network)
+    }
+
+    /// Whether addr falls within the network, addresses of other family never do
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        Self::truncate(addr, self.len).is_ok_and(|p| p == *self)
+    }
+
+    /// Whether route's destination network equals or lies within this network
+    pub fn covers(&self, route: &Route) -> bool {
+        route.prefix >= self.len && self.contains(route.destination)
+    }
+
+    /// Whether route's destination network equals or contains this network
+    pub fn covered_by(&self, route: &Route) -> bool {
+        route.prefix <= self.len && route.contains(self.network)
+    }
+
+    /// Whether route's destination and prefix equal this network
+    pub fn matches(&self, route: &Route) -> bool {
+        route.destination == self.network && route.prefix == self.len
+    }
+}
+
+/// Whether gateway is usable without a connected subnet: unspecified for routes without
+/// gateway, or link-local
+pub(crate) fn is_on_link(gateway: IpAddr) -> bool {
+    match gateway {
+        IpAddr::V4(a) => a.is_unspecified() || a.is_link_local(),
+        IpAddr::V6(a) => a.is_unspecified() || a.segments()[0] & 0xffc0 == 0xfe80,
+    }
+}
+
+/// Ok when gateway is on-link or within one of `subnets` connected to interface ifindex
+pub(crate) fn verify_gateway(gateway: IpAddr, ifindex: u32, subnets: &[Prefix]) -> io::Result<()> {
+    if is_on_link(gateway) || subnets.iter().any(|p| p.contains(gateway)) {
+        return Ok(());
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!(
+            "gateway {} is not on a subnet connected to interface {}",
+            gateway, ifindex
+        ),
+    ))
+}
+
+impl Display for Prefix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.network, self.len)
+    }
+}
+
+impl FromStr for Prefix {
+    type Err = io::Error;
+
+    /// Parse `network/len`, a bare address is a host prefix
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid prefix {:?}", s),
+            )
+        };
+        match s.split_once('/') {
+            Some((addr, len)) => Prefix::new(
+                addr.parse().map_err(|_| invalid())?,
+                len.parse().map_err(|_| invalid())?,
+            ),
+            None => Ok(Prefix::host(s.parse().map_err(|_| invalid())?)),
+        }
+    }
+}
+
+/// Written as `network/len` string
+#[cfg(feature = "serializable")]
+impl serde::Serialize for Prefix {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serializable")]
+impl<'de> serde::Deserialize<'de> for Prefix {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <String as serde::Deserialize>::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl From<Prefix> for Route {
+    fn from(prefix: Prefix) -> Self {
+        Route::new(prefix.network, prefix.len)
+    }
+}
+
+impl TryFrom<&Route> for Prefix {
+    type Error = io::Error;
+
+    fn try_from(route: &Route) -> Result<Self, Self::Error> {
+        Prefix::new(route.destination, route.prefix)
+    }
+}
+
+#[cfg(test)]
+pub mod test_prefix {
+    use super::Prefix;
+    use crate::{Family, Route};
+
+    #[test]
+    fn test_construct() {
+        let prefix: Prefix = "10.1.0.0/16".parse().unwrap();
+        assert_eq!(
+            "10.1.0.0".parse::<std::net::IpAddr>().unwrap(),
+            prefix.network()
+        );
+        assert_eq!(16, prefix.prefix_len());
+        assert_eq!("10.1.0.0/16", prefix.to_string());
+        assert!(prefix.contains("10.1.2.3".parse().unwrap()));
+        assert!(!prefix.contains("10.2.0.1".parse().unwrap()));
+        assert!(!prefix.contains("::1".parse().unwrap()));
+
+        assert!("10.0.0.0/33".parse::<Prefix>().is_err());
+        assert!("10.0.0.1/8".parse::<Prefix>().is_err());
+        assert!("fe80::/129".parse::<Prefix>().is_err());
+        assert!("10.0.0.0/x".parse::<Prefix>().is_err());
+        assert_eq!(
+            "10.0.0.0/8".parse::<Prefix>().unwrap(),
+            Prefix::truncate("10.9.8.7".parse().unwrap(), 8).unwrap()
+        );
+        assert_eq!(128, "fe80::1".parse::<Prefix>().unwrap().prefix_len());
+        assert_eq!(
+            "::/0".parse::<Prefix>().unwrap(),
+            Prefix::default_route(Family::V6)
+        );
+
+        let route = Route::from(prefix).ifindex(3);
+        assert!(prefix.matches(&route));
+        assert_eq!(prefix, Prefix::try_from(&route).unwrap());
+        assert!(Prefix::try_from(&Route::new("10.0.0.0".parse().unwrap(), 40)).is_err());
+    }
+}
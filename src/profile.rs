@@ -0,0 +1,593 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    collections::BTreeMap,
+    io,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+use serde::{Deserialize, Serialize};
+
+use crate::{InterfaceNames, Prefix, Route, RouteManager, SystemRouteOperate};
+
+/// Route of a profile as written in a profile file
+///
+/// Interface is referenced either by alias (`interface = "Ethernet"`) or by index
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteSpec {
+    /// Destination network, e.g. `10.0.0.0/8`
+    pub destination: Prefix,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gateway: Option<IpAddr>,
+    /// Interface alias, resolved to an index when profile is activated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interface: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ifindex: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metric: Option<u32>,
+}
+
+impl RouteSpec {
+    /// Route described by spec, interface alias is looked up in names
+    ///
+    /// # Errors
+    /// ErrorKind::NotFound when alias is unknown, ErrorKind::InvalidInput when gateway family
+    /// differs from destination or alias and index name different interfaces
+    pub fn resolve(&self, names: &InterfaceNames) -> io::Result<Route> {
+        let mut route = Route::from(self.destination);
+        if let Some(gateway) = self.gateway {
+            if gateway.is_ipv4() != route.destination.is_ipv4() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "gateway {} and destination {} are different address families",
+                        gateway, self.destination
+                    ),
+                ));
+            }
+            route = route.gateway(gateway);
+        }
+        let ifindex = match (&self.interface, self.ifindex) {
+            (Some(alias), ifindex) => {
+                let idx = names.index_of(alias).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("no interface named {:?}", alias),
+                    )
+                })?;
+                if ifindex.is_some_and(|i| i != idx) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("interface {:?} is not index {}", alias, idx),
+                    ));
+                }
+                Some(idx)
+            }
+            (None, ifindex) => ifindex,
+        };
+        if let Some(idx) = ifindex {
+            route = route.ifindex(idx);
+        }
+        if let Some(metric) = self.metric {
+            route = route.metric(metric);
+        }
+        Ok(route)
+    }
+}
+
+/// Named set of routes activated and deactivated together
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Profile {
+    #[serde(default)]
+    pub routes: Vec<RouteSpec>,
+}
+
+impl Profile {
+    /// Routes of profile, see [`RouteSpec::resolve`]
+    ///
+    /// # Errors
+    /// When any route can not be resolved
+    pub fn resolve(&self, names: &InterfaceNames) -> io::Result<Vec<Route>> {
+        self.routes.iter().map(|spec| spec.resolve(names)).collect()
+    }
+}
+
+/// Profiles loaded from a TOML or YAML file, keyed by name
+///
+/// ```toml
+/// [[profiles.office.routes]]
+/// destination = "10.0.0.0/8"
+/// gateway = "192.168.1.1"
+/// interface = "Ethernet"
+/// metric = 10
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Profiles {
+    #[serde(default)]
+    profiles: BTreeMap<String, Profile>,
+}
+
+impl Profiles {
+    /// Parse profiles written in TOML
+    ///
+    /// # Errors
+    /// ErrorKind::InvalidData when text is not a valid profile file
+    pub fn from_toml(text: &str) -> io::Result<Self> {
+        toml::from_str(text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+    }
+
+    /// Parse profiles written in YAML
+    ///
+    /// # Errors
+    /// ErrorKind::InvalidData when text is not a valid profile file
+    pub fn from_yaml(text: &str) -> io::Result<Self> {
+        serde_yaml::from_str(text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Read profiles from path, format is chosen by extension: `.toml`, `.yaml` or `.yml`
+    ///
+    /// # Errors
+    /// When file can not be read, has another extension or is not a valid profile file
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        Self::parse(path, &std::fs::read_to_string(path)?)
+    }
+
+    /// Parse text read from path, format is chosen by path's extension
+    fn parse(path: &Path, text: &str) -> io::Result<Self> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Self::from_toml(text),
+            Some("yaml" | "yml") => Self::from_yaml(text),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unknown profile format {}", path.display()),
+            )),
+        }
+    }
+
+    /// Profile named name
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.profiles.get(name)
+    }
+
+    /// Add or replace profile named name
+    pub fn insert(&mut self, name: impl Into<String>, profile: Profile) {
+        self.profiles.insert(name.into(), profile);
+    }
+
+    /// Names of all profiles in alphabetical order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.profiles.keys().map(String::as_str)
+    }
+}
+
+impl<O: SystemRouteOperate> RouteManager<O> {
+    /// Add every route of profile, return routes as installed
+    ///
+    /// Either all routes are added or none: routes are resolved before the table is touched,
+    /// and routes already added are deleted again when one fails
+    ///
+    /// # Errors
+    /// When a route can not be resolved or added, the error of the first failure is returned
+    pub fn activate_profile(
+        &self,
+        profile: &Profile,
+        names: &InterfaceNames,
+    ) -> io::Result<Vec<Route>> {
+        let routes = profile.resolve(names)?;
+        let mut installed = Vec::with_capacity(routes.len());
+        for route in &routes {
+            match self.install(route) {
+                Ok(route) => installed.push(route),
+                Err(e) => {
+                    for route in installed.iter().rev() {
+                        let _ = self.remove(route);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(installed)
+    }
+
+    /// Delete routes of profile that are present in system, return routes that were deleted
+    ///
+    /// Either all present routes are deleted or none: deleted routes are added back when one
+    /// fails
+    ///
+    /// # Errors
+    /// When a route can not be resolved, read or deleted
+    pub fn deactivate_profile(
+        &self,
+        profile: &Profile,
+        names: &InterfaceNames,
+    ) -> io::Result<Vec<Route>> {
+        let mut present = Vec::new();
+        for route in profile.resolve(names)? {
+            match self.read_profile_route(&route) {
+                Ok(existing) => present.push(existing),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        for (i, route) in present.iter().enumerate() {
+            if let Err(e) = self.remove(route) {
+                for route in present[..i].iter().rev() {
+                    let _ = self.install(route);
+                }
+                return Err(e);
+            }
+        }
+        Ok(present)
+    }
+}
+
+impl<O: SystemRouteOperate> RouteManager<O> {
+    /// System entry of a profile route, routes without interface are looked up on the best
+    /// interface for their gateway, where ```install``` puts them
+    fn read_profile_route(&self, route: &Route) -> io::Result<Route> {
+        if route.ifindex.is_some() || route.luid.is_some() {
+            return self.backend().read_route(route);
+        }
+        let resolved = route.clone().ifindex(self.route_ifindex(route)?);
+        self.backend().read_route(&resolved)
+    }
+}
+
+/// Changes made by ```RouteManager::reconcile_profile```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reconciled {
+    /// Routes of profile that were missing and got added
+    pub added: Vec<Route>,
+    /// Routes no longer in profile, or whose metric changed, that got deleted
+    pub removed: Vec<Route>,
+    /// Routes of profile present in system afterwards
+    pub active: Vec<Route>,
+}
+
+/// Outcome of re-reading a watched profile file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProfileEvent {
+    /// Routing table was reconciled with the new profile
+    Applied(Reconciled),
+    /// Profile could not be loaded or applied, the error message is attached
+    Failed(String),
+}
+
+/// Background watcher created by ```RouteManager::watch_profile```, stops when dropped
+///
+/// Routes applied so far are left in place when watcher stops
+pub struct ProfileWatcher {
+    events: Receiver<ProfileEvent>,
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ProfileWatcher {
+    /// Events emitted every time the profile file changed and was applied
+    pub fn events(&self) -> &Receiver<ProfileEvent> {
+        &self.events
+    }
+}
+
+impl Drop for ProfileWatcher {
+    fn drop(&mut self) {
+        // disconnecting wakes the watcher thread immediately
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Whether installed satisfies wanted, metric is only compared when profile sets one
+fn satisfies(installed: &Route, wanted: &Route) -> bool {
+    installed.is_same_entry(wanted)
+        && (wanted.metric.is_none() || installed.metric == wanted.metric)
+}
+
+impl<O: SystemRouteOperate> RouteManager<O> {
+    /// Bring routing table in line with profile: routes of `previous` (usually ```active``` of
+    /// the last reconcile) that profile no longer contains are deleted, then routes of profile
+    /// missing from system are added. Routes not listed in either are never touched
+    ///
+    /// Unlike ```activate_profile``` this is not all or nothing, changes made before a failure
+    /// stay in place
+    ///
+    /// # Errors
+    /// When profile can not be resolved or a route can not be read, added or deleted
+    pub fn reconcile_profile(
+        &self,
+        previous: &[Route],
+        profile: &Profile,
+        names: &InterfaceNames,
+    ) -> io::Result<Reconciled> {
+        let wanted = profile.resolve(names)?;
+        let mut result = Reconciled::default();
+        for route in previous {
+            if wanted.iter().any(|w| satisfies(route, w)) {
+                continue;
+            }
+            match self.remove(route) {
+                Ok(()) => result.removed.push(route.clone()),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+        for route in &wanted {
+            match self.read_profile_route(route) {
+                Ok(existing) if satisfies(&existing, route) => {
+                    result.active.push(existing);
+                    continue;
+                }
+                Ok(existing) => {
+                    self.remove(&existing)?;
+                    result.removed.push(existing);
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+            let installed = self.install(route)?;
+            result.added.push(installed.clone());
+            result.active.push(installed);
+        }
+        Ok(result)
+    }
+}
+
+impl<O: SystemRouteOperate + 'static> RouteManager<O> {
+    /// Watch profile file at path and reconcile routing table with profile `name` every time
+    /// the file content changes, checking every `interval`. Profile is applied right away
+    ///
+    /// Interface aliases are resolved through `names` as given
+    ///
+    /// ```rust no_run
+    /// use std::{sync::Arc, time::Duration};
+    /// use winroute::*;
+    ///
+    /// let manager = Arc::new(RouteManager::new().unwrap());
+    /// let names = InterfaceNames::load().unwrap();
+    /// let watcher = manager
+    ///     .watch_profile("routes.toml", "office", names, Duration::from_secs(1))
+    ///     .unwrap();
+    /// for event in watcher.events() {
+    ///     println!("{event:?}");
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    /// When watcher thread can not be spawned
+    pub fn watch_profile(
+        self: &Arc<Self>,
+        path: impl Into<PathBuf>,
+        name: impl Into<String>,
+        names: InterfaceNames,
+        interval: Duration,
+    ) -> io::Result<ProfileWatcher> {
+        let (path, name) = (path.into(), name.into());
+        let (sender, events) = crossbeam_channel::unbounded();
+        let (stop, stopped) = crossbeam_channel::bounded::<()>(0);
+        let manager = self.clone();
+        let thread = self.thread_options().spawn("profile", move || {
+            let mut last: Option<String> = None;
+            let mut missing = false;
+            let mut active = Vec::new();
+            loop {
+                match std::fs::read_to_string(&path) {
+                    Ok(text) if last.as_ref() != Some(&text) => {
+                        missing = false;
+                        let event = match manager
+                            .apply_profile_file(&path, &text, &name, &active, &names)
+                        {
+                            Ok(reconciled) => {
+                                active = reconciled.active.clone();
+                                ProfileEvent::Applied(reconciled)
+                            }
+                            Err(e) => ProfileEvent::Failed(e.to_string()),
+                        };
+                        last = Some(text);
+                        if sender.send(event).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(_) => {}
+                    // unreadable file is reported once, routes stay until it is readable again
+                    Err(e) if !missing => {
+                        missing = true;
+                        last = None;
+                        if sender.send(ProfileEvent::Failed(e.to_string())).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => {}
+                }
+                if stopped.recv_timeout(interval) != Err(RecvTimeoutError::Timeout) {
+                    break;
+                }
+            }
+        })?;
+        Ok(ProfileWatcher {
+            events,
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+
+    fn apply_profile_file(
+        &self,
+        path: &Path,
+        text: &str,
+        name: &str,
+        active: &[Route],
+        names: &InterfaceNames,
+    ) -> io::Result<Reconciled> {
+        let profiles = Profiles::parse(path, text)?;
+        let profile = profiles.get(name).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no profile named {:?} in {}", name, path.display()),
+            )
+        })?;
+        self.reconcile_profile(active, profile, names)
+    }
+}
+
+#[cfg(test)]
+pub mod test_profile {
+    use std::{sync::Arc, time::Duration};
+
+    use super::{ProfileEvent, Profiles};
+    use crate::{mock::MockOperator, InterfaceNames, RouteManager};
+
+    const TOML: &str = r#"
+[[profiles.office.routes]]
+destination = "10.0.0.0/8"
+gateway = "192.168.1.1"
+interface = "Ethernet"
+metric = 10
+
+[[profiles.office.routes]]
+destination = "172.16.0.0/12"
+ifindex = 2
+"#;
+
+    const YAML: &str = r#"
+profiles:
+  office:
+    routes:
+      - destination: 10.0.0.0/8
+        gateway: 192.168.1.1
+        interface: Ethernet
+        metric: 10
+      - destination: 172.16.0.0/12
+        ifindex: 2
+"#;
+
+    #[test]
+    fn test_parse() {
+        let profiles = Profiles::from_toml(TOML).unwrap();
+        assert_eq!(profiles, Profiles::from_yaml(YAML).unwrap());
+        assert_eq!(vec!["office"], profiles.names().collect::<Vec<_>>());
+
+        let mut names = InterfaceNames::new();
+        names.insert(1, "Ethernet");
+        let routes = profiles.get("office").unwrap().resolve(&names).unwrap();
+        assert_eq!(Some(1), routes[0].ifindex);
+        assert_eq!(Some(10), routes[0].metric);
+        assert!(routes[1].gateway.is_unspecified());
+
+        assert!(
+            Profiles::from_toml("[[profiles.x.routes]]\ndestination = \"10.0.0.1/8\"").is_err()
+        );
+        let unknown = profiles
+            .get("office")
+            .unwrap()
+            .resolve(&InterfaceNames::new());
+        assert_eq!(std::io::ErrorKind::NotFound, unknown.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_activate() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        let mut names = InterfaceNames::new();
+        names.insert(1, "Ethernet");
+        let mut profiles = Profiles::from_toml(TOML).unwrap();
+        let office = profiles.get("office").unwrap().clone();
+
+        assert_eq!(2, manager.activate_profile(&office, &names).unwrap().len());
+        assert_eq!(2, manager.backend().table.lock().unwrap().len());
+        assert_eq!(
+            2,
+            manager.deactivate_profile(&office, &names).unwrap().len()
+        );
+        assert!(manager.backend().table.lock().unwrap().is_empty());
+
+        // second route duplicates the first, the first is rolled back
+        let mut broken = office.clone();
+        broken.routes[1] = broken.routes[0].clone();
+        profiles.insert("broken", broken.clone());
+        assert!(manager.activate_profile(&broken, &names).is_err());
+        assert!(manager.backend().table.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_profile_without_interface() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        let names = InterfaceNames::new();
+        let profiles = Profiles::from_toml(
+            "[[profiles.vpn.routes]]\ndestination = \"10.8.0.0/16\"\ngateway = \"192.168.1.1\"",
+        )
+        .unwrap();
+        let vpn = profiles.get("vpn").unwrap();
+
+        let reconciled = manager.reconcile_profile(&[], vpn, &names).unwrap();
+        assert_eq!(1, reconciled.added.len());
+        let again = manager
+            .reconcile_profile(&reconciled.active, vpn, &names)
+            .unwrap();
+        assert!(again.added.is_empty() && again.removed.is_empty());
+        assert_eq!(1, manager.deactivate_profile(vpn, &names).unwrap().len());
+        assert!(manager.backend().table.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_watch() {
+        let path =
+            std::env::temp_dir().join(format!("winroute-profile-{}.toml", std::process::id()));
+        std::fs::write(&path, TOML).unwrap();
+        let manager = Arc::new(RouteManager::with_backend(MockOperator::new(vec![])).unwrap());
+        let mut names = InterfaceNames::new();
+        names.insert(1, "Ethernet");
+        let watcher = manager
+            .watch_profile(&path, "office", names, Duration::from_millis(5))
+            .unwrap();
+        let timeout = Duration::from_secs(5);
+
+        match watcher.events().recv_timeout(timeout).unwrap() {
+            ProfileEvent::Applied(r) => assert_eq!((2, 0), (r.added.len(), r.removed.len())),
+            e => panic!("{:?}", e),
+        }
+        // drop the second route and change metric of the first
+        std::fs::write(
+            &path,
+            TOML.split("\n\n").next().unwrap().replace("= 10", "= 20"),
+        )
+        .unwrap();
+        match watcher.events().recv_timeout(timeout).unwrap() {
+            ProfileEvent::Applied(r) => {
+                assert_eq!((1, 2), (r.added.len(), r.removed.len()));
+                assert_eq!(Some(20), r.active[0].metric);
+            }
+            e => panic!("{:?}", e),
+        }
+        assert_eq!(1, manager.backend().table.lock().unwrap().len());
+
+        std::fs::write(&path, "garbage").unwrap();
+        let failed = watcher.events().recv_timeout(timeout).unwrap();
+        assert!(matches!(failed, ProfileEvent::Failed(_)));
+        drop(watcher);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,205 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{io, net::IpAddr, time::Duration};
+
+use crate::{DefaultOperator, Prefix, Route, SystemRouteOperate};
+
+/// Table reads attempted before a transient failure is reported
+const READ_ATTEMPTS: u32 = 4;
+/// Pause before the next attempt, multiplied by the number of failed attempts
+const READ_RETRY_DELAY: Duration = Duration::from_millis(10);
+
+/// Read-only access to the routing table, without route change listener, channels or cache
+///
+/// Every query reads from system, reading needs no administrator privileges
+///
+/// # Examples
+///
+/// ```rust no_run
+/// use winroute::*;
+///
+/// let reader = RouteTableReader::new().unwrap();
+/// for r in reader.routes().unwrap() {
+///     println!("{r}");
+/// }
+/// ```
+pub struct RouteTableReader<O: SystemRouteOperate = DefaultOperator> {
+    operator: O,
+}
+
+impl RouteTableReader {
+    /// Create a reader of system routing table
+    ///
+    /// # Errors
+    /// On non windows system
+    #[cfg(windows)]
+    pub fn new() -> io::Result<Self> {
+        Ok(Self::with_backend(crate::WindowsOperator::new()))
+    }
+
+    #[cfg(not(windows))]
+    pub fn new() -> io::Result<Self> {
+        Err(io::Error::other("None windows system not supported"))
+    }
+}
+
+impl<O: SystemRouteOperate> RouteTableReader<O> {
+    /// Create a reader on top of a custom backend, its ```init``` is never called
+    pub fn with_backend(backend: O) -> Self {
+        Self { operator: backend }
+    }
+
+    /// Get system routing table, include IPv6 and IPv4 routes
+    ///
+    /// # Errors
+    /// when system api return error
+    pub fn routes(&self) -> io::Result<Vec<Route>> {
+        self.operator.read_all_routes()
+    }
+
+    /// Routes with given destination network on any interface
+    ///
+    /// # Errors
+    /// when system api return error
+    pub fn routes_to(&self, prefix: Prefix) -> io::Result<Vec<Route>> {
+        Ok(self
+            .routes()?
+            .into_iter()
+            .filter(|r| prefix.matches(r))
+            .collect())
+    }
+
+    /// Default routes of both families which have a gateway
+    ///
+    /// # Errors
+    /// when system api return error
+    pub fn default_routes(&self) -> io::Result<Vec<Route>> {
+        Ok(self
+            .routes()?
+            .into_iter()
+            .filter(|r| r.is_default() && !r.gateway.is_unspecified())
+            .collect())
+    }
+
+    /// Read the single entry matching route's destination, prefix, gateway and interface
+    ///
+    /// # Errors
+    /// ErrorKind::NotFound when no entry matches, or when system api return error
+    pub fn read_route(&self, route: &Route) -> io::Result<Route> {
+        self.operator.read_route(route)
+    }
+
+    /// Index of the interface system would use to reach addr
+    ///
+    /// # Errors
+    /// when system api return error
+    pub fn best_interface(&self, addr: IpAddr) -> io::Result<u32> {
+        self.operator.best_interface(addr)
+    }
+}
+
+/// Read the table with `read` until two consecutive reads agree on the number of entries,
+/// retrying failures that are not caused by the caller
+///
+/// System tables may fail to read or come back half updated while routes churn. After
+/// `READ_ATTEMPTS` the latest table is returned even if unverified, an error only when no
+/// read succeeded
+#[cfg_attr(not(windows), allow(dead_code))]
+pub(crate) fn read_consistent<F>(mut read: F) -> io::Result<Vec<Route>>
+where
+    F: FnMut() -> io::Result<Vec<Route>>,
+{
+    let mut previous: Option<Vec<Route>> = None;
+    let mut failure = None;
+    for attempt in 1..=READ_ATTEMPTS {
+        match read() {
+            Ok(table) => {
+                if previous.as_ref().is_some_and(|p| p.len() == table.len()) {
+                    return Ok(table);
+                }
+                let retry = previous.is_some();
+                previous = Some(table);
+                // first read is verified right away, only disagreement waits
+                if !retry {
+                    continue;
+                }
+            }
+            Err(e) => match e.kind() {
+                io::ErrorKind::PermissionDenied
+                | io::ErrorKind::InvalidInput
+                | io::ErrorKind::Unsupported => return Err(e),
+                _ => failure = Some(e),
+            },
+        }
+        std::thread::sleep(READ_RETRY_DELAY * attempt);
+    }
+    match (previous, failure) {
+        (Some(table), _) => Ok(table),
+        (None, Some(e)) => Err(e),
+        (None, None) => unreachable!("at least one read is attempted"),
+    }
+}
+
+#[cfg(test)]
+pub mod test_reader {
+    use std::io;
+
+    use super::{read_consistent, RouteTableReader};
+    use crate::{mock::MockOperator, Route};
+
+    #[test]
+    fn test_queries() {
+        let default = Route::new("0.0.0.0".parse().unwrap(), 0)
+            .gateway("192.168.1.1".parse().unwrap())
+            .ifindex(1);
+        let link = Route::new("192.168.1.0".parse().unwrap(), 24).ifindex(1);
+        let reader =
+            RouteTableReader::with_backend(MockOperator::new(vec![default.clone(), link.clone()]));
+        assert_eq!(vec![default], reader.default_routes().unwrap());
+        assert_eq!(
+            vec![link],
+            reader.routes_to("192.168.1.0/24".parse().unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_read_consistent() {
+        let route = Route::new("10.0.0.0".parse().unwrap(), 8);
+        let mut reads = vec![
+            Ok(vec![route.clone(), route.clone()]),
+            Ok(vec![route.clone()]),
+            Ok(vec![route.clone()]),
+            Err(io::Error::other("table changed")),
+        ];
+        assert_eq!(1, read_consistent(|| reads.pop().unwrap()).unwrap().len());
+        assert_eq!(1, reads.len());
+
+        let mut reads = vec![
+            Ok(vec![route.clone()]),
+            Ok(vec![route.clone()]),
+            Ok(vec![route.clone(), route]),
+        ];
+        assert_eq!(1, read_consistent(|| reads.pop().unwrap()).unwrap().len());
+        assert!(reads.is_empty());
+
+        let denied = read_consistent(|| Err(io::Error::from(io::ErrorKind::PermissionDenied)));
+        assert_eq!(io::ErrorKind::PermissionDenied, denied.unwrap_err().kind());
+        assert!(read_consistent(|| Err(io::Error::other("busy"))).is_err());
+    }
+}
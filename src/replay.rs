@@ -0,0 +1,351 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Record route events to a file and replay them through a simulated backend,
+//! enabled with the `binary` feature
+
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    net::IpAddr,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{Receiver, Sender};
+
+use crate::{Family, Route, RouteEvent, SystemRouteOperate, ThreadOptions, TimedEvent, Timestamp};
+
+/// Recorded event, offset is the time elapsed since the first recorded event
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct RecordedEvent {
+    pub offset: Duration,
+    pub event: RouteEvent,
+}
+
+/// Write events with the time they were observed, the output can be replayed
+/// with [`ReplayOperator`]
+///
+/// ```rust no_run
+/// use winroute::*;
+///
+/// let manager = RouteManager::new().unwrap();
+/// let mut recorder = EventRecorder::create("routes.rec").unwrap();
+/// loop {
+///     let event = manager.poll_timed().unwrap();
+///     recorder.record(&event).unwrap();
+///     recorder.flush().unwrap();
+/// }
+/// ```
+pub struct EventRecorder<W: Write> {
+    writer: W,
+    start: Option<Instant>,
+}
+
+impl EventRecorder<BufWriter<File>> {
+    /// Record into file at path, file is truncated if it exists
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write> EventRecorder<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            start: None,
+        }
+    }
+
+    /// Append event, its offset is computed from the monotonic timestamp
+    ///
+    /// # Errors
+    /// When writing to underlying writer fails
+    pub fn record(&mut self, event: &TimedEvent) -> io::Result<()> {
+        let start = *self.start.get_or_insert(event.timestamp.instant);
+        let record = RecordedEvent {
+            offset: event.timestamp.instant.saturating_duration_since(start),
+            event: event.event.clone(),
+        };
+        bincode::serialize_into(&mut self.writer, &record).map_err(to_io_error)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Backend over an in-memory routing table that plays recorded events back
+///
+/// Events are emitted when manager initializes the backend, all at once by default, or
+/// paced by their recorded offsets with ```realtime```. Either way consumers receive the
+/// recorded gaps between events in the event timestamps. Routes added, deleted or changed
+/// through the backend only modify the in-memory table.
+///
+/// ```rust no_run
+/// use winroute::*;
+///
+/// let backend = ReplayOperator::open(vec![], "routes.rec").unwrap();
+/// let manager = RouteManager::with_backend(backend).unwrap();
+/// for event in manager.events() {
+///     println!("{event}");
+/// }
+/// ```
+pub struct ReplayOperator {
+    table: Arc<Mutex<Vec<Route>>>,
+    pending: Mutex<Vec<RecordedEvent>>,
+    realtime: bool,
+    threads: ThreadOptions,
+    sender: Sender<TimedEvent>,
+    receiver: Receiver<TimedEvent>,
+}
+
+impl ReplayOperator {
+    /// Replay events over initial routing table
+    pub fn new(table: Vec<Route>, events: Vec<RecordedEvent>) -> Self {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        Self {
+            table: Arc::new(Mutex::new(table)),
+            pending: Mutex::new(events),
+            realtime: false,
+            threads: ThreadOptions::default(),
+            sender,
+            receiver,
+        }
+    }
+
+    /// Replay events read from file written by [`EventRecorder`]
+    pub fn open(table: Vec<Route>, path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_reader(table, BufReader::new(File::open(path)?))
+    }
+
+    /// Replay events read from output of [`EventRecorder`]
+    ///
+    /// # Errors
+    /// When reading fails or a record is truncated or invalid
+    pub fn from_reader(table: Vec<Route>, mut reader: impl Read) -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let mut remaining = &bytes[..];
+        let mut events = Vec::new();
+        while !remaining.is_empty() {
+            events.push(bincode::deserialize_from(&mut remaining).map_err(to_io_error)?);
+        }
+        Ok(Self::new(table, events))
+    }
+
+    /// Sleep between events as long as they were apart when recorded
+    pub fn realtime(mut self, realtime: bool) -> Self {
+        self.realtime = realtime;
+        self
+    }
+
+    /// Options of the thread pacing events in ```realtime``` mode
+    pub fn thread_options(mut self, threads: ThreadOptions) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Current content of the in-memory routing table
+    pub fn table(&self) -> Vec<Route> {
+        lock(&self.table).map(|t| t.clone()).unwrap_or_default()
+    }
+
+    fn emit(&self, event: RouteEvent) -> io::Result<()> {
+        emit(&self.table, &self.sender, event, Timestamp::now())
+    }
+}
+
+impl SystemRouteOperate for ReplayOperator {
+    fn init(&self) -> io::Result<()> {
+        let events = std::mem::take(&mut *lock(&self.pending)?);
+        let start = Timestamp::now();
+        let stamp = move |offset: Duration| Timestamp {
+            instant: start.instant + offset,
+            system: start.system + offset,
+        };
+        if !self.realtime {
+            for recorded in events {
+                emit(
+                    &self.table,
+                    &self.sender,
+                    recorded.event,
+                    stamp(recorded.offset),
+                )?;
+            }
+            return Ok(());
+        }
+        let table = self.table.clone();
+        let sender = self.sender.clone();
+        self.threads.spawn("replay", move || {
+            for recorded in events {
+                let timestamp = stamp(recorded.offset);
+                std::thread::sleep(timestamp.instant.saturating_duration_since(Instant::now()));
+                if emit(&table, &sender, recorded.event, timestamp).is_err() {
+                    break;
+                }
+            }
+        })?;
+        Ok(())
+    }
+
+    fn event_receiver(&self) -> Receiver<TimedEvent> {
+        self.receiver.clone()
+    }
+
+    fn read_all_routes(&self) -> io::Result<Vec<Route>> {
+        Ok(lock(&self.table)?.clone())
+    }
+
+    fn add_route(&self, route: &Route) -> io::Result<Route> {
+        let route = match route.ifindex {
+            Some(_) => route.clone(),
+            None => route.clone().ifindex(self.best_interface(route.gateway)?),
+        };
+        if lock(&self.table)?.iter().any(|r| r.is_same_entry(&route)) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "route already exists",
+            ));
+        }
+        self.emit(RouteEvent::Add(route.clone()))?;
+        Ok(route)
+    }
+
+    fn best_interface(&self, addr: IpAddr) -> io::Result<u32> {
+        lock(&self.table)?
+            .iter()
+            .filter(|r| r.ifindex.is_some() && r.contains(addr))
+            .max_by_key(|r| (r.prefix, std::cmp::Reverse(r.metric.unwrap_or(0))))
+            .and_then(|r| r.ifindex)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no route to host"))
+    }
+
+    fn interface_metric(&self, _family: Family, _ifindex: u32) -> io::Result<u32> {
+        Ok(0)
+    }
+
+    fn read_route(&self, route: &Route) -> io::Result<Route> {
+        lock(&self.table)?
+            .iter()
+            .find(|r| r.is_same_entry(route))
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "route not found"))
+    }
+
+    fn delete_route(&self, route: &Route) -> io::Result<()> {
+        let existing = self.read_route(route)?;
+        self.emit(RouteEvent::Delete(existing))
+    }
+
+    fn update_route(&self, route: &Route) -> io::Result<()> {
+        let mut existing = self.read_route(route)?;
+        existing.metric = route.metric;
+        self.emit(RouteEvent::Change(existing))
+    }
+}
+
+/// Apply event to table, then send it to manager
+fn emit(
+    table: &Mutex<Vec<Route>>,
+    sender: &Sender<TimedEvent>,
+    event: RouteEvent,
+    timestamp: Timestamp,
+) -> io::Result<()> {
+    {
+        let mut table = lock(table)?;
+        match &event {
+            RouteEvent::Add(route) => table.push(route.clone()),
+            RouteEvent::Delete(route) => table.retain(|r| !r.is_same_entry(route)),
+            RouteEvent::Change(route) => {
+                for r in table.iter_mut().filter(|r| r.is_same_entry(route)) {
+                    *r = route.clone();
+                }
+            }
+            RouteEvent::Initial => {}
+        }
+    }
+    sender
+        .send(TimedEvent { event, timestamp })
+        .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "event receiver is gone"))
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> io::Result<std::sync::MutexGuard<'_, T>> {
+    mutex
+        .lock()
+        .map_err(|_| io::Error::other("Can not lock inner data, this is a thread safe error"))
+}
+
+fn to_io_error(e: bincode::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+#[cfg(test)]
+pub mod test_replay {
+    use std::time::Duration;
+
+    use super::{EventRecorder, ReplayOperator};
+    use crate::{Route, RouteEvent, RouteManager, SystemRouteOperate, TimedEvent, Timestamp};
+
+    #[test]
+    fn test_record_replay() {
+        let default = Route::new("0.0.0.0".parse().unwrap(), 0)
+            .gateway("192.168.1.1".parse().unwrap())
+            .ifindex(3);
+        let vpn = Route::new("10.0.0.0".parse().unwrap(), 8).ifindex(7);
+
+        let start = Timestamp::now();
+        let mut recorder = EventRecorder::new(Vec::new());
+        for (offset, event) in [
+            (0, RouteEvent::Add(vpn.clone())),
+            (1500, RouteEvent::Change(vpn.clone().metric(5))),
+            (1600, RouteEvent::Delete(vpn.clone().metric(5))),
+        ] {
+            let mut timestamp = start;
+            timestamp.instant += Duration::from_millis(offset);
+            recorder.record(&TimedEvent { event, timestamp }).unwrap();
+        }
+        let bytes = recorder.into_inner();
+        assert!(ReplayOperator::from_reader(vec![], &bytes[..bytes.len() - 1]).is_err());
+
+        let backend = ReplayOperator::from_reader(vec![default.clone()], &bytes[..]).unwrap();
+        let manager = RouteManager::with_backend(backend).unwrap();
+        let first = manager.poll_timed().unwrap();
+        assert_eq!(RouteEvent::Add(vpn.clone()), first.event);
+        let second = manager.poll_timed().unwrap();
+        assert_eq!(RouteEvent::Change(vpn.clone().metric(5)), second.event);
+        assert_eq!(
+            Duration::from_millis(1500),
+            second.timestamp.instant - first.timestamp.instant
+        );
+        manager.poll_timed().unwrap();
+        assert_eq!(vec![default.clone()], manager.routes().unwrap());
+        assert_eq!(
+            3,
+            manager
+                .backend()
+                .best_interface("8.8.8.8".parse().unwrap())
+                .unwrap()
+        );
+    }
+}
@@ -0,0 +1,135 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use crossbeam_channel::Sender;
+
+use crate::{RouteManager, SystemRouteOperate};
+
+/// Thread comparing cached table with system every interval and repairing it, created by
+/// ```RouteManager::spawn_resync```, stopped when dropped
+pub struct Resyncer {
+    repaired: Arc<AtomicUsize>,
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Resyncer {
+    /// Number of events emitted so far for entries where cache diverged from system
+    pub fn repaired(&self) -> usize {
+        self.repaired.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Resyncer {
+    fn drop(&mut self) {
+        // disconnecting wakes the resync thread immediately
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl<O: SystemRouteOperate + 'static> RouteManager<O> {
+    /// Start a thread that calls ```resync``` every `interval` with notification enabled, so
+    /// a long running daemon recovers from missed notifications, until the returned handle
+    /// is dropped or manager is shut down
+    ///
+    /// Events of changes that happened while a resync ran may be delivered twice, once as
+    /// repair and once when polled
+    ///
+    /// ```rust no_run
+    /// use std::{sync::Arc, time::Duration};
+    /// use winroute::*;
+    ///
+    /// let manager = Arc::new(RouteManager::new().unwrap());
+    /// let _poller = manager.spawn_poller().unwrap();
+    /// let _resync = manager.spawn_resync(Duration::from_secs(300)).unwrap();
+    /// ```
+    ///
+    /// # Errors
+    /// ErrorKind::Unsupported when listener is disabled, or when thread can not be spawned
+    pub fn spawn_resync(self: &Arc<Self>, interval: Duration) -> io::Result<Resyncer> {
+        if self.backend_events().is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "route change listener is disabled",
+            ));
+        }
+        let repaired = Arc::new(AtomicUsize::new(0));
+        let (stop, stopped) = crossbeam_channel::bounded::<()>(0);
+        let closed = self.closed().clone();
+        let (manager, counter) = (self.clone(), repaired.clone());
+        let thread = self.thread_options().spawn("resync", move || loop {
+            crossbeam_channel::select! {
+                recv(stopped) -> _ => return,
+                recv(closed) -> _ => return,
+                default(interval) => {
+                    // failures are retried on next interval
+                    if let Ok(events) = manager.resync(true) {
+                        counter.fetch_add(events.len(), Ordering::Relaxed);
+                    }
+                }
+            }
+        })?;
+        Ok(Resyncer {
+            repaired,
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod test_resync {
+    use std::{sync::Arc, time::Duration};
+
+    use crate::{mock::MockOperator, Route, RouteEvent, RouteManager};
+
+    #[test]
+    fn test_spawn_resync() {
+        let manager = Arc::new(RouteManager::with_backend(MockOperator::new(vec![])).unwrap());
+        let subscription = manager.subscribe_route_change();
+        let resync = manager.spawn_resync(Duration::from_millis(10)).unwrap();
+
+        // route appears without a notification
+        let missed = Route::new("10.0.0.0".parse().unwrap(), 8)
+            .gateway("192.168.1.1".parse().unwrap())
+            .ifindex(1);
+        manager.backend().table.lock().unwrap().push(missed.clone());
+        let event = subscription.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(RouteEvent::Add(missed.clone()), event);
+        assert_eq!(vec![missed], manager.routes().unwrap());
+        // counted once the resync that delivered the event returns
+        let deadline = std::time::Instant::now() + Duration::from_secs(1);
+        while resync.repaired() == 0 && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(1, resync.repaired());
+    }
+}
@@ -21,8 +21,32 @@ use std::{
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
 };
 
+use crate::InterfaceNames;
+
+/// IP address family of a route
+#[cfg_attr(feature = "serializable", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Family {
+    V4,
+    V6,
+}
+
+impl Family {
+    /// Family of an address
+    pub fn of(addr: &IpAddr) -> Self {
+        match addr {
+            IpAddr::V4(_) => Family::V4,
+            IpAddr::V6(_) => Family::V6,
+        }
+    }
+}
+
 /// Routing data structure, including destination address, gateway and other information
-#[cfg_attr(feature = "serializable", derive(serde::Serialize))]
+#[cfg_attr(
+    feature = "serializable",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "RouteRepr")
+)]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Route {
     /// Network address of the destination. `0.0.0.0` with a prefix of `0` is considered a default route.
@@ -45,12 +69,20 @@ pub struct Route {
 
     /// The IP version number, the value is 4 or 6
     pub version: u8,
+
+    /// Routing protocol or mechanism that installed the entry, `MIB_IPFORWARD_ROW2.Protocol`
+    /// (e.g. `2` local, `3` network management, `14` BGP), set on routes read from system
+    pub protocol: Option<u32>,
 }
 
 impl Route {
     /// Create a route that matches a given destination network.
     ///
     /// Either the gateway or interface should be set before attempting to add to a routing table.
+    ///
+    /// Prefix length is not checked here, adding a route longer than its family allows fails
+    /// with ErrorKind::InvalidInput and the route cache skips such rows. Build it from a
+    /// [`Prefix`](crate::Prefix) or check ```has_valid_prefix``` to catch it early
     pub fn new(destination: IpAddr, prefix: u8) -> Self {
         let version = match destination {
             IpAddr::V4(_) => 4,
@@ -67,6 +99,7 @@ impl Route {
             metric: None,
             luid: None,
             version,
+            protocol: None,
         }
     }
 
@@ -109,94 +142,261 @@ impl Route {
         self.luid = Some(luid);
         self
     }
+
+    /// protocol setter
+    pub fn protocol(mut self, protocol: u32) -> Self {
+        self.protocol = Some(protocol);
+        self
+    }
+}
+
+impl Route {
+    /// Address family of destination
+    pub fn family(&self) -> Family {
+        Family::of(&self.destination)
+    }
+
+    /// Whether prefix fits the family of destination, at most 32 for IPv4 and 128 for IPv6
+    pub fn has_valid_prefix(&self) -> bool {
+        self.prefix <= self.family().max_prefix_len()
+    }
+
+    /// Whether route is a default route, `0.0.0.0/0` or `::/0`
+    pub fn is_default(&self) -> bool {
+        self.prefix == 0 && self.destination.is_unspecified()
+    }
+
+    /// Whether route only carries host local traffic that changes as interfaces come and go:
+    /// loopback (`127.0.0.0/8`, `::1`), multicast, broadcast and link-local autoconfiguration
+    /// (`169.254.0.0/16`, `fe80::/10`) destinations
+    pub fn is_noise(&self) -> bool {
+        match self.destination {
+            IpAddr::V4(v4) => {
+                v4.is_loopback() || v4.is_multicast() || v4.is_broadcast() || v4.is_link_local()
+            }
+            IpAddr::V6(v6) => {
+                v6.is_loopback() || v6.is_multicast() || v6.segments()[0] & 0xffc0 == 0xfe80
+            }
+        }
+    }
+
+    /// Whether addr falls within destination prefix, addresses of other family never do
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        let (network, addr, bits) = match (self.destination, addr) {
+            (IpAddr::V4(n), IpAddr::V4(a)) => (u32::from(n) as u128, u32::from(a) as u128, 32),
+            (IpAddr::V6(n), IpAddr::V6(a)) => (u128::from(n), u128::from(a), 128),
+            _ => return false,
+        };
+        let host_bits = bits - u32::from(self.prefix).min(bits);
+        host_bits >= bits || (network ^ addr) >> host_bits == 0
+    }
+
+    /// Whether both describe the same system entry: destination, prefix and gateway are equal,
+    /// interface is compared only when both know it
+    pub(crate) fn is_same_entry(&self, other: &Route) -> bool {
+        self.destination == other.destination
+            && self.prefix == other.prefix
+            && self.gateway == other.gateway
+            && (self.ifindex.is_none() || other.ifindex.is_none() || self.ifindex == other.ifindex)
+            && (self.luid.is_none() || other.luid.is_none() || self.luid == other.luid)
+    }
+
+    /// Compact display adapter, only prints `destination/prefix`
+    pub fn cidr(&self) -> Cidr<'_> {
+        Cidr(self)
+    }
+
+    /// Display adapter that appends interface alias resolved from `names`
+    ///
+    /// Alternate flag `{:#}` is honored the same way as `Route`'s own `Display`, the alias
+    /// then follows ifindex, luid and protocol
+    pub fn display_with<'a>(&'a self, names: &'a InterfaceNames) -> RouteDisplay<'a> {
+        RouteDisplay { route: self, names }
+    }
 }
 
 impl Display for Route {
+    /// `{}` prints destination, gateway and metric, `{:#}` also prints ifindex, luid and
+    /// protocol
+    ///
+    /// Route does not know its interface alias, [`Route::display_with`] appends it
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
             "{}/{} gateway {} metric {:?}",
-            self.destination.to_string(),
-            self.prefix,
-            self.gateway.to_string(),
-            self.metric,
-        )
+            self.destination, self.prefix, self.gateway, self.metric,
+        )?;
+        if f.alternate() {
+            write!(
+                f,
+                " ifindex {:?} luid {:?} protocol {:?}",
+                self.ifindex, self.luid, self.protocol
+            )?;
+        }
+        Ok(())
     }
 }
 
-#[cfg(feature = "serializable")]
-struct CustomVisitor;
+/// Route displayed as `destination/prefix`, created by [`Route::cidr`]
+pub struct Cidr<'a>(&'a Route);
 
-#[cfg(feature = "serializable")]
-impl<'de> serde::Deserialize<'de> for Route {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: serde::Deserializer<'de>,
-    {
-        deserializer.deserialize_any(CustomVisitor)
+impl Display for Cidr<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.0.destination, self.0.prefix)
     }
 }
-#[cfg(feature = "serializable")]
-impl<'de> serde::de::Visitor<'de> for CustomVisitor {
-    type Value = Route;
 
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(formatter, "a map with keys 'first' and 'second'")
+/// Route displayed with its interface alias, created by [`Route::display_with`]
+pub struct RouteDisplay<'a> {
+    route: &'a Route,
+    names: &'a InterfaceNames,
+}
+
+impl Display for RouteDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if f.alternate() {
+            write!(f, "{:#}", self.route)?;
+        } else {
+            write!(f, "{}", self.route)?;
+        }
+        match self.route.ifindex.and_then(|idx| self.names.get(idx)) {
+            Some(alias) => write!(f, " dev {}", alias),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Order independent hash of a routing table, stable across processes and builds
+///
+/// Every route is hashed with FNV-1a over its fields, route hashes are summed
+pub(crate) fn fingerprint(routes: &[Route]) -> u64 {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    fn addr_bytes(addr: &IpAddr) -> Vec<u8> {
+        match addr {
+            IpAddr::V4(v4) => v4.octets().to_vec(),
+            IpAddr::V6(v6) => v6.octets().to_vec(),
+        }
+    }
+
+    fn optional(value: Option<u64>) -> [u8; 9] {
+        let mut bytes = [0u8; 9];
+        if let Some(value) = value {
+            bytes[0] = 1;
+            bytes[1..].copy_from_slice(&value.to_le_bytes());
+        }
+        bytes
     }
 
-    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
-    where
-        A: serde::de::MapAccess<'de>,
-    {
-        let mut route = Route::new(IpAddr::V4("0.0.0.0".parse().unwrap()), 0);
-        while let Some(key) = map.next_key()? {
-            match key {
-                "destination" => {
-                    let dest_ip: String = map.next_value()?;
-                    let res = dest_ip.parse::<IpAddr>();
-                    if res.is_ok() {
-                        route = route.destination(res.unwrap());
-                    }
-                }
-                "prefix" => {
-                    route = route.prefix(map.next_value()?);
-                }
-                "gateway" => {
-                    let v = map.next_value();
-                    if v.is_ok() {
-                        route = route.gateway(v.unwrap());
-                    }
-                }
-                "ifindex" => {
-                    let v = map.next_value();
-                    if v.is_ok() {
-                        route = route.ifindex(v.unwrap());
-                    }
-                }
-                "luid" => {
-                    let v = map.next_value();
-                    if v.is_ok() {
-                        route = route.luid(v.unwrap());
-                    }
-                }
-                "metric" => {
-                    let v = map.next_value();
-                    if v.is_ok() {
-                        route = route.metric(v.unwrap());
-                    }
-                }
-                _ => {
-                    let _: serde::de::IgnoredAny = map.next_value()?;
-                }
+    routes
+        .iter()
+        .map(|route| {
+            let mut bytes = addr_bytes(&route.destination);
+            bytes.push(route.prefix);
+            bytes.extend(addr_bytes(&route.gateway));
+            bytes.extend(optional(route.ifindex.map(u64::from)));
+            bytes.extend(optional(route.metric.map(u64::from)));
+            bytes.extend(optional(route.luid));
+            bytes.extend(optional(route.protocol.map(u64::from)));
+            bytes
+                .iter()
+                .fold(OFFSET, |hash, b| (hash ^ u64::from(*b)).wrapping_mul(PRIME))
+        })
+        .fold(0u64, u64::wrapping_add)
+}
+
+/// Wire representation of [`Route`], mirrors exactly what `Serialize` produces
+#[cfg(feature = "serializable")]
+#[derive(serde::Deserialize)]
+struct RouteRepr {
+    destination: IpAddr,
+    prefix: u8,
+    #[serde(default, deserialize_with = "present")]
+    gateway: Option<IpAddr>,
+    #[serde(default)]
+    ifindex: Option<u32>,
+    #[serde(default)]
+    metric: Option<u32>,
+    #[serde(default)]
+    luid: Option<u64>,
+    #[serde(default, deserialize_with = "present")]
+    version: Option<u8>,
+    #[serde(default)]
+    protocol: Option<u32>,
+}
+
+/// Fields that `Serialize` always writes but may be omitted by hand written input,
+/// keeps non self-describing formats reading the same layout that was written
+#[cfg(feature = "serializable")]
+fn present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+#[cfg(feature = "serializable")]
+impl TryFrom<RouteRepr> for Route {
+    type Error = String;
+
+    fn try_from(repr: RouteRepr) -> Result<Self, Self::Error> {
+        let mut route = Route::new(repr.destination, repr.prefix);
+        if !route.has_valid_prefix() {
+            return Err(format!(
+                "prefix {} of {} is longer than {}",
+                route.prefix,
+                route.destination,
+                route.family().max_prefix_len()
+            ));
+        }
+        if let Some(version) = repr.version {
+            if version != route.version {
+                return Err(format!(
+                    "version {} does not match destination {}",
+                    version, route.destination
+                ));
+            }
+        }
+        if let Some(gateway) = repr.gateway {
+            if gateway.is_ipv4() != route.destination.is_ipv4() {
+                return Err(format!(
+                    "gateway {} and destination {} are different address families",
+                    gateway, route.destination
+                ));
             }
+            route.gateway = gateway;
         }
+        route.ifindex = repr.ifindex;
+        route.metric = repr.metric;
+        route.luid = repr.luid;
+        route.protocol = repr.protocol;
         Ok(route)
     }
 }
 
 #[cfg(test)]
 pub mod test_route {
-    use super::Route;
+    use super::{fingerprint, Route};
+    use crate::InterfaceNames;
+
+    #[test]
+    fn test_is_noise() {
+        let noise = [
+            "127.0.0.1",
+            "224.0.0.0",
+            "255.255.255.255",
+            "169.254.3.4",
+            "::1",
+        ];
+        for dst in noise.into_iter().chain(["ff00::", "fe80::1"]) {
+            assert!(Route::new(dst.parse().unwrap(), 32).is_noise(), "{dst}");
+        }
+        for dst in ["0.0.0.0", "10.0.0.0", "2001:db8::", "fec0::"] {
+            assert!(!Route::new(dst.parse().unwrap(), 8).is_noise(), "{dst}");
+        }
+    }
 
     #[test]
     fn testv4() {
@@ -220,6 +420,18 @@ pub mod test_route {
         );
     }
 
+    #[test]
+    fn test_fingerprint() {
+        let a = Route::new("10.0.0.0".parse().unwrap(), 8).ifindex(1);
+        let b = Route::new("fe80::".parse().unwrap(), 64).metric(256);
+        let both = fingerprint(&[a.clone(), b.clone()]);
+        assert_eq!(both, fingerprint(&[b.clone(), a.clone()]));
+        let single = fingerprint(std::slice::from_ref(&a));
+        assert_ne!(single, fingerprint(&[a.metric(0)]));
+        assert_ne!(single, both);
+        assert_eq!(0, fingerprint(&[]));
+    }
+
     #[test]
     fn testv6() {
         let route = Route::new("fe80:9464::".parse().unwrap(), 32);
@@ -227,6 +439,18 @@ pub mod test_route {
         assert_eq!(6, route.version);
     }
 
+    #[test]
+    fn test_contains() {
+        let route = Route::new("192.168.0.0".parse().unwrap(), 24);
+        assert!(route.contains("192.168.0.77".parse().unwrap()));
+        assert!(!route.contains("192.168.1.1".parse().unwrap()));
+        assert!(!route.contains("::1".parse().unwrap()));
+
+        let route = Route::new("fe80:9464::".parse().unwrap(), 32);
+        assert!(route.contains("fe80:9464::1".parse().unwrap()));
+        assert!(Route::new("::".parse().unwrap(), 0).contains("2001:db8::1".parse().unwrap()));
+    }
+
     #[test]
     #[cfg(feature = "serializable")]
     fn test_serializable() {
@@ -238,7 +462,7 @@ pub mod test_route {
             .luid(123456)
             .metric(1);
         let res = serde_json::to_string(&route).expect("Failed to serialize Route Object");
-        assert_eq!("{\"destination\":\"192.168.0.0\",\"prefix\":24,\"gateway\":\"172.1.1.254\",\"ifindex\":1,\"metric\":1,\"luid\":123456,\"version\":4}", res);
+        assert_eq!("{\"destination\":\"192.168.0.0\",\"prefix\":24,\"gateway\":\"172.1.1.254\",\"ifindex\":1,\"metric\":1,\"luid\":123456,\"version\":4,\"protocol\":null}", res);
         let route: Route = serde_json::from_str(&res).unwrap();
         assert_eq!(
             "192.168.0.0/24 gateway 172.1.1.254 metric Some(1)",
@@ -248,9 +472,59 @@ pub mod test_route {
 
         let route = Route::new("fe80:9464::".parse().unwrap(), 32);
         let res = serde_json::to_string(&route).expect("Failed to serialize Route Object");
-        assert_eq!("{\"destination\":\"fe80:9464::\",\"prefix\":32,\"gateway\":\"::\",\"ifindex\":null,\"metric\":null,\"luid\":null,\"version\":6}", res);
+        assert_eq!("{\"destination\":\"fe80:9464::\",\"prefix\":32,\"gateway\":\"::\",\"ifindex\":null,\"metric\":null,\"luid\":null,\"version\":6,\"protocol\":null}", res);
         let route: Route = serde_json::from_str(&res).unwrap();
         assert_eq!("fe80:9464::/32 gateway :: metric None", route.to_string());
         assert_eq!(6, route.version);
     }
+
+    #[test]
+    #[cfg(feature = "serializable")]
+    fn test_deserialize_strict() {
+        let route: Route =
+            serde_json::from_str("{\"destination\":\"10.0.0.0\",\"prefix\":8}").unwrap();
+        assert_eq!("10.0.0.0/8 gateway 0.0.0.0 metric None", route.to_string());
+        assert_eq!(None, route.ifindex);
+
+        assert!(
+            serde_json::from_str::<Route>("{\"destination\":\"10.0.0.256\",\"prefix\":8}").is_err()
+        );
+        assert!(serde_json::from_str::<Route>("{\"prefix\":8}").is_err());
+        assert!(serde_json::from_str::<Route>(
+            "{\"destination\":\"10.0.0.0\",\"prefix\":8,\"gateway\":\"::1\"}"
+        )
+        .is_err());
+        assert!(serde_json::from_str::<Route>(
+            "{\"destination\":\"10.0.0.0\",\"prefix\":8,\"version\":6}"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_display_formats() {
+        let route = Route::new("192.168.0.0".parse().unwrap(), 24)
+            .gateway("172.1.1.254".parse().unwrap())
+            .ifindex(12)
+            .metric(5);
+        assert_eq!("192.168.0.0/24", route.cidr().to_string());
+        assert_eq!(
+            "192.168.0.0/24 gateway 172.1.1.254 metric Some(5) ifindex Some(12) luid None protocol None",
+            format!("{:#}", route)
+        );
+
+        let mut names = InterfaceNames::new();
+        names.insert(12, "Ethernet");
+        assert_eq!(
+            "192.168.0.0/24 gateway 172.1.1.254 metric Some(5) dev Ethernet",
+            route.display_with(&names).to_string()
+        );
+        assert_eq!(
+            "192.168.0.0/24 gateway 172.1.1.254 metric Some(5) ifindex Some(12) luid None protocol Some(3) dev Ethernet",
+            format!("{:#}", route.clone().protocol(3).display_with(&names))
+        );
+        assert_eq!(
+            "192.168.0.0/24 gateway 172.1.1.254 metric Some(5)",
+            route.clone().ifindex(3).display_with(&names).to_string()
+        );
+    }
 }
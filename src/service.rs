@@ -0,0 +1,269 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Run a manager inside a Windows service, enabled with the `service` feature
+
+use std::{
+    ffi::OsStr,
+    io,
+    os::windows::ffi::OsStrExt,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crossbeam_channel::{Receiver, Sender};
+use winapi::{
+    shared::minwindef::{DWORD, LPVOID},
+    um::{
+        winnt::{LPWSTR, SERVICE_WIN32_OWN_PROCESS},
+        winsvc::{
+            RegisterServiceCtrlHandlerExW, SetServiceStatus, StartServiceCtrlDispatcherW,
+            SERVICE_ACCEPT_PAUSE_CONTINUE, SERVICE_ACCEPT_PRESHUTDOWN, SERVICE_ACCEPT_STOP,
+            SERVICE_CONTROL_CONTINUE, SERVICE_CONTROL_INTERROGATE, SERVICE_CONTROL_PAUSE,
+            SERVICE_CONTROL_PRESHUTDOWN, SERVICE_CONTROL_STOP, SERVICE_PAUSED, SERVICE_RUNNING,
+            SERVICE_STATUS, SERVICE_STATUS_HANDLE, SERVICE_STOPPED, SERVICE_STOP_PENDING,
+            SERVICE_TABLE_ENTRYW,
+        },
+    },
+};
+
+use crate::{Poller, RouteManager, SystemRouteOperate};
+
+/// ERROR_CALL_NOT_IMPLEMENTED, answer to controls the service does not handle
+const CALL_NOT_IMPLEMENTED: DWORD = 120;
+/// ERROR_SERVICE_SPECIFIC_ERROR, exit code telling SCM to read the service specific one
+const SERVICE_SPECIFIC_ERROR: DWORD = 1066;
+
+type ServiceBody = Box<dyn FnOnce(&ServiceContext) -> io::Result<()> + Send>;
+
+/// Service main receives no context, body waits here until SCM starts it
+static SERVICE: Mutex<Option<(Vec<u16>, ServiceBody)>> = Mutex::new(None);
+
+/// Control request sent by the service control manager
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceControl {
+    /// SERVICE_CONTROL_STOP
+    Stop,
+    /// SERVICE_CONTROL_PRESHUTDOWN, system is about to shut down
+    Preshutdown,
+    /// SERVICE_CONTROL_PAUSE
+    Pause,
+    /// SERVICE_CONTROL_CONTINUE
+    Continue,
+}
+
+/// State reported to the service control manager
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceState {
+    Running,
+    Paused,
+    /// Service is cleaning up, no further controls are accepted
+    StopPending,
+}
+
+/// Handle of a running service passed to the body of [`run_service`]
+pub struct ServiceContext {
+    handle: SERVICE_STATUS_HANDLE,
+    controls: Receiver<ServiceControl>,
+    checkpoint: AtomicU32,
+}
+
+impl ServiceContext {
+    /// Control requests in the order SCM sent them
+    pub fn controls(&self) -> &Receiver<ServiceControl> {
+        &self.controls
+    }
+
+    /// Report state to the service control manager
+    ///
+    /// # Errors
+    /// When SetServiceStatus fails
+    pub fn set_state(&self, state: ServiceState) -> io::Result<()> {
+        let state = match state {
+            ServiceState::Running => SERVICE_RUNNING,
+            ServiceState::Paused => SERVICE_PAUSED,
+            ServiceState::StopPending => SERVICE_STOP_PENDING,
+        };
+        self.report(state, 0, 0)
+    }
+
+    fn report(&self, state: DWORD, exit_code: DWORD, specific_exit_code: DWORD) -> io::Result<()> {
+        let pending = state == SERVICE_STOP_PENDING;
+        let mut status = SERVICE_STATUS {
+            dwServiceType: SERVICE_WIN32_OWN_PROCESS,
+            dwCurrentState: state,
+            dwControlsAccepted: match state {
+                SERVICE_RUNNING | SERVICE_PAUSED => {
+                    SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_PRESHUTDOWN | SERVICE_ACCEPT_PAUSE_CONTINUE
+                }
+                _ => 0,
+            },
+            dwWin32ExitCode: exit_code,
+            dwServiceSpecificExitCode: specific_exit_code,
+            dwCheckPoint: match pending {
+                true => self.checkpoint.fetch_add(1, Ordering::SeqCst) + 1,
+                false => 0,
+            },
+            dwWaitHint: match pending {
+                true => 10_000,
+                false => 0,
+            },
+        };
+        if unsafe { SetServiceStatus(self.handle, &mut status) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Keep manager polling until service is stopped: pause suspends event delivery,
+    /// continue resumes it, stop and preshutdown shut manager down
+    ///
+    /// Polling runs on a ```RouteManager::spawn_poller``` thread, stopped when this returns.
+    /// It ends early when processing an event fails, the failure is returned on stop
+    ///
+    /// # Errors
+    /// When polling thread can not be spawned, status can not be reported, shutdown fails or
+    /// polling failed with ```PollError::Internal```
+    pub fn run_manager<O: SystemRouteOperate + 'static>(
+        &self,
+        manager: Arc<RouteManager<O>>,
+    ) -> io::Result<()> {
+        // without a listener there is nothing to poll
+        let poller = match manager.spawn_poller() {
+            Ok(poller) => Some(poller),
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => None,
+            Err(e) => return Err(e),
+        };
+        for control in self.controls.iter() {
+            match control {
+                ServiceControl::Pause => {
+                    manager.suspend();
+                    self.set_state(ServiceState::Paused)?;
+                }
+                ServiceControl::Continue => {
+                    manager.resume();
+                    self.set_state(ServiceState::Running)?;
+                }
+                ServiceControl::Stop | ServiceControl::Preshutdown => {
+                    self.set_state(ServiceState::StopPending)?;
+                    manager.shutdown()?;
+                    return match poller.map(Poller::shutdown) {
+                        Some(Err(e)) => Err(io::Error::other(e.to_string())),
+                        _ => Ok(()),
+                    };
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Run body as service `name`, blocking until it stops. Must be called from the process
+/// started by the service control manager, shortly after start
+///
+/// Service is reported running before body is called and stopped after it returns, an error
+/// is reported as service specific exit code
+///
+/// ```rust no_run
+/// use std::sync::Arc;
+/// use winroute::*;
+///
+/// run_service("winroute", |service| {
+///     let manager = Arc::new(RouteManager::builder().cleanup_on_drop(true).build()?);
+///     service.run_manager(manager)
+/// })
+/// .unwrap();
+/// ```
+///
+/// # Errors
+/// When process was not started by the service control manager
+pub fn run_service<F>(name: &str, body: F) -> io::Result<()>
+where
+    F: FnOnce(&ServiceContext) -> io::Result<()> + Send + 'static,
+{
+    let name: Vec<u16> = OsStr::new(name).encode_wide().chain(Some(0)).collect();
+    let table = [
+        SERVICE_TABLE_ENTRYW {
+            lpServiceName: name.as_ptr(),
+            lpServiceProc: Some(service_main),
+        },
+        SERVICE_TABLE_ENTRYW {
+            lpServiceName: std::ptr::null(),
+            lpServiceProc: None,
+        },
+    ];
+    *SERVICE
+        .lock()
+        .map_err(|_| io::Error::other("Can not lock service body"))? =
+        Some((name.clone(), Box::new(body)));
+    if unsafe { StartServiceCtrlDispatcherW(table.as_ptr()) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+unsafe extern "system" fn service_main(_argc: DWORD, _argv: *mut LPWSTR) {
+    let Some((name, body)) = SERVICE.lock().ok().and_then(|mut s| s.take()) else {
+        return;
+    };
+    let (sender, controls) = crossbeam_channel::unbounded();
+    // SCM may call the handler until process exits, context is never freed
+    let sender: *mut Sender<ServiceControl> = Box::into_raw(Box::new(sender));
+    let handle =
+        RegisterServiceCtrlHandlerExW(name.as_ptr(), Some(control_handler), sender as LPVOID);
+    if handle.is_null() {
+        return;
+    }
+    let context = ServiceContext {
+        handle,
+        controls,
+        checkpoint: AtomicU32::new(0),
+    };
+    if context.set_state(ServiceState::Running).is_err() {
+        return;
+    }
+    let _ = match body(&context) {
+        Ok(()) => context.report(SERVICE_STOPPED, 0, 0),
+        Err(e) => context.report(
+            SERVICE_STOPPED,
+            SERVICE_SPECIFIC_ERROR,
+            e.raw_os_error().map_or(1, |code| code as DWORD),
+        ),
+    };
+}
+
+unsafe extern "system" fn control_handler(
+    control: DWORD,
+    _event_type: DWORD,
+    _event_data: LPVOID,
+    context: LPVOID,
+) -> DWORD {
+    let sender = &*(context as *const Sender<ServiceControl>);
+    let control = match control {
+        SERVICE_CONTROL_STOP => ServiceControl::Stop,
+        SERVICE_CONTROL_PRESHUTDOWN => ServiceControl::Preshutdown,
+        SERVICE_CONTROL_PAUSE => ServiceControl::Pause,
+        SERVICE_CONTROL_CONTINUE => ServiceControl::Continue,
+        SERVICE_CONTROL_INTERROGATE => return 0,
+        _ => return CALL_NOT_IMPLEMENTED,
+    };
+    let _ = sender.send(control);
+    0
+}
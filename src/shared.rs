@@ -0,0 +1,289 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    io,
+    net::IpAddr,
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+
+use crossbeam_channel::{Receiver, Sender};
+
+use crate::{
+    CancelToken, ConnectionEvent, DefaultOperator, Family, InterfaceConfig, InterfaceInfo, MacAddr,
+    Prefix, Route, SystemRouteOperate, ThreadOptions, TimedEvent,
+};
+
+/// Backend whose route change notifications are fanned out to every [`SharedOperator`]
+struct Hub<O: SystemRouteOperate> {
+    operator: O,
+    subscribers: Mutex<Vec<Sender<TimedEvent>>>,
+    started: Mutex<bool>,
+    threads: ThreadOptions,
+}
+
+impl<O: SystemRouteOperate + 'static> Hub<O> {
+    fn new(operator: O, threads: ThreadOptions) -> Arc<Self> {
+        Arc::new(Self {
+            operator,
+            subscribers: Mutex::new(Vec::new()),
+            started: Mutex::new(false),
+            threads,
+        })
+    }
+
+    /// Initialize backend and start fan-out thread, only the first call has effect
+    ///
+    /// Thread only holds a weak reference, so dropping the last [`SharedBackend`] and
+    /// [`SharedOperator`] drops backend, which disconnects its events and ends the thread
+    fn start(self: &Arc<Self>) -> io::Result<()> {
+        let mut started = self.started.lock().map_err(|_| {
+            io::Error::other("Can not lock inner data, this is a thread safe error")
+        })?;
+        if *started {
+            return Ok(());
+        }
+        self.operator.init()?;
+        let receiver = self.operator.event_receiver();
+        let hub = Arc::downgrade(self);
+        self.threads.spawn("shared", move || {
+            for event in receiver.iter() {
+                let Some(live) = Weak::upgrade(&hub) else {
+                    break;
+                };
+                let Ok(mut subscribers) = live.subscribers.lock() else {
+                    break;
+                };
+                subscribers.retain(|s| s.send(event.clone()).is_ok());
+            }
+        })?;
+        *started = true;
+        Ok(())
+    }
+
+    fn subscribe(&self) -> Receiver<TimedEvent> {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.push(sender);
+        }
+        receiver
+    }
+}
+
+/// Backend whose single route change registration is shared by any number of
+/// [`SharedOperator`]s, ```SharedOperator::new``` uses a process-wide one over the system backend
+pub struct SharedBackend<O: SystemRouteOperate + 'static> {
+    hub: Arc<Hub<O>>,
+}
+
+impl<O: SystemRouteOperate + 'static> SharedBackend<O> {
+    /// Share `backend`, it is initialized when the first manager is built on it
+    pub fn new(backend: O) -> Self {
+        Self::with_thread_options(backend, ThreadOptions::default())
+    }
+
+    /// Share `backend`, fan-out thread is spawned with `threads`
+    pub fn with_thread_options(backend: O, threads: ThreadOptions) -> Self {
+        Self {
+            hub: Hub::new(backend, threads),
+        }
+    }
+
+    /// Create an operator that receives every event of the shared backend
+    pub fn operator(&self) -> SharedOperator<O> {
+        SharedOperator::attach(&self.hub)
+    }
+}
+
+impl<O: SystemRouteOperate + 'static> Clone for SharedBackend<O> {
+    fn clone(&self) -> Self {
+        Self {
+            hub: self.hub.clone(),
+        }
+    }
+}
+
+/// Backend sharing a single process-wide route change registration
+///
+/// Every [`RouteManager`](crate::RouteManager) built on a `SharedOperator` receives all
+/// events of the same underlying backend, so several managers in one process register
+/// only one system notification callback
+///
+/// ```rust no_run
+/// use winroute::*;
+///
+/// let a = RouteManager::with_backend(SharedOperator::new().unwrap()).unwrap();
+/// let b = RouteManager::with_backend(SharedOperator::new().unwrap()).unwrap();
+/// ```
+pub struct SharedOperator<O: SystemRouteOperate + 'static = DefaultOperator> {
+    hub: Arc<Hub<O>>,
+    receiver: Receiver<TimedEvent>,
+}
+
+#[cfg(windows)]
+impl SharedOperator {
+    /// Attach to the process-wide backend, it is created on first use
+    pub fn new() -> io::Result<Self> {
+        static BACKEND: std::sync::OnceLock<SharedBackend<DefaultOperator>> =
+            std::sync::OnceLock::new();
+
+        let backend = BACKEND.get_or_init(|| SharedBackend::new(crate::WindowsOperator::new()));
+        Ok(backend.operator())
+    }
+}
+
+#[cfg(not(windows))]
+impl SharedOperator {
+    pub fn new() -> io::Result<Self> {
+        Err(io::Error::other("None windows system not supported"))
+    }
+}
+
+impl<O: SystemRouteOperate + 'static> SharedOperator<O> {
+    fn attach(hub: &Arc<Hub<O>>) -> Self {
+        Self {
+            hub: hub.clone(),
+            receiver: hub.subscribe(),
+        }
+    }
+}
+
+impl<O: SystemRouteOperate + 'static> SystemRouteOperate for SharedOperator<O> {
+    fn init(&self) -> io::Result<()> {
+        self.hub.start()
+    }
+
+    fn event_receiver(&self) -> Receiver<TimedEvent> {
+        self.receiver.clone()
+    }
+
+    fn read_all_routes(&self) -> io::Result<Vec<Route>> {
+        self.hub.operator.read_all_routes()
+    }
+
+    fn add_route(&self, route: &Route) -> io::Result<Route> {
+        self.hub.operator.add_route(route)
+    }
+
+    fn add_routes(&self, routes: &[Route]) -> Vec<io::Result<Route>> {
+        self.hub.operator.add_routes(routes)
+    }
+
+    fn best_interface(&self, addr: IpAddr) -> io::Result<u32> {
+        self.hub.operator.best_interface(addr)
+    }
+
+    fn interface_metric(&self, family: Family, ifindex: u32) -> io::Result<u32> {
+        self.hub.operator.interface_metric(family, ifindex)
+    }
+
+    fn read_route(&self, route: &Route) -> io::Result<Route> {
+        self.hub.operator.read_route(route)
+    }
+
+    fn delete_route(&self, route: &Route) -> io::Result<()> {
+        self.hub.operator.delete_route(route)
+    }
+
+    fn update_route(&self, route: &Route) -> io::Result<()> {
+        self.hub.operator.update_route(route)
+    }
+
+    fn best_route(&self, destination: IpAddr) -> io::Result<Route> {
+        self.hub.operator.best_route(destination)
+    }
+
+    fn resolve_neighbor(&self, addr: IpAddr, ifindex: u32) -> io::Result<Option<MacAddr>> {
+        self.hub.operator.resolve_neighbor(addr, ifindex)
+    }
+
+    fn probe_neighbor(&self, addr: IpAddr, ifindex: u32) -> io::Result<Option<MacAddr>> {
+        self.hub.operator.probe_neighbor(addr, ifindex)
+    }
+
+    fn interfaces(&self) -> io::Result<Vec<InterfaceInfo>> {
+        self.hub.operator.interfaces()
+    }
+
+    fn interface_link_speed(&self, ifindex: u32) -> io::Result<u64> {
+        self.hub.operator.interface_link_speed(ifindex)
+    }
+
+    fn interface_addresses(&self, ifindex: u32) -> io::Result<Vec<IpAddr>> {
+        self.hub.operator.interface_addresses(ifindex)
+    }
+
+    fn interface_prefixes(&self, ifindex: u32) -> io::Result<Vec<Prefix>> {
+        self.hub.operator.interface_prefixes(ifindex)
+    }
+
+    fn wait_stable_addresses(
+        &self,
+        family: Option<Family>,
+        timeout: Duration,
+        cancel: &CancelToken,
+    ) -> io::Result<()> {
+        self.hub
+            .operator
+            .wait_stable_addresses(family, timeout, cancel)
+    }
+
+    fn connection_events(&self) -> io::Result<Receiver<ConnectionEvent>> {
+        self.hub.operator.connection_events()
+    }
+
+    fn interface_config(&self, family: Family, ifindex: u32) -> io::Result<InterfaceConfig> {
+        self.hub.operator.interface_config(family, ifindex)
+    }
+
+    fn set_interface_config(&self, config: &InterfaceConfig) -> io::Result<()> {
+        self.hub.operator.set_interface_config(config)
+    }
+}
+
+#[cfg(test)]
+pub mod test_shared {
+    use super::SharedBackend;
+    use crate::{mock::MockOperator, Route, RouteEvent, RouteManager, SystemRouteOperate};
+
+    #[test]
+    fn test_fan_out() {
+        let shared = SharedBackend::new(MockOperator::new(vec![]));
+        let a = RouteManager::with_backend(shared.operator()).unwrap();
+        let b = RouteManager::with_backend(shared.operator()).unwrap();
+
+        let route = Route::new("10.0.0.0".parse().unwrap(), 8).ifindex(1);
+        a.add_route(&route).unwrap();
+        assert_eq!(RouteEvent::Add(route.clone()), a.poll_event().unwrap());
+        assert_eq!(RouteEvent::Add(route), b.poll_event().unwrap());
+    }
+
+    #[test]
+    fn test_release() {
+        let shared = SharedBackend::new(MockOperator::new(vec![]));
+        let manager = RouteManager::with_backend(shared.operator()).unwrap();
+        let hub = std::sync::Arc::downgrade(&shared.hub);
+        let events = hub.upgrade().unwrap().operator.event_receiver();
+        drop(shared);
+        drop(manager);
+        assert!(hub.upgrade().is_none());
+        // backend is gone with the hub, fan-out thread sees its channel disconnect
+        assert!(events.recv().is_err());
+    }
+}
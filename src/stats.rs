@@ -0,0 +1,223 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::RouteEvent;
+
+const MINUTE: u64 = 60;
+const HOUR: u64 = 60 * MINUTE;
+const MINUTE_BUCKETS: usize = 60;
+const HOUR_BUCKETS: usize = 24;
+
+/// Number of events by kind
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EventCounts {
+    pub add: u64,
+    pub delete: u64,
+    pub change: u64,
+}
+
+impl EventCounts {
+    /// Sum of all kinds
+    pub fn total(&self) -> u64 {
+        self.add + self.delete + self.change
+    }
+
+    fn record(&mut self, event: &RouteEvent) {
+        match event {
+            RouteEvent::Add(_) => self.add += 1,
+            RouteEvent::Delete(_) => self.delete += 1,
+            RouteEvent::Change(_) => self.change += 1,
+            RouteEvent::Initial => {}
+        }
+    }
+
+    fn merge(&mut self, other: &EventCounts) {
+        self.add += other.add;
+        self.delete += other.delete;
+        self.change += other.change;
+    }
+}
+
+/// Route change statistics, returned by ```RouteManager::statistics```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RouteStatistics {
+    /// Events since manager was created
+    pub total: EventCounts,
+    /// Events of the last 60 minutes, one entry per minute, oldest first
+    pub per_minute: Vec<EventCounts>,
+    /// Events of the last 24 hours, one entry per hour, oldest first
+    pub per_hour: Vec<EventCounts>,
+    /// Destination prefixes with most events, most churned first
+    pub top_prefixes: Vec<((IpAddr, u8), u64)>,
+    /// Events per interface index, most churned first
+    pub interfaces: Vec<(u32, u64)>,
+}
+
+/// Time bucketed event counters
+struct Buckets {
+    width: u64,
+    len: usize,
+    buckets: VecDeque<(u64, EventCounts)>,
+}
+
+impl Buckets {
+    fn new(width: u64, len: usize) -> Self {
+        Self {
+            width,
+            len,
+            buckets: VecDeque::new(),
+        }
+    }
+
+    fn record(&mut self, now: u64, event: &RouteEvent) {
+        let index = now / self.width;
+        if self.buckets.back().map(|(i, _)| *i) != Some(index) {
+            self.buckets.push_back((index, EventCounts::default()));
+        }
+        if let Some((_, counts)) = self.buckets.back_mut() {
+            counts.record(event);
+        }
+        while self.buckets.len() > self.len {
+            self.buckets.pop_front();
+        }
+    }
+
+    /// Counters of the last `len` buckets ending at now, oldest first, empty buckets included
+    fn window(&self, now: u64) -> Vec<EventCounts> {
+        let last = now / self.width;
+        let first = (last + 1).saturating_sub(self.len as u64);
+        (first..=last)
+            .map(|index| {
+                let mut counts = EventCounts::default();
+                for (_, c) in self.buckets.iter().filter(|(i, _)| *i == index) {
+                    counts.merge(c);
+                }
+                counts
+            })
+            .collect()
+    }
+}
+
+pub(crate) struct StatisticsCollector {
+    total: EventCounts,
+    minutes: Buckets,
+    hours: Buckets,
+    prefixes: HashMap<(IpAddr, u8), u64>,
+    interfaces: HashMap<u32, u64>,
+}
+
+impl StatisticsCollector {
+    pub(crate) fn new() -> Self {
+        Self {
+            total: EventCounts::default(),
+            minutes: Buckets::new(MINUTE, MINUTE_BUCKETS),
+            hours: Buckets::new(HOUR, HOUR_BUCKETS),
+            prefixes: HashMap::new(),
+            interfaces: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn record(&mut self, event: &RouteEvent) {
+        self.record_at(event, SystemTime::now());
+    }
+
+    fn record_at(&mut self, event: &RouteEvent, time: SystemTime) {
+        let Some(route) = event.route() else {
+            return;
+        };
+        let now = unix_secs(time);
+        self.total.record(event);
+        self.minutes.record(now, event);
+        self.hours.record(now, event);
+        *self
+            .prefixes
+            .entry((route.destination, route.prefix))
+            .or_default() += 1;
+        if let Some(ifindex) = route.ifindex {
+            *self.interfaces.entry(ifindex).or_default() += 1;
+        }
+    }
+
+    pub(crate) fn snapshot(&self, top: usize) -> RouteStatistics {
+        self.snapshot_at(top, SystemTime::now())
+    }
+
+    fn snapshot_at(&self, top: usize, time: SystemTime) -> RouteStatistics {
+        let now = unix_secs(time);
+        let mut top_prefixes: Vec<((IpAddr, u8), u64)> =
+            self.prefixes.iter().map(|(k, v)| (*k, *v)).collect();
+        top_prefixes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        top_prefixes.truncate(top);
+        let mut interfaces: Vec<(u32, u64)> =
+            self.interfaces.iter().map(|(k, v)| (*k, *v)).collect();
+        interfaces.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        RouteStatistics {
+            total: self.total,
+            per_minute: self.minutes.window(now),
+            per_hour: self.hours.window(now),
+            top_prefixes,
+            interfaces,
+        }
+    }
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+pub mod test_stats {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::StatisticsCollector;
+    use crate::{Route, RouteEvent};
+
+    #[test]
+    fn test_buckets() {
+        let a = Route::new("10.0.0.0".parse().unwrap(), 8).ifindex(1);
+        let b = Route::new("10.1.0.0".parse().unwrap(), 16).ifindex(2);
+        let start = UNIX_EPOCH + Duration::from_secs(3600 * 1000);
+        let mut stats = StatisticsCollector::new();
+        stats.record_at(&RouteEvent::Add(a.clone()), start);
+        stats.record_at(
+            &RouteEvent::Change(a.clone()),
+            start + Duration::from_secs(61),
+        );
+        stats.record_at(
+            &RouteEvent::Delete(b.clone()),
+            start + Duration::from_secs(62),
+        );
+
+        let snapshot = stats.snapshot_at(1, start + Duration::from_secs(62));
+        assert_eq!(3, snapshot.total.total());
+        assert_eq!(60, snapshot.per_minute.len());
+        assert_eq!(2, snapshot.per_minute[59].total());
+        assert_eq!(1, snapshot.per_minute[58].add);
+        assert_eq!(3, snapshot.per_hour[23].total());
+        assert_eq!(vec![((a.destination, 8), 2)], snapshot.top_prefixes);
+        assert_eq!(vec![(1, 2), (2, 1)], snapshot.interfaces);
+    }
+}
@@ -0,0 +1,156 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Async consumption of route events, enabled with the `async` feature
+
+use std::{
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use crossbeam_channel::TryRecvError;
+use futures_core::Stream;
+
+use crate::{RouteEvent, RouteManager, Subscription, SystemRouteOperate};
+
+/// Waker of the task polling a stream, woken when manager buffers an event for it and when
+/// manager drops its side
+struct Notifier(Arc<Mutex<Option<Waker>>>);
+
+impl Notifier {
+    fn wake(&self) {
+        if let Some(waker) = self.0.lock().ok().and_then(|mut w| w.take()) {
+            waker.wake();
+        }
+    }
+}
+
+impl Drop for Notifier {
+    fn drop(&mut self) {
+        self.wake();
+    }
+}
+
+/// Route events as a [`Stream`], created by ```RouteManager::subscribe_stream```
+///
+/// Events are buffered while manager is polled, e.g. by ```RouteManager::poll``` on a
+/// thread or a blocking task, the stream itself never blocks. It ends when manager is dropped
+pub struct RouteEventStream {
+    subscription: Subscription,
+    waker: Arc<Mutex<Option<Waker>>>,
+}
+
+impl Stream for RouteEventStream {
+    type Item = RouteEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.subscription.try_recv() {
+            Ok(event) => return Poll::Ready(Some(event)),
+            Err(TryRecvError::Disconnected) => return Poll::Ready(None),
+            Err(TryRecvError::Empty) => {}
+        }
+        if let Ok(mut waker) = self.waker.lock() {
+            *waker = Some(cx.waker().clone());
+        }
+        // an event may have been buffered before the waker was registered
+        match self.subscription.try_recv() {
+            Ok(event) => Poll::Ready(Some(event)),
+            Err(TryRecvError::Disconnected) => Poll::Ready(None),
+            Err(TryRecvError::Empty) => Poll::Pending,
+        }
+    }
+}
+
+impl<O: SystemRouteOperate> RouteManager<O> {
+    /// Subscribe routing table change event as an async [`Stream`] with a dedicated buffer
+    ///
+    /// ```rust ignore
+    /// use futures::StreamExt;
+    ///
+    /// let manager = std::sync::Arc::new(winroute::RouteManager::new()?);
+    /// let mut events = manager.subscribe_stream()?;
+    /// let poller = manager.clone();
+    /// tokio::task::spawn_blocking(move || while poller.poll().is_ok() {});
+    /// while let Some(event) = events.next().await {
+    ///     println!("{event}");
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn subscribe_stream(&self) -> io::Result<RouteEventStream> {
+        let waker = Arc::new(Mutex::new(None));
+        let notifier = Notifier(waker.clone());
+        let (subscriber, subscription) = self.new_subscriber();
+        self.push_subscriber(subscriber.notify(Box::new(move || notifier.wake())))?;
+        Ok(RouteEventStream {
+            subscription,
+            waker,
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod test_stream {
+    use std::{
+        pin::Pin,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        task::{Context, Poll, Wake, Waker},
+    };
+
+    use futures_core::Stream;
+
+    use crate::{mock::MockOperator, Route, RouteEvent, RouteManager};
+
+    struct CountWaker(AtomicUsize);
+
+    impl Wake for CountWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_stream() {
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        let mut stream = manager.subscribe_stream().unwrap();
+        let count = Arc::new(CountWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(count.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        assert!(Pin::new(&mut stream).poll_next(&mut cx).is_pending());
+        let route = Route::new("10.0.0.0".parse().unwrap(), 8);
+        manager.add_route(&route).unwrap();
+        manager.poll().unwrap();
+        assert_eq!(1, count.0.load(Ordering::SeqCst));
+        assert!(matches!(
+            Pin::new(&mut stream).poll_next(&mut cx),
+            Poll::Ready(Some(RouteEvent::Add(_)))
+        ));
+
+        assert!(Pin::new(&mut stream).poll_next(&mut cx).is_pending());
+        drop(manager);
+        assert_eq!(2, count.0.load(Ordering::SeqCst));
+        assert_eq!(Poll::Ready(None), Pin::new(&mut stream).poll_next(&mut cx));
+    }
+}
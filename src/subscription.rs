@@ -0,0 +1,409 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Weak,
+    },
+    time::Duration,
+};
+
+use crossbeam_channel::{
+    Receiver, RecvError, RecvTimeoutError, Sender, TryRecvError, TrySendError,
+};
+
+use crate::RouteEvent;
+
+/// What to do with a new event when subscriber's buffer is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Wait until subscriber receives an event, ```poll``` blocks meanwhile, nothing is lost
+    Block,
+    /// Discard the oldest buffered event to make room for the new one
+    DropOldest,
+    /// Discard the new event
+    DropNewest,
+}
+
+/// Route event subscription with its own buffer, created by
+/// ```RouteManager::subscribe_route_change``` or ```RouteManager::subscribe_with```
+///
+/// Manager stops delivering to it once it is dropped
+pub struct Subscription {
+    receiver: Receiver<RouteEvent>,
+    _alive: Arc<()>,
+}
+
+impl Subscription {
+    /// Block until next event arrives
+    ///
+    /// # Errors
+    /// When manager is dropped and buffer is empty
+    pub fn recv(&self) -> Result<RouteEvent, RecvError> {
+        self.receiver.recv()
+    }
+
+    /// Return buffered event without blocking
+    ///
+    /// # Errors
+    /// When buffer is empty or manager is dropped
+    pub fn try_recv(&self) -> Result<RouteEvent, TryRecvError> {
+        self.receiver.try_recv()
+    }
+
+    /// Block until next event arrives or timeout elapses
+    ///
+    /// # Errors
+    /// When timeout elapses or manager is dropped
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<RouteEvent, RecvTimeoutError> {
+        self.receiver.recv_timeout(timeout)
+    }
+
+    /// Detach from manager, which stops delivering to it on the next event, same as dropping it
+    pub fn unsubscribe(self) {}
+}
+
+impl Subscription {
+    /// Underlying channel, for use in `crossbeam_channel::select!`
+    #[cfg_attr(
+        not(any(feature = "http", all(feature = "pipe", windows))),
+        allow(dead_code)
+    )]
+    pub(crate) fn receiver(&self) -> &Receiver<RouteEvent> {
+        &self.receiver
+    }
+
+    /// Blocking iterator over events, ends when manager is dropped
+    pub fn iter(&self) -> SubscriptionIter<'_> {
+        SubscriptionIter {
+            inner: self.receiver.iter(),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a Subscription {
+    type Item = RouteEvent;
+    type IntoIter = SubscriptionIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for Subscription {
+    type Item = RouteEvent;
+    type IntoIter = SubscriptionIntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        SubscriptionIntoIter {
+            inner: self.receiver.clone().into_iter(),
+            _subscription: self,
+        }
+    }
+}
+
+/// Borrowing blocking iterator over a [`Subscription`]
+pub struct SubscriptionIter<'a> {
+    inner: crossbeam_channel::Iter<'a, RouteEvent>,
+}
+
+impl Iterator for SubscriptionIter<'_> {
+    type Item = RouteEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+/// Owning blocking iterator over a [`Subscription`]
+pub struct SubscriptionIntoIter {
+    inner: crossbeam_channel::IntoIter<RouteEvent>,
+    // keeps subscription registered while iterating
+    _subscription: Subscription,
+}
+
+impl Iterator for SubscriptionIntoIter {
+    type Item = RouteEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+/// Route event subscription delivering batches of events, created by
+/// ```RouteManager::subscribe_batched```
+///
+/// Every receive returns all events accumulated since the previous one, up to the maximum
+/// batch size, so consumers rebuilding derived state wake up once per batch
+pub struct BatchSubscription {
+    subscription: Subscription,
+    max_batch: usize,
+}
+
+impl BatchSubscription {
+    pub(crate) fn new(subscription: Subscription, max_batch: usize) -> Self {
+        Self {
+            subscription,
+            max_batch: max_batch.max(1),
+        }
+    }
+
+    /// Block until at least one event arrives, then return it with events buffered after it
+    ///
+    /// # Errors
+    /// When manager is dropped and buffer is empty
+    pub fn recv(&self) -> Result<Vec<RouteEvent>, RecvError> {
+        let first = self.subscription.recv()?;
+        Ok(self.fill(first))
+    }
+
+    /// Return buffered events without blocking
+    ///
+    /// # Errors
+    /// When buffer is empty or manager is dropped
+    pub fn try_recv(&self) -> Result<Vec<RouteEvent>, TryRecvError> {
+        let first = self.subscription.try_recv()?;
+        Ok(self.fill(first))
+    }
+
+    /// Block until at least one event arrives or timeout elapses
+    ///
+    /// # Errors
+    /// When timeout elapses or manager is dropped
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Vec<RouteEvent>, RecvTimeoutError> {
+        let first = self.subscription.recv_timeout(timeout)?;
+        Ok(self.fill(first))
+    }
+
+    /// Blocking iterator over batches, ends when manager is dropped
+    pub fn iter(&self) -> impl Iterator<Item = Vec<RouteEvent>> + '_ {
+        std::iter::from_fn(move || self.recv().ok())
+    }
+
+    fn fill(&self, first: RouteEvent) -> Vec<RouteEvent> {
+        let mut batch = vec![first];
+        while batch.len() < self.max_batch {
+            match self.subscription.try_recv() {
+                Ok(event) => batch.push(event),
+                Err(_) => break,
+            }
+        }
+        batch
+    }
+}
+
+/// Predicate deciding which events a [`Subscriber`] receives
+pub(crate) type EventFilter = Box<dyn Fn(&RouteEvent) -> bool + Send>;
+
+/// Sending end of a channel that applies an overflow policy when it is bounded
+pub(crate) struct PolicySender<T> {
+    sender: Sender<T>,
+    // kept to evict buffered items with DropOldest
+    receiver: Option<Receiver<T>>,
+    policy: OverflowPolicy,
+    // items discarded by policy, shared with whoever reports the loss
+    lost: Arc<AtomicUsize>,
+}
+
+impl<T> PolicySender<T> {
+    /// Create bounded channel, or unbounded one when capacity is `None`
+    pub(crate) fn channel(capacity: Option<usize>, policy: OverflowPolicy) -> (Self, Receiver<T>) {
+        let (sender, receiver) = match capacity {
+            Some(capacity) => crossbeam_channel::bounded(capacity),
+            None => crossbeam_channel::unbounded(),
+        };
+        let sender = PolicySender {
+            sender,
+            receiver: (policy == OverflowPolicy::DropOldest).then(|| receiver.clone()),
+            policy,
+            lost: Arc::default(),
+        };
+        (sender, receiver)
+    }
+
+    /// Counter of items discarded by overflow policy
+    pub(crate) fn lost(&self) -> Arc<AtomicUsize> {
+        self.lost.clone()
+    }
+
+    /// Send item according to overflow policy, counting it when it or an older one was
+    /// discarded
+    pub(crate) fn send(&self, item: T) -> Delivery {
+        let delivery = self.deliver(item);
+        if let Delivery::Dropped = delivery {
+            self.lost.fetch_add(1, Ordering::SeqCst);
+        }
+        delivery
+    }
+
+    fn deliver(&self, item: T) -> Delivery {
+        match self.policy {
+            OverflowPolicy::Block => match self.sender.send(item) {
+                Ok(()) => Delivery::Delivered,
+                Err(_) => Delivery::Closed,
+            },
+            OverflowPolicy::DropNewest => match self.sender.try_send(item) {
+                Ok(()) => Delivery::Delivered,
+                Err(TrySendError::Full(_)) => Delivery::Dropped,
+                Err(TrySendError::Disconnected(_)) => Delivery::Closed,
+            },
+            OverflowPolicy::DropOldest => {
+                let mut item = item;
+                let mut delivery = Delivery::Delivered;
+                loop {
+                    match self.sender.try_send(item) {
+                        Ok(()) => return delivery,
+                        Err(TrySendError::Disconnected(_)) => return Delivery::Closed,
+                        Err(TrySendError::Full(back)) => {
+                            if let Some(receiver) = &self.receiver {
+                                let _ = receiver.try_recv();
+                            }
+                            delivery = Delivery::Dropped;
+                            item = back;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Manager side of a [`Subscription`]
+pub(crate) struct Subscriber {
+    sender: PolicySender<RouteEvent>,
+    alive: Weak<()>,
+    filter: Option<EventFilter>,
+    // called after an event was buffered, e.g. to wake an async task
+    notify: Option<Box<dyn Fn() + Send>>,
+}
+
+impl Subscriber {
+    /// Create subscriber with bounded buffer, or unbounded one when capacity is `None`
+    pub(crate) fn new(capacity: Option<usize>, policy: OverflowPolicy) -> (Self, Subscription) {
+        let (sender, receiver) = PolicySender::channel(capacity, policy);
+        let alive = Arc::new(());
+        let subscriber = Subscriber {
+            sender,
+            alive: Arc::downgrade(&alive),
+            filter: None,
+            notify: None,
+        };
+        let subscription = Subscription {
+            receiver,
+            _alive: alive,
+        };
+        (subscriber, subscription)
+    }
+
+    /// Only deliver events filter accepts
+    pub(crate) fn filter(mut self, filter: EventFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Call notify after every event buffered for subscription
+    #[cfg_attr(not(any(feature = "async", feature = "tokio")), allow(dead_code))]
+    pub(crate) fn notify(mut self, notify: Box<dyn Fn() + Send>) -> Self {
+        self.notify = Some(notify);
+        self
+    }
+
+    /// Whether its subscription is still held
+    pub(crate) fn is_alive(&self) -> bool {
+        self.alive.strong_count() > 0
+    }
+
+    /// Deliver event according to overflow policy
+    pub(crate) fn deliver(&self, event: &RouteEvent) -> Delivery {
+        if !self.is_alive() {
+            return Delivery::Closed;
+        }
+        if self.filter.as_ref().is_some_and(|accept| !accept(event)) {
+            return Delivery::Delivered;
+        }
+        let delivery = self.sender.send(event.clone());
+        if let (Some(notify), Delivery::Delivered | Delivery::Dropped) = (&self.notify, delivery) {
+            notify();
+        }
+        delivery
+    }
+}
+
+/// Outcome of delivering an event to a [`Subscriber`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Delivery {
+    Delivered,
+    /// Buffer was full and an event was discarded by overflow policy
+    Dropped,
+    /// Subscription was dropped
+    Closed,
+}
+
+#[cfg(test)]
+pub mod test_subscription {
+    use super::{BatchSubscription, Delivery, OverflowPolicy, Subscriber};
+    use crate::{Route, RouteEvent};
+
+    fn event(prefix: u8) -> RouteEvent {
+        RouteEvent::Add(Route::new("10.0.0.0".parse().unwrap(), prefix))
+    }
+
+    #[test]
+    fn test_overflow_policy() {
+        let (oldest, oldest_sub) = Subscriber::new(Some(2), OverflowPolicy::DropOldest);
+        let (newest, newest_sub) = Subscriber::new(Some(2), OverflowPolicy::DropNewest);
+        for prefix in 8..10 {
+            assert_eq!(Delivery::Delivered, oldest.deliver(&event(prefix)));
+            assert_eq!(Delivery::Delivered, newest.deliver(&event(prefix)));
+        }
+        assert_eq!(Delivery::Dropped, oldest.deliver(&event(10)));
+        assert_eq!(Delivery::Dropped, newest.deliver(&event(10)));
+        assert_eq!(event(9), oldest_sub.try_recv().unwrap());
+        assert_eq!(event(10), oldest_sub.try_recv().unwrap());
+        assert_eq!(event(8), newest_sub.try_recv().unwrap());
+        assert_eq!(event(9), newest_sub.try_recv().unwrap());
+        assert!(newest_sub.try_recv().is_err());
+
+        drop(oldest_sub);
+        assert_eq!(Delivery::Closed, oldest.deliver(&event(8)));
+    }
+
+    #[test]
+    fn test_batch() {
+        let (subscriber, subscription) = Subscriber::new(None, OverflowPolicy::Block);
+        let batches = BatchSubscription::new(subscription, 2);
+        assert!(batches.try_recv().is_err());
+        for prefix in 1..=3 {
+            subscriber.deliver(&event(prefix));
+        }
+        assert_eq!(vec![event(1), event(2)], batches.recv().unwrap());
+        assert_eq!(vec![event(3)], batches.try_recv().unwrap());
+    }
+
+    #[test]
+    fn test_iter() {
+        let (subscriber, subscription) = Subscriber::new(None, OverflowPolicy::Block);
+        for prefix in 8..11 {
+            assert_eq!(Delivery::Delivered, subscriber.deliver(&event(prefix)));
+        }
+        drop(subscriber);
+        let events: Vec<RouteEvent> = subscription.into_iter().collect();
+        assert_eq!(vec![event(8), event(9), event(10)], events);
+    }
+}
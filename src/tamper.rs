@@ -0,0 +1,102 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fmt::Display;
+
+use crate::{Route, RouteEvent};
+
+/// Change made by someone else to a route this manager installed, see
+/// ```RouteManager::subscribe_tamper```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OwnedRouteEvent {
+    /// Route is still present but its metric is no longer the one manager set
+    Modified { owned: Route, current: Route },
+    /// Route was deleted, manager no longer owns it
+    Deleted(Route),
+}
+
+impl Display for OwnedRouteEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OwnedRouteEvent::Modified { owned, current } => write!(
+                f,
+                "owned route {} modified, metric {:?} -> {:?}",
+                owned.cidr(),
+                owned.metric,
+                current.metric
+            ),
+            OwnedRouteEvent::Deleted(route) => write!(f, "owned route {} deleted", route.cidr()),
+        }
+    }
+}
+
+/// Compare event with routes manager owns, forgetting owned routes that were deleted
+///
+/// Routes manager deleted or changed itself are updated in `owned` before their event
+/// arrives, so they are never reported
+pub(crate) fn detect(owned: &mut Vec<Route>, event: &RouteEvent) -> Option<OwnedRouteEvent> {
+    match event {
+        RouteEvent::Add(_) | RouteEvent::Initial => None,
+        RouteEvent::Delete(route) => {
+            let index = owned.iter().position(|r| r.is_same_entry(route))?;
+            Some(OwnedRouteEvent::Deleted(owned.remove(index)))
+        }
+        RouteEvent::Change(route) => {
+            let mine = owned.iter().find(|r| r.is_same_entry(route))?;
+            if mine.metric.is_none() || mine.metric == route.metric {
+                return None;
+            }
+            Some(OwnedRouteEvent::Modified {
+                owned: mine.clone(),
+                current: route.clone(),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test_tamper {
+    use super::{detect, OwnedRouteEvent};
+    use crate::{Route, RouteEvent};
+
+    #[test]
+    fn test_detect() {
+        let mine = Route::new("10.0.0.0".parse().unwrap(), 8)
+            .gateway("192.168.1.1".parse().unwrap())
+            .ifindex(1)
+            .metric(5);
+        let other = Route::new("10.1.0.0".parse().unwrap(), 16).ifindex(1);
+        let mut owned = vec![mine.clone()];
+
+        assert_eq!(None, detect(&mut owned, &RouteEvent::Delete(other)));
+        assert_eq!(None, detect(&mut owned, &RouteEvent::Change(mine.clone())));
+        let changed = mine.clone().metric(50);
+        assert_eq!(
+            Some(OwnedRouteEvent::Modified {
+                owned: mine.clone(),
+                current: changed.clone()
+            }),
+            detect(&mut owned, &RouteEvent::Change(changed))
+        );
+        assert_eq!(
+            Some(OwnedRouteEvent::Deleted(mine.clone())),
+            detect(&mut owned, &RouteEvent::Delete(mine))
+        );
+        assert!(owned.is_empty());
+    }
+}
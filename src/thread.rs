@@ -0,0 +1,145 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    io,
+    thread::{Builder, JoinHandle, Scope, ScopedJoinHandle},
+};
+
+/// Scheduling priority of background threads, only applied on Windows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ThreadPriority {
+    Lowest,
+    BelowNormal,
+    #[default]
+    Normal,
+    AboveNormal,
+    Highest,
+}
+
+/// How background threads owned by this crate are spawned
+///
+/// Threads are named `<name>-<role>`, e.g. `winroute-poller`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreadOptions {
+    name: String,
+    stack_size: Option<usize>,
+    priority: ThreadPriority,
+}
+
+impl Default for ThreadOptions {
+    fn default() -> Self {
+        Self {
+            name: "winroute".into(),
+            stack_size: None,
+            priority: ThreadPriority::Normal,
+        }
+    }
+}
+
+impl ThreadOptions {
+    /// Create options with name prefix `winroute`, default stack size and normal priority
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// name prefix setter
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// stack size setter, in bytes
+    pub fn stack_size(mut self, size: usize) -> Self {
+        self.stack_size = Some(size);
+        self
+    }
+
+    /// priority setter
+    pub fn priority(mut self, priority: ThreadPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Spawn a thread named after `role`
+    pub(crate) fn spawn<F, T>(&self, role: &str, f: F) -> io::Result<JoinHandle<T>>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let priority = self.priority;
+        self.builder(role).spawn(move || {
+            if priority != ThreadPriority::Normal {
+                set_current_thread_priority(priority);
+            }
+            f()
+        })
+    }
+
+    /// Spawn a scoped thread named after `role`
+    pub(crate) fn spawn_scoped<'scope, 'env, F, T>(
+        &self,
+        scope: &'scope Scope<'scope, 'env>,
+        role: &str,
+        f: F,
+    ) -> io::Result<ScopedJoinHandle<'scope, T>>
+    where
+        F: FnOnce() -> T + Send + 'scope,
+        T: Send + 'scope,
+    {
+        let priority = self.priority;
+        self.builder(role).spawn_scoped(scope, move || {
+            if priority != ThreadPriority::Normal {
+                set_current_thread_priority(priority);
+            }
+            f()
+        })
+    }
+
+    fn builder(&self, role: &str) -> Builder {
+        let mut builder = Builder::new().name(format!("{}-{}", self.name, role));
+        if let Some(size) = self.stack_size {
+            builder = builder.stack_size(size);
+        }
+        builder
+    }
+}
+
+#[cfg(windows)]
+fn set_current_thread_priority(priority: ThreadPriority) {
+    crate::windows::set_current_thread_priority(priority);
+}
+
+#[cfg(not(windows))]
+fn set_current_thread_priority(_priority: ThreadPriority) {}
+
+#[cfg(test)]
+pub mod test_thread {
+    use super::ThreadOptions;
+
+    #[test]
+    fn test_spawn_named() {
+        let options = ThreadOptions::new().name("test").stack_size(256 * 1024);
+        let name = options
+            .spawn("worker", || std::thread::current().name().map(String::from))
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(Some("test-worker".to_string()), name);
+    }
+}
@@ -0,0 +1,168 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Driving a manager from tokio tasks, enabled with the `tokio` feature
+
+use std::{io, sync::Arc};
+
+use crossbeam_channel::TryRecvError;
+use tokio::sync::{mpsc, Mutex, Notify};
+
+use crate::{PollError, RouteEvent, RouteManager, Subscription, SystemRouteOperate, TimedEvent};
+
+/// Backend events forwarded from a dedicated thread to ```RouteManager::poll_async```,
+/// started by its first call
+#[derive(Default)]
+pub(crate) struct Bridge {
+    events: Mutex<Option<mpsc::UnboundedReceiver<TimedEvent>>>,
+}
+
+impl<O: SystemRouteOperate> RouteManager<O> {
+    /// Async version of ```poll```, waits for the next backend event without blocking a
+    /// runtime worker thread
+    ///
+    /// First call starts a thread forwarding backend events to it, from then on events are
+    /// only processed by ```poll_async``` and ```poll_event_async```, never mix them with the
+    /// blocking variants
+    ///
+    /// ```rust ignore
+    /// let manager = std::sync::Arc::new(winroute::RouteManager::new()?);
+    /// let poller = manager.clone();
+    /// tokio::spawn(async move { while poller.poll_async().await.is_ok() {} });
+    /// ```
+    ///
+    /// # Errors
+    /// See ```PollError``` for what each error means and how to recover from it
+    pub async fn poll_async(&self) -> Result<(), PollError> {
+        self.poll_event_async().await?;
+        Ok(())
+    }
+
+    /// Async version of ```poll_event```, see ```poll_async```
+    ///
+    /// # Errors
+    /// See ```PollError``` for what each error means and how to recover from it
+    pub async fn poll_event_async(&self) -> Result<RouteEvent, PollError> {
+        let mut events = self.bridge().events.lock().await;
+        if events.is_none() {
+            let backend = self
+                .backend_events()
+                .ok_or(PollError::Disconnected)?
+                .clone();
+            let closed = self.closed().clone();
+            let (sender, receiver) = mpsc::unbounded_channel();
+            self.thread_options()
+                .spawn("async", move || loop {
+                    // ends when backend goes away, manager is shut down or dropped
+                    crossbeam_channel::select! {
+                        recv(backend) -> event => {
+                            let Ok(event) = event else { return };
+                            if sender.send(event).is_err() {
+                                return;
+                            }
+                        }
+                        recv(closed) -> _ => return,
+                    }
+                })
+                .map_err(|e| PollError::Internal(e.to_string()))?;
+            *events = Some(receiver);
+        }
+        let timed = match events.as_mut() {
+            Some(events) => events.recv().await.ok_or(PollError::Disconnected)?,
+            None => return Err(PollError::Disconnected),
+        };
+        drop(events);
+        Ok(self.handle(timed)?.event)
+    }
+
+    /// Subscribe routing table change event with a dedicated buffer that is received with
+    /// `.await`
+    ///
+    /// # Errors
+    /// When try to lock Mutex and it return an error
+    pub fn subscribe_async(&self) -> io::Result<AsyncSubscription> {
+        let notify = Arc::new(Notify::new());
+        let notifier = Notifier(notify.clone());
+        let (subscriber, subscription) = self.new_subscriber();
+        self.push_subscriber(subscriber.notify(Box::new(move || notifier.0.notify_one())))?;
+        Ok(AsyncSubscription {
+            subscription,
+            notify,
+        })
+    }
+}
+
+/// Wakes receiver when an event was buffered and when manager drops its side
+struct Notifier(Arc<Notify>);
+
+impl Drop for Notifier {
+    fn drop(&mut self) {
+        self.0.notify_one();
+    }
+}
+
+/// Route event subscription received with `.await`, created by
+/// ```RouteManager::subscribe_async```
+pub struct AsyncSubscription {
+    subscription: Subscription,
+    notify: Arc<Notify>,
+}
+
+impl AsyncSubscription {
+    /// Wait until next event arrives, `None` when manager was dropped
+    pub async fn recv(&self) -> Option<RouteEvent> {
+        loop {
+            match self.subscription.try_recv() {
+                Ok(event) => return Some(event),
+                Err(TryRecvError::Disconnected) => return None,
+                Err(TryRecvError::Empty) => self.notify.notified().await,
+            }
+        }
+    }
+
+    /// Receive an event if one is buffered
+    ///
+    /// # Errors
+    /// When no event is buffered or manager was dropped
+    pub fn try_recv(&self) -> Result<RouteEvent, TryRecvError> {
+        self.subscription.try_recv()
+    }
+}
+
+#[cfg(test)]
+pub mod test_tokio {
+    use crate::{mock::MockOperator, Route, RouteEvent, RouteManager};
+
+    #[test]
+    fn test_poll_async() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let manager = RouteManager::with_backend(MockOperator::new(vec![])).unwrap();
+        let subscription = manager.subscribe_async().unwrap();
+        runtime.block_on(async {
+            let route = Route::new("10.0.0.0".parse().unwrap(), 8);
+            manager.add_route(&route).unwrap();
+            let event = manager.poll_event_async().await.unwrap();
+            assert!(matches!(event, RouteEvent::Add(_)));
+            assert_eq!(Some(event), subscription.recv().await);
+        });
+        drop(manager);
+        assert_eq!(None, runtime.block_on(subscription.recv()));
+    }
+}
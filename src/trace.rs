@@ -0,0 +1,322 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{fmt::Display, io, net::IpAddr};
+
+use crate::{Family, Prefix, Route};
+
+/// Lookups deeper than this are reported as a routing loop
+const MAX_LOOKUPS: usize = 16;
+
+/// One lookup of a [`Trace`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceStep {
+    /// Address looked up
+    pub target: IpAddr,
+    /// Longest prefix match for target, lowest metric wins a tie
+    pub route: Route,
+    /// Address packets are handed to, target itself when route is on-link
+    pub next_hop: IpAddr,
+    /// Whether next hop is directly reachable on route's interface,
+    /// otherwise next hop needs another lookup
+    pub on_link: bool,
+}
+
+/// Result of walking the routing table for a destination, created by ```RouteManager::trace```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace {
+    pub destination: IpAddr,
+    /// Lookups in order, the last one is on-link unless destination is unreachable
+    pub steps: Vec<TraceStep>,
+    /// Interface packets leave through
+    pub ifindex: Option<u32>,
+    /// Source address selected on the outgoing interface, if backend knows its addresses
+    pub source: Option<IpAddr>,
+}
+
+impl Trace {
+    /// Whether lookups ended on an on-link next hop
+    pub fn is_reachable(&self) -> bool {
+        self.steps.last().map(|s| s.on_link).unwrap_or(false)
+    }
+
+    /// Address packets to destination are finally handed to
+    pub fn next_hop(&self) -> Option<IpAddr> {
+        self.steps.last().filter(|s| s.on_link).map(|s| s.next_hop)
+    }
+}
+
+impl Display for Trace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.destination)?;
+        for step in &self.steps {
+            write!(f, " -> {} via {}", step.route.cidr(), step.next_hop)?;
+        }
+        if !self.is_reachable() {
+            return write!(f, " unreachable");
+        }
+        if let Some(ifindex) = self.ifindex {
+            write!(f, " dev {ifindex}")?;
+        }
+        if let Some(source) = self.source {
+            write!(f, " src {source}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Change of the route selected for a destination, delivered by
+/// ```RouteManager::watch_best_route``` and ```RouteManager::watch_default_route```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BestRouteChange {
+    pub destination: IpAddr,
+    pub previous: Option<Route>,
+    pub current: Option<Route>,
+}
+
+/// Manager side of a ```RouteManager::watch_best_route``` subscription
+pub(crate) struct BestRouteWatcher {
+    pub(crate) destination: IpAddr,
+    // only consider default routes of destination's family
+    default_only: bool,
+    selected: Option<Route>,
+    sender: crossbeam_channel::Sender<BestRouteChange>,
+}
+
+impl BestRouteWatcher {
+    pub(crate) fn new(
+        destination: IpAddr,
+        selected: Option<Route>,
+        sender: crossbeam_channel::Sender<BestRouteChange>,
+    ) -> Self {
+        Self {
+            destination,
+            default_only: false,
+            selected,
+            sender,
+        }
+    }
+
+    /// Watch the effective default route of family instead of a destination
+    pub(crate) fn default_route(
+        family: Family,
+        selected: Option<Route>,
+        sender: crossbeam_channel::Sender<BestRouteChange>,
+    ) -> Self {
+        Self {
+            destination: Prefix::default_route(family).network(),
+            default_only: true,
+            selected,
+            sender,
+        }
+    }
+
+    /// Recompute selection from routes and notify when it changed, return false when
+    /// receiver was dropped. Default routes are ranked by `effective` metric
+    pub(crate) fn update(&mut self, routes: &[Route], effective: impl Fn(&Route) -> u32) -> bool {
+        let current = if self.default_only {
+            lookup_default(routes, Family::of(&self.destination), effective).cloned()
+        } else {
+            lookup(routes, self.destination).cloned()
+        };
+        if current == self.selected {
+            return true;
+        }
+        let change = BestRouteChange {
+            destination: self.destination,
+            previous: std::mem::replace(&mut self.selected, current.clone()),
+            current,
+        };
+        self.sender.send(change).is_ok()
+    }
+}
+
+/// Change of the route and interface system selects for a destination, delivered by
+/// ```RouteManager::watch_resolved_route```, `None` while destination is unreachable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedRouteChange {
+    pub destination: IpAddr,
+    pub previous: Option<Route>,
+    pub current: Option<Route>,
+}
+
+/// Manager side of a ```RouteManager::watch_resolved_route``` subscription
+pub(crate) struct ResolvedRouteWatcher {
+    pub(crate) destination: IpAddr,
+    selected: Option<Route>,
+    sender: crossbeam_channel::Sender<ResolvedRouteChange>,
+}
+
+impl ResolvedRouteWatcher {
+    pub(crate) fn new(
+        destination: IpAddr,
+        selected: Option<Route>,
+        sender: crossbeam_channel::Sender<ResolvedRouteChange>,
+    ) -> Self {
+        Self {
+            destination,
+            selected,
+            sender,
+        }
+    }
+
+    /// Notify when the selected route or its interface changed, return false when receiver
+    /// was dropped
+    pub(crate) fn update(&mut self, current: Option<Route>) -> bool {
+        if current == self.selected {
+            return true;
+        }
+        let change = ResolvedRouteChange {
+            destination: self.destination,
+            previous: std::mem::replace(&mut self.selected, current.clone()),
+            current,
+        };
+        self.sender.send(change).is_ok()
+    }
+}
+
+/// Longest prefix match for addr, lowest metric wins a tie
+pub(crate) fn lookup(routes: &[Route], addr: IpAddr) -> Option<&Route> {
+    routes
+        .iter()
+        .filter(|r| r.contains(addr))
+        .min_by_key(|r| (std::cmp::Reverse(r.prefix), r.metric.unwrap_or(0)))
+}
+
+/// Default route of family with the lowest effective metric, as computed by `effective`
+pub(crate) fn lookup_default(
+    routes: &[Route],
+    family: Family,
+    effective: impl Fn(&Route) -> u32,
+) -> Option<&Route> {
+    routes
+        .iter()
+        .filter(|r| r.prefix == 0 && Family::of(&r.destination) == family)
+        .min_by_key(|r| effective(r))
+}
+
+/// Walk routes from destination until an on-link next hop is found
+pub(crate) fn trace(routes: &[Route], destination: IpAddr) -> io::Result<Trace> {
+    let mut steps: Vec<TraceStep> = Vec::new();
+    let mut target = destination;
+    while let Some(route) = lookup(routes, target) {
+        if steps.len() == MAX_LOOKUPS || steps.iter().any(|s| s.target == target) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("routing loop while tracing {destination}"),
+            ));
+        }
+        let on_link = route.gateway.is_unspecified();
+        let next_hop = if on_link { target } else { route.gateway };
+        steps.push(TraceStep {
+            target,
+            route: route.clone(),
+            next_hop,
+            on_link,
+        });
+        if on_link {
+            break;
+        }
+        target = next_hop;
+    }
+    let ifindex = steps
+        .last()
+        .filter(|s| s.on_link)
+        .and_then(|s| s.route.ifindex);
+    Ok(Trace {
+        destination,
+        steps,
+        ifindex,
+        source: None,
+    })
+}
+
+/// Pick source address of destination's family, non link-local addresses are preferred
+/// unless destination itself is link-local
+pub(crate) fn select_source(addresses: &[IpAddr], destination: IpAddr) -> Option<IpAddr> {
+    let link_local = |addr: &IpAddr| match addr {
+        IpAddr::V4(v4) => v4.is_link_local(),
+        IpAddr::V6(v6) => (v6.segments()[0] & 0xffc0) == 0xfe80,
+    };
+    let candidates = addresses
+        .iter()
+        .filter(|a| a.is_ipv4() == destination.is_ipv4());
+    candidates
+        .clone()
+        .find(|a| link_local(a) == link_local(&destination))
+        .or_else(|| candidates.clone().next())
+        .copied()
+}
+
+#[cfg(test)]
+pub mod test_trace {
+    use super::{select_source, trace};
+    use crate::Route;
+
+    #[test]
+    fn test_recursive() {
+        let routes = vec![
+            Route::new("0.0.0.0".parse().unwrap(), 0)
+                .gateway("192.168.1.1".parse().unwrap())
+                .ifindex(1),
+            Route::new("192.168.1.0".parse().unwrap(), 24).ifindex(1),
+            Route::new("10.0.0.0".parse().unwrap(), 8)
+                .gateway("172.16.0.1".parse().unwrap())
+                .ifindex(2),
+            Route::new("172.16.0.0".parse().unwrap(), 16)
+                .gateway("192.168.1.254".parse().unwrap())
+                .ifindex(1),
+        ];
+        let result = trace(&routes, "10.2.3.4".parse().unwrap()).unwrap();
+        assert_eq!(3, result.steps.len());
+        assert!(result.is_reachable());
+        assert_eq!(Some("192.168.1.254".parse().unwrap()), result.next_hop());
+        assert_eq!(Some(1), result.ifindex);
+        assert_eq!(
+            "10.2.3.4 -> 10.0.0.0/8 via 172.16.0.1 -> 172.16.0.0/16 via 192.168.1.254 \
+             -> 192.168.1.0/24 via 192.168.1.254 dev 1",
+            result.to_string()
+        );
+
+        let result = trace(&routes[2..], "10.2.3.4".parse().unwrap()).unwrap();
+        assert!(!result.is_reachable());
+        assert_eq!(None, result.ifindex);
+
+        let looping = vec![Route::new("10.0.0.0".parse().unwrap(), 8)
+            .gateway("10.0.0.1".parse().unwrap())
+            .ifindex(1)];
+        assert!(trace(&looping, "10.2.3.4".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_select_source() {
+        let addresses = vec![
+            "fe80::1".parse().unwrap(),
+            "192.168.1.10".parse().unwrap(),
+            "2001:db8::10".parse().unwrap(),
+        ];
+        assert_eq!(
+            Some("2001:db8::10".parse().unwrap()),
+            select_source(&addresses, "2001:db8:1::1".parse().unwrap())
+        );
+        assert_eq!(
+            Some("192.168.1.10".parse().unwrap()),
+            select_source(&addresses, "8.8.8.8".parse().unwrap())
+        );
+    }
+}
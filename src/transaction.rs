@@ -0,0 +1,201 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io;
+
+use crate::{operator::DefaultOperator, Route, RouteManager, SystemRouteOperate};
+
+enum Operation {
+    Add(Route),
+    Delete(Route),
+    Update(Route),
+}
+
+/// Applied operation and how to take it back
+enum Undo {
+    /// Route was added, delete it
+    Delete(Route),
+    /// Route was deleted, add it back as it was
+    Restore(Route),
+    /// Route was updated, put previous values back
+    Update(Route),
+}
+
+/// Queue of route changes created by ```RouteManager::transaction```, applied all together by
+/// ```RouteTransaction::commit```
+///
+/// Nothing is sent to system before commit, dropping the transaction discards the queue
+pub struct RouteTransaction<'a, O: SystemRouteOperate = DefaultOperator> {
+    manager: &'a RouteManager<O>,
+    operations: Vec<Operation>,
+}
+
+impl<'a, O: SystemRouteOperate> RouteTransaction<'a, O> {
+    /// Queue adding `route`
+    pub fn add_route(mut self, route: Route) -> Self {
+        self.operations.push(Operation::Add(route));
+        self
+    }
+
+    /// Queue deleting `route`
+    pub fn delete_route(mut self, route: Route) -> Self {
+        self.operations.push(Operation::Delete(route));
+        self
+    }
+
+    /// Queue updating the entry `route` identifies in place, see ```RouteManager::update_route```
+    pub fn update_route(mut self, route: Route) -> Self {
+        self.operations.push(Operation::Update(route));
+        self
+    }
+
+    /// Number of queued operations
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// Apply queued operations in order, when one fails the ones already applied are reverted
+    /// in reverse order and the table is left as it was before commit
+    ///
+    /// # Errors
+    /// The error of the failing operation, with its kind kept. When reverting fails too, the
+    /// message also tells what could not be reverted
+    pub fn commit(self) -> io::Result<()> {
+        let mut applied = Vec::with_capacity(self.operations.len());
+        for operation in &self.operations {
+            match self.apply(operation) {
+                Ok(undo) => applied.push(undo),
+                Err(e) => {
+                    return match self.rollback(applied) {
+                        Ok(()) => Err(e),
+                        Err(rollback) => Err(io::Error::new(
+                            e.kind(),
+                            format!("{}, rollback failed: {}", e, rollback),
+                        )),
+                    };
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn apply(&self, operation: &Operation) -> io::Result<Undo> {
+        let manager = self.manager;
+        match operation {
+            Operation::Add(route) => manager.install(route).map(Undo::Delete),
+            Operation::Delete(route) => {
+                let existing = manager.backend().read_route(route)?;
+                manager.remove(&existing)?;
+                Ok(Undo::Restore(existing))
+            }
+            Operation::Update(route) => {
+                let previous = manager.backend().read_route(route)?;
+                manager.update_route(route)?;
+                Ok(Undo::Update(previous))
+            }
+        }
+    }
+
+    fn rollback(&self, applied: Vec<Undo>) -> io::Result<()> {
+        let manager = self.manager;
+        let mut result = Ok(());
+        for undo in applied.into_iter().rev() {
+            let reverted = match undo {
+                Undo::Delete(route) => manager.remove(&route),
+                // deleted route may not belong to manager, restore it without taking ownership
+                Undo::Restore(route) => manager.restore(&route),
+                Undo::Update(route) => manager.update_route(&route),
+            };
+            result = result.and(reverted);
+        }
+        result
+    }
+}
+
+impl<O: SystemRouteOperate> RouteManager<O> {
+    /// Start a transaction: queue adds, deletes and updates, then apply them with
+    /// ```RouteTransaction::commit```, which reverts everything applied so far when one fails
+    ///
+    /// ```rust no_run
+    /// use winroute::*;
+    ///
+    /// let manager = RouteManager::new().unwrap();
+    /// let gateway = "10.8.0.1".parse().unwrap();
+    /// manager
+    ///     .transaction()
+    ///     .add_route(Route::new("0.0.0.0".parse().unwrap(), 1).gateway(gateway))
+    ///     .add_route(Route::new("128.0.0.0".parse().unwrap(), 1).gateway(gateway))
+    ///     .delete_route(Route::new("10.0.0.0".parse().unwrap(), 8).ifindex(12))
+    ///     .commit()
+    ///     .unwrap();
+    /// ```
+    pub fn transaction(&self) -> RouteTransaction<'_, O> {
+        RouteTransaction {
+            manager: self,
+            operations: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test_transaction {
+    use crate::{
+        mock::{route, MockOperator},
+        RouteManager, SystemRouteOperate,
+    };
+
+    #[test]
+    fn test_commit() {
+        let existing = route("10.0.0.0", 8, 10);
+        let manager =
+            RouteManager::with_backend(MockOperator::new(vec![existing.clone()])).unwrap();
+        manager
+            .transaction()
+            .add_route(route("172.16.0.0", 12, 1))
+            .update_route(existing.clone().metric(5))
+            .commit()
+            .unwrap();
+        let table = manager.backend().read_all_routes().unwrap();
+        assert_eq!(vec![existing.metric(5), route("172.16.0.0", 12, 1)], table);
+    }
+
+    #[test]
+    fn test_rollback() {
+        let existing = route("10.0.0.0", 8, 10);
+        let other = route("10.1.0.0", 16, 10);
+        let manager =
+            RouteManager::with_backend(MockOperator::new(vec![existing.clone(), other.clone()]))
+                .unwrap();
+        let err = manager
+            .transaction()
+            .add_route(route("172.16.0.0", 12, 1))
+            .delete_route(other.clone())
+            .update_route(existing.clone().metric(5))
+            .add_route(route("172.16.0.0", 12, 1))
+            .commit()
+            .unwrap_err();
+        assert_eq!(std::io::ErrorKind::AlreadyExists, err.kind());
+        let mut table = manager.backend().read_all_routes().unwrap();
+        table.sort_by_key(|r| r.destination);
+        assert_eq!(vec![existing, other], table);
+    }
+}
@@ -0,0 +1,291 @@
+/*
+ * Licensed to the Apache Software Foundation (ASF) under one
+ * or more contributor license agreements.  See the NOTICE file
+ * distributed with this work for additional information
+ * regarding copyright ownership.  The ASF licenses this file
+ * to you under the Apache License, Version 2.0 (the
+ * "License"); you may not use this file except in compliance
+ * with the License.  You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::IpAddr;
+
+use crate::{Family, Prefix, Route, RouteEvent};
+
+/// Binary trie of routes keyed by destination prefix, one per family, answering longest
+/// prefix match in at most 32 or 128 steps regardless of table size
+///
+/// Mirrors the cached table: every event applied to the table must be applied here as well.
+/// Routes whose prefix is longer than their family allows are ignored, walks never go below
+/// 32 bits for IPv4 and 128 bits for IPv6
+#[derive(Debug, Default)]
+pub(crate) struct RouteTrie {
+    v4: Node,
+    v6: Node,
+}
+
+#[derive(Debug, Default)]
+struct Node {
+    /// Routes whose prefix ends at this node, in the order they were cached
+    routes: Vec<Route>,
+    children: [Option<Box<Node>>; 2],
+}
+
+impl Node {
+    fn is_empty(&self) -> bool {
+        self.routes.is_empty() && self.children.iter().all(Option::is_none)
+    }
+
+    /// Route of this node containing addr with the lowest metric
+    fn best(&self, addr: IpAddr) -> Option<&Route> {
+        self.routes
+            .iter()
+            .filter(|r| r.contains(addr))
+            .min_by_key(|r| r.metric.unwrap_or(0))
+    }
+
+    /// Remove the first route of the node at `depth` of `key` matching `found`, pruning nodes
+    /// left empty, return the removed route
+    fn remove(&mut self, key: u128, depth: u8, found: &dyn Fn(&Route) -> bool) -> Option<Route> {
+        if depth == 0 {
+            let index = self.routes.iter().position(found)?;
+            return Some(self.routes.remove(index));
+        }
+        let branch = bit(key, 0);
+        let child = self.children[branch].as_mut()?;
+        let removed = child.remove(key << 1, depth - 1, found);
+        if child.is_empty() {
+            self.children[branch] = None;
+        }
+        removed
+    }
+}
+
+/// Address bits aligned to the most significant bit
+fn key(addr: &IpAddr) -> u128 {
+    match addr {
+        IpAddr::V4(v4) => (u32::from(*v4) as u128) << 96,
+        IpAddr::V6(v6) => u128::from(*v6),
+    }
+}
+
+fn bit(key: u128, index: u8) -> usize {
+    ((key >> (127 - index)) & 1) as usize
+}
+
+impl RouteTrie {
+    pub(crate) fn new(routes: &[Route]) -> Self {
+        let mut trie = Self::default();
+        for route in routes {
+            trie.insert(route.clone());
+        }
+        trie
+    }
+
+    fn root(&self, addr: &IpAddr) -> &Node {
+        match addr {
+            IpAddr::V4(_) => &self.v4,
+            IpAddr::V6(_) => &self.v6,
+        }
+    }
+
+    fn root_mut(&mut self, addr: &IpAddr) -> &mut Node {
+        match addr {
+            IpAddr::V4(_) => &mut self.v4,
+            IpAddr::V6(_) => &mut self.v6,
+        }
+    }
+
+    fn insert(&mut self, route: Route) {
+        if !route.has_valid_prefix() {
+            return;
+        }
+        let key = key(&route.destination);
+        let mut node = self.root_mut(&route.destination);
+        for index in 0..route.prefix {
+            node = node.children[bit(key, index)].get_or_insert_with(Box::default);
+        }
+        node.routes.push(route);
+    }
+
+    fn remove(&mut self, route: &Route, found: &dyn Fn(&Route) -> bool) -> Option<Route> {
+        if !route.has_valid_prefix() {
+            return None;
+        }
+        let key = key(&route.destination);
+        self.root_mut(&route.destination)
+            .remove(key, route.prefix, found)
+    }
+
+    /// Apply event the way the cached table applies it
+    pub(crate) fn apply(&mut self, event: &RouteEvent) {
+        match event {
+            RouteEvent::Add(route) => {
+                let entry = |v: &Route| {
+                    (v.destination, v.gateway, v.ifindex)
+                        == (route.destination, route.gateway, route.ifindex)
+                };
+                while self.remove(route, &entry).is_some() {}
+                self.insert(route.clone());
+            }
+            RouteEvent::Delete(route) => {
+                self.remove(route, &|v| v == route);
+            }
+            RouteEvent::Change(route) => {
+                if self
+                    .remove(route, &|v| v.destination == route.destination)
+                    .is_some()
+                {
+                    self.insert(route.clone());
+                }
+            }
+            RouteEvent::Initial => {}
+        }
+    }
+
+    /// Routes whose network equals or contains prefix, shortest prefix first
+    pub(crate) fn covering(&self, prefix: &Prefix) -> Vec<&Route> {
+        let key = key(&prefix.network());
+        let mut node = self.root(&prefix.network());
+        let mut found: Vec<&Route> = Vec::new();
+        for index in 0..=prefix.prefix_len() {
+            found.extend(node.routes.iter().filter(|r| prefix.covered_by(r)));
+            if index == prefix.prefix_len() {
+                break;
+            }
+            match node.children[bit(key, index)].as_deref() {
+                Some(child) => node = child,
+                None => break,
+            }
+        }
+        found
+    }
+
+    /// Routes whose network equals or lies within prefix
+    pub(crate) fn within(&self, prefix: &Prefix) -> Vec<&Route> {
+        let key = key(&prefix.network());
+        let mut node = self.root(&prefix.network());
+        for index in 0..prefix.prefix_len() {
+            match node.children[bit(key, index)].as_deref() {
+                Some(child) => node = child,
+                None => return Vec::new(),
+            }
+        }
+        let mut found = Vec::new();
+        let mut pending = vec![node];
+        while let Some(node) = pending.pop() {
+            found.extend(node.routes.iter());
+            pending.extend(node.children.iter().flatten().map(|child| &**child));
+        }
+        found
+    }
+
+    /// Longest prefix match for addr, lowest metric wins a tie, same as a scan of the table
+    pub(crate) fn lookup(&self, addr: IpAddr) -> Option<&Route> {
+        let key = key(&addr);
+        let mut node = self.root(&addr);
+        let mut best = None;
+        for index in 0..Family::of(&addr).max_prefix_len() {
+            best = node.best(addr).or(best);
+            match node.children[bit(key, index)].as_deref() {
+                Some(child) => node = child,
+                None => return best,
+            }
+        }
+        node.best(addr).or(best)
+    }
+}
+
+#[cfg(test)]
+pub mod test_trie {
+    use super::RouteTrie;
+    use crate::{mock::route, trace, Prefix, RouteEvent};
+
+    #[test]
+    fn test_lookup_matches_scan() {
+        let routes = vec![
+            route("0.0.0.0", 0, 50),
+            route("10.0.0.0", 8, 10),
+            route("10.1.0.0", 16, 30),
+            route("10.1.0.0", 16, 20).ifindex(2),
+            route("10.1.2.3", 32, 5),
+            route("::", 0, 5),
+            route("fd00::", 8, 5),
+        ];
+        let trie = RouteTrie::new(&routes);
+        for addr in [
+            "10.1.2.3",
+            "10.1.2.4",
+            "10.2.0.1",
+            "8.8.8.8",
+            "fd00::1",
+            "2001:db8::1",
+        ] {
+            let addr = addr.parse().unwrap();
+            assert_eq!(trace::lookup(&routes, addr), trie.lookup(addr), "{addr}");
+        }
+    }
+
+    #[test]
+    fn test_overlaps() {
+        let routes = vec![
+            route("0.0.0.0", 0, 50),
+            route("10.0.0.0", 8, 10),
+            route("10.1.0.0", 16, 30),
+            route("10.1.2.0", 24, 5),
+            route("10.2.0.0", 16, 5),
+            route("fd00::", 8, 5),
+        ];
+        let trie = RouteTrie::new(&routes);
+        let prefix = Prefix::new("10.1.0.0".parse().unwrap(), 16).unwrap();
+        assert_eq!(
+            vec![&routes[0], &routes[1], &routes[2]],
+            trie.covering(&prefix)
+        );
+        let mut within = trie.within(&prefix);
+        within.sort_by_key(|r| r.prefix);
+        assert_eq!(vec![&routes[2], &routes[3]], within);
+        let prefix = Prefix::new("10.1.2.128".parse().unwrap(), 25).unwrap();
+        assert_eq!(4, trie.covering(&prefix).len());
+        assert!(trie.within(&prefix).is_empty());
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut trie = RouteTrie::default();
+        let addr = "10.1.2.3".parse().unwrap();
+        trie.apply(&RouteEvent::Add(route("10.0.0.0", 8, 10)));
+        trie.apply(&RouteEvent::Add(route("10.1.0.0", 16, 10)));
+        trie.apply(&RouteEvent::Add(route("10.1.0.0", 16, 10)));
+        assert_eq!(Some(&route("10.1.0.0", 16, 10)), trie.lookup(addr));
+
+        trie.apply(&RouteEvent::Change(route("10.1.0.0", 16, 3)));
+        assert_eq!(Some(&route("10.1.0.0", 16, 3)), trie.lookup(addr));
+
+        trie.apply(&RouteEvent::Delete(route("10.1.0.0", 16, 3)));
+        assert_eq!(Some(&route("10.0.0.0", 8, 10)), trie.lookup(addr));
+        trie.apply(&RouteEvent::Delete(route("10.0.0.0", 8, 10)));
+        assert_eq!(None, trie.lookup(addr));
+        assert!(trie.v4.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_prefix() {
+        let mut trie = RouteTrie::new(&[route("10.0.0.0", 40, 1), route("fd00::", 200, 1)]);
+        trie.apply(&RouteEvent::Add(route("10.0.0.0", 33, 1)));
+        trie.apply(&RouteEvent::Delete(route("10.0.0.0", 255, 1)));
+        assert!(trie.v4.is_empty() && trie.v6.is_empty());
+
+        trie.apply(&RouteEvent::Add(route("10.1.2.3", 32, 1)));
+        let addr = "10.1.2.3".parse().unwrap();
+        assert_eq!(Some(&route("10.1.2.3", 32, 1)), trie.lookup(addr));
+    }
+}
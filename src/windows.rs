@@ -16,122 +16,636 @@
  * limitations under the License.
  */
 
-use std::{io, net::IpAddr};
+use std::{
+    collections::HashMap,
+    io,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
 
-use crossbeam_channel::Sender;
+use crossbeam_channel::{Receiver, Sender};
 use winapi::{
     shared::{
+        guiddef::GUID,
+        ifdef::IfOperStatusUp,
+        ipifcons::{IF_TYPE_PPP, IF_TYPE_PROP_VIRTUAL, IF_TYPE_TUNNEL},
         netioapi::*,
-        nldef::MIB_IPPROTO_NETMGMT,
+        nldef::{
+            NlnsReachable, NlnsStale, RouterDiscoveryDhcp, RouterDiscoveryDisabled,
+            RouterDiscoveryEnabled, MIB_IPPROTO_NETMGMT, NL_NEIGHBOR_STATE,
+        },
         ntdef::{BOOLEAN, HANDLE, PVOID},
         ws2def::{AF_INET, AF_INET6, AF_UNSPEC, PSOCKADDR, SOCKADDR_IN},
-        ws2ipdef::SOCKADDR_IN6,
+        ws2ipdef::{SOCKADDR_IN6, SOCKADDR_INET},
     },
-    um::iphlpapi::GetBestInterfaceEx,
+    um::{
+        iphlpapi::GetBestInterfaceEx,
+        processthreadsapi::{GetCurrentThread, SetThreadPriority},
+        winbase::{
+            THREAD_PRIORITY_ABOVE_NORMAL, THREAD_PRIORITY_BELOW_NORMAL, THREAD_PRIORITY_HIGHEST,
+            THREAD_PRIORITY_LOWEST, THREAD_PRIORITY_NORMAL,
+        },
+    },
+};
+
+use crate::{
+    reader::read_consistent, subscription::PolicySender, Adapter, AdapterKind, CancelToken,
+    ConnectionEvent, Family, InterfaceConfig, InterfaceInfo, MacAddr, OverflowPolicy, Prefix,
+    Route, RouteEvent, RouterDiscovery, SystemRouteOperate, ThreadPriority, TimedEvent, Timestamp,
 };
 
-use crate::{manager::SystemRouteOperate, Route, RouteEvent};
+/// Backend based on Windows IP Helper API
+pub struct WindowsOperator {
+    notify_handle: Mutex<Option<HANDLE>>,
+    // boxed so that its address handed to NotifyRouteChange2 stays valid when operator moves
+    sender: Box<PolicySender<TimedEvent>>,
+    initial_notification: bool,
+    receiver: Receiver<TimedEvent>,
+    connection_handle: Mutex<Option<HANDLE>>,
+    // boxed for the same reason, handed to NotifyIpInterfaceChange
+    connections: Box<ConnectionWatch>,
+}
+
+/// Connected dial-up, RAS and VPN adapters, and subscribers of their changes
+#[derive(Default)]
+struct ConnectionWatch {
+    senders: Mutex<Vec<Sender<ConnectionEvent>>>,
+    connected: Mutex<HashMap<u32, Adapter>>,
+}
 
-pub(crate) struct WindowsOperator {
-    notify_handle: Option<HANDLE>,
-    sender: Sender<RouteEvent>,
+impl ConnectionWatch {
+    /// Compare adapter ifindex with known state and notify subscribers when it changed
+    fn update(&self, ifindex: u32) {
+        let current = read_adapter(ifindex);
+        let event = {
+            let Ok(mut connected) = self.connected.lock() else {
+                return;
+            };
+            match (connected.contains_key(&ifindex), current) {
+                (false, Some(adapter)) => {
+                    connected.insert(ifindex, adapter.clone());
+                    ConnectionEvent::Connected(adapter)
+                }
+                (true, None) => match connected.remove(&ifindex) {
+                    Some(adapter) => ConnectionEvent::Disconnected(adapter),
+                    None => return,
+                },
+                _ => return,
+            }
+        };
+        if let Ok(mut senders) = self.senders.lock() {
+            senders.retain(|s| s.send(event.clone()).is_ok());
+        }
+    }
 }
 
+// notify handles are only used to cancel the notification, sender is Sync
+unsafe impl Send for WindowsOperator {}
+
+unsafe impl Sync for WindowsOperator {}
+
 impl WindowsOperator {
+    /// Create a backend, route change notifications are registered by ```init```
+    pub fn new() -> Self {
+        Self::with_buffer(None, OverflowPolicy::Block)
+    }
+
+    /// Create a backend buffering at most `capacity` route change events, unbounded when it is
+    /// `None`, `policy` decides what happens when they are not polled in time
+    pub fn with_buffer(capacity: Option<usize>, policy: OverflowPolicy) -> Self {
+        let (sender, receiver) = PolicySender::channel(capacity, policy);
+        Self {
+            notify_handle: Mutex::new(None),
+            sender: Box::new(sender),
+            initial_notification: false,
+            receiver,
+            connection_handle: Mutex::new(None),
+            connections: Box::default(),
+        }
+    }
+
+    /// Request an initial notification, reported as ```RouteEvent::Initial```, when route
+    /// change listener is registered
+    pub fn initial_notification(mut self, initial: bool) -> Self {
+        self.initial_notification = initial;
+        self
+    }
+
+    fn register_connection_listener(&self) -> io::Result<()> {
+        let mut connection_handle = self
+            .connection_handle
+            .lock()
+            .map_err(|_| io::Error::other("Can not lock notify handle"))?;
+        if connection_handle.is_some() {
+            return Ok(());
+        }
+        // Adapters already connected are known, only changes are reported
+        let adapters = read_adapters()?;
+        if let Ok(mut connected) = self.connections.connected.lock() {
+            connected.extend(adapters.into_iter().map(|a| (a.ifindex, a)));
+        }
+        let mut handle = std::ptr::null_mut();
+        let ret = unsafe {
+            NotifyIpInterfaceChange(
+                AF_UNSPEC as u16,
+                Some(interface_callback),
+                &*self.connections as *const ConnectionWatch as PVOID,
+                BOOLEAN::from(false),
+                &mut handle,
+            )
+        };
+        if ret != 0 {
+            return Err(code_to_error(ret, "error notify interface change"));
+        }
+        *connection_handle = Some(handle);
+        Ok(())
+    }
+
     fn register_route_listener(&self) -> io::Result<()> {
-        if let Some(_) = self.notify_handle {
+        let mut notify_handle = self
+            .notify_handle
+            .lock()
+            .map_err(|_| io::Error::other("Can not lock notify handle"))?;
+        if notify_handle.is_some() {
             return Err(code_to_error(5010, "Already registered"));
-        } else {
-            let mut handle = std::ptr::null_mut();
-            let ret = unsafe {
-                NotifyRouteChange2(
-                    AF_UNSPEC as u16,
-                    Some(callback),
-                    std::mem::transmute(&self.sender),
-                    BOOLEAN::from(false),
-                    &mut handle,
-                )
-            };
-            if ret != 0 {
-                return Err(code_to_error(ret, "error notify route change"));
-            }
-            Ok(())
         }
+        let mut handle = std::ptr::null_mut();
+        let ret = unsafe {
+            NotifyRouteChange2(
+                AF_UNSPEC as u16,
+                Some(callback),
+                &*self.sender as *const PolicySender<TimedEvent> as PVOID,
+                BOOLEAN::from(self.initial_notification),
+                &mut handle,
+            )
+        };
+        if ret != 0 {
+            return Err(code_to_error(ret, "error notify route change"));
+        }
+        *notify_handle = Some(handle);
+        Ok(())
+    }
+}
+
+impl Default for WindowsOperator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Neighbor table row keyed by addr and ifindex
+fn neighbor_row(addr: IpAddr, ifindex: u32) -> MIB_IPNET_ROW2 {
+    let mut row: MIB_IPNET_ROW2 = unsafe { std::mem::zeroed() };
+    row.InterfaceIndex = ifindex;
+    match addr {
+        IpAddr::V4(addr) => unsafe {
+            *row.Address.si_family_mut() = AF_INET as u16;
+            *row.Address.Ipv4_mut().sin_addr.S_un.S_addr_mut() = u32::from_ne_bytes(addr.octets());
+        },
+        IpAddr::V6(addr) => unsafe {
+            *row.Address.si_family_mut() = AF_INET6 as u16;
+            *row.Address.Ipv6_mut().sin6_addr.u.Byte_mut() = addr.octets();
+        },
     }
+    row
+}
+
+/// Link layer address of a resolved neighbor row, `None` when neighbor did not answer or
+/// its entry is below min_state
+fn neighbor_mac(
+    err: u32,
+    row: &MIB_IPNET_ROW2,
+    min_state: NL_NEIGHBOR_STATE,
+) -> io::Result<Option<MacAddr>> {
+    match err {
+        0 => {}
+        // ERROR_BAD_NET_NAME, neighbor did not answer
+        67 => return Ok(None),
+        _ => return Err(code_to_error(err, "error resolving neighbor")),
+    }
+    if row.State < min_state || row.PhysicalAddressLength != 6 {
+        return Ok(None);
+    }
+    let mut mac = [0u8; 6];
+    mac.copy_from_slice(&row.PhysicalAddress[..6]);
+    Ok(Some(MacAddr(mac)))
 }
 
 impl SystemRouteOperate for WindowsOperator {
-    fn add_route(&self, route: &Route) -> io::Result<()> {
+    fn add_route(&self, route: &Route) -> io::Result<Route> {
         // if not set interface index and luid, it will use default route's params
-        let row = if route.ifindex.is_none() && route.luid.is_none() {
+        let route = if route.ifindex.is_none() && route.luid.is_none() {
             let best_idx = find_best_interface(route.gateway)?;
-            let mut clone = route.clone();
-            clone.ifindex = Some(best_idx);
-            MIB_IPFORWARD_ROW2::from(&clone)
+            route.clone().ifindex(best_idx)
         } else {
-            MIB_IPFORWARD_ROW2::from(route)
+            route.clone()
         };
+        let row = MIB_IPFORWARD_ROW2::from(&route);
 
         let err = unsafe { CreateIpForwardEntry2(&row) };
         if err != 0 {
             return Err(code_to_error(err, "error creating entry"));
         }
+        Ok(route)
+    }
+
+    fn add_routes(&self, routes: &[Route]) -> Vec<io::Result<Route>> {
+        // initialized once, every row starts as a copy of it
+        let mut template: MIB_IPFORWARD_ROW2 = unsafe { std::mem::zeroed() };
+        unsafe { InitializeIpForwardEntry(&mut template) };
+        routes
+            .iter()
+            .map(|route| {
+                let route = if route.ifindex.is_none() && route.luid.is_none() {
+                    route.clone().ifindex(find_best_interface(route.gateway)?)
+                } else {
+                    route.clone()
+                };
+                let row = fill_row(template, &route);
+                let err = unsafe { CreateIpForwardEntry2(&row) };
+                if err != 0 {
+                    return Err(code_to_error(err, "error creating entry"));
+                }
+                Ok(route)
+            })
+            .collect()
+    }
+
+    fn update_route(&self, route: &Route) -> io::Result<()> {
+        // read current row first, so that properties Route doesn't carry are kept
+        let mut row = MIB_IPFORWARD_ROW2::from(route);
+        let err = unsafe { GetIpForwardEntry2(&mut row) };
+        if err != 0 {
+            return Err(code_to_error(err, "error reading entry"));
+        }
+        if let Some(metric) = route.metric {
+            row.Metric = metric;
+        }
+
+        let err = unsafe { SetIpForwardEntry2(&row) };
+        if err != 0 {
+            return Err(code_to_error(err, "error updating entry"));
+        }
         Ok(())
     }
 
-    fn delete_route(&self, route: &Route) -> io::Result<()> {
-        let row: MIB_IPFORWARD_ROW2 = MIB_IPFORWARD_ROW2::from(route);
+    fn best_interface(&self, addr: IpAddr) -> io::Result<u32> {
+        find_best_interface(addr)
+    }
 
-        let err = unsafe { DeleteIpForwardEntry2(&row) };
+    fn interface_metric(&self, family: Family, ifindex: u32) -> io::Result<u32> {
+        Ok(read_interface_row(family, ifindex)?.Metric)
+    }
+
+    fn interfaces(&self) -> io::Result<Vec<InterfaceInfo>> {
+        read_interfaces()
+    }
+
+    fn interface_link_speed(&self, ifindex: u32) -> io::Result<u64> {
+        let mut row: MIB_IF_ROW2 = unsafe { std::mem::zeroed() };
+        row.InterfaceIndex = ifindex;
+        let err = unsafe { GetIfEntry2(&mut row) };
         if err != 0 {
-            return Err(code_to_error(err, "error deleting entry"));
+            return Err(code_to_error(err, "error reading interface"));
+        }
+        Ok(row.TransmitLinkSpeed.max(row.ReceiveLinkSpeed))
+    }
+
+    fn interface_config(&self, family: Family, ifindex: u32) -> io::Result<InterfaceConfig> {
+        let row = read_interface_row(family, ifindex)?;
+        Ok(InterfaceConfig {
+            family,
+            ifindex,
+            metric: row.Metric,
+            use_automatic_metric: row.UseAutomaticMetric != 0,
+            mtu: row.NlMtu,
+            dad_transmits: row.DadTransmits,
+            router_discovery: match row.RouterDiscoveryBehavior {
+                n if n == RouterDiscoveryEnabled => RouterDiscovery::Enabled,
+                n if n == RouterDiscoveryDhcp => RouterDiscovery::Dhcp,
+                _ => RouterDiscovery::Disabled,
+            },
+            base_reachable_time: row.BaseReachableTime,
+            retransmit_time: row.RetransmitTime,
+            path_mtu_discovery_timeout: row.PathMtuDiscoveryTimeout,
+            use_neighbor_unreachability_detection: row.UseNeighborUnreachabilityDetection != 0,
+            forwarding_enabled: row.ForwardingEnabled != 0,
+            weak_host_send: row.WeakHostSend != 0,
+            weak_host_receive: row.WeakHostReceive != 0,
+            advertising_enabled: row.AdvertisingEnabled != 0,
+            advertise_default_route: row.AdvertiseDefaultRoute != 0,
+            min_router_advertisement_interval: row.MinRouterAdvertisementInterval,
+            max_router_advertisement_interval: row.MaxRouterAdvertisementInterval,
+            reachable_time: row.ReachableTime,
+            connected: row.Connected != 0,
+            supports_neighbor_discovery: row.SupportsNeighborDiscovery != 0,
+            supports_router_discovery: row.SupportsRouterDiscovery != 0,
+        })
+    }
+
+    fn set_interface_config(&self, config: &InterfaceConfig) -> io::Result<()> {
+        let mut row = read_interface_row(config.family, config.ifindex)?;
+        row.Metric = config.metric;
+        row.UseAutomaticMetric = BOOLEAN::from(config.use_automatic_metric);
+        row.NlMtu = config.mtu;
+        row.DadTransmits = config.dad_transmits;
+        row.RouterDiscoveryBehavior = match config.router_discovery {
+            RouterDiscovery::Disabled => RouterDiscoveryDisabled,
+            RouterDiscovery::Enabled => RouterDiscoveryEnabled,
+            RouterDiscovery::Dhcp => RouterDiscoveryDhcp,
+        };
+        row.BaseReachableTime = config.base_reachable_time;
+        row.RetransmitTime = config.retransmit_time;
+        row.PathMtuDiscoveryTimeout = config.path_mtu_discovery_timeout;
+        row.UseNeighborUnreachabilityDetection =
+            BOOLEAN::from(config.use_neighbor_unreachability_detection);
+        row.ForwardingEnabled = BOOLEAN::from(config.forwarding_enabled);
+        row.WeakHostSend = BOOLEAN::from(config.weak_host_send);
+        row.WeakHostReceive = BOOLEAN::from(config.weak_host_receive);
+        row.AdvertisingEnabled = BOOLEAN::from(config.advertising_enabled);
+        row.AdvertiseDefaultRoute = BOOLEAN::from(config.advertise_default_route);
+        row.MinRouterAdvertisementInterval = config.min_router_advertisement_interval;
+        row.MaxRouterAdvertisementInterval = config.max_router_advertisement_interval;
+        // SetIpInterfaceEntry rejects IPv4 rows with a site prefix length
+        if config.family == Family::V4 {
+            row.SitePrefixLength = 0;
+        }
+
+        let err = unsafe { SetIpInterfaceEntry(&mut row) };
+        if err != 0 {
+            return Err(code_to_error(err, "error writing interface"));
         }
         Ok(())
     }
 
-    fn read_all_routes(&self) -> io::Result<Vec<Route>> {
-        let mut ptable: PMIB_IPFORWARD_TABLE2 = std::ptr::null_mut();
+    fn best_route(&self, destination: IpAddr) -> io::Result<Route> {
+        let mut address: SOCKADDR_INET = unsafe { std::mem::zeroed() };
+        match destination {
+            IpAddr::V4(addr) => unsafe {
+                *address.si_family_mut() = AF_INET as u16;
+                *address.Ipv4_mut().sin_addr.S_un.S_addr_mut() = u32::from_ne_bytes(addr.octets());
+            },
+            IpAddr::V6(addr) => unsafe {
+                *address.si_family_mut() = AF_INET6 as u16;
+                *address.Ipv6_mut().sin6_addr.u.Byte_mut() = addr.octets();
+            },
+        }
+        let mut row: MIB_IPFORWARD_ROW2 = unsafe { std::mem::zeroed() };
+        let mut source: SOCKADDR_INET = unsafe { std::mem::zeroed() };
+        let err = unsafe {
+            GetBestRoute2(
+                std::ptr::null_mut(),
+                0,
+                std::ptr::null(),
+                &address,
+                0,
+                &mut row,
+                &mut source,
+            )
+        };
+        if err != 0 {
+            return Err(code_to_error(err, "error looking up best route"));
+        }
+        Ok(Route::from(&row))
+    }
+
+    fn resolve_neighbor(&self, addr: IpAddr, ifindex: u32) -> io::Result<Option<MacAddr>> {
+        let mut row = neighbor_row(addr, ifindex);
+
+        // Use the cached entry first, ResolveIpNetEntry2 blocks while the neighbor is probed
+        let mut err = unsafe { GetIpNetEntry2(&mut row) };
+        if err != 0 || row.State < NlnsStale {
+            err = unsafe { ResolveIpNetEntry2(&mut row, std::ptr::null()) };
+        }
+        neighbor_mac(err, &row, NlnsStale)
+    }
+
+    fn probe_neighbor(&self, addr: IpAddr, ifindex: u32) -> io::Result<Option<MacAddr>> {
+        let mut row = neighbor_row(addr, ifindex);
+
+        // A reachable entry was confirmed recently, anything below it is only a guess.
+        // ResolveIpNetEntry2 returns existing entries as is, drop it to force a solicitation
+        let err = unsafe { GetIpNetEntry2(&mut row) };
+        if err == 0 && row.State >= NlnsReachable {
+            return neighbor_mac(err, &row, NlnsReachable);
+        }
+        if err == 0 {
+            unsafe { DeleteIpNetEntry2(&row) };
+        }
+        let mut row = neighbor_row(addr, ifindex);
+        let err = unsafe { ResolveIpNetEntry2(&mut row, std::ptr::null()) };
+        neighbor_mac(err, &row, NlnsReachable)
+    }
+
+    fn interface_addresses(&self, ifindex: u32) -> io::Result<Vec<IpAddr>> {
+        let mut ptable: PMIB_UNICASTIPADDRESS_TABLE = std::ptr::null_mut();
 
-        let ret = unsafe { GetIpForwardTable2(AF_UNSPEC as u16, &mut ptable) };
+        let ret = unsafe { GetUnicastIpAddressTable(AF_UNSPEC as u16, &mut ptable) };
         if ret != 0 {
-            return Err(code_to_error(ret, "Error getting table"));
+            return Err(code_to_error(ret, "Error getting address table"));
         }
 
-        let prows = unsafe {
-            std::ptr::slice_from_raw_parts(
-                &(*ptable).Table as *const MIB_IPFORWARD_ROW2,
+        let rows = unsafe {
+            std::slice::from_raw_parts(
+                &(*ptable).Table as *const MIB_UNICASTIPADDRESS_ROW,
                 (*ptable).NumEntries as usize,
             )
         };
+        let res = rows
+            .iter()
+            .filter(|row| row.InterfaceIndex == ifindex)
+            .filter_map(|row| sockaddr_to_ip(&row.Address))
+            .collect();
+        unsafe { FreeMibTable(ptable as *mut _) };
+        Ok(res)
+    }
 
-        let entries = unsafe { (*ptable).NumEntries };
-        let res = (0..entries)
-            .map(|idx| unsafe { (*prows)[idx as usize] })
-            .filter_map(|row| Some(Route::from(&row)))
+    fn interface_prefixes(&self, ifindex: u32) -> io::Result<Vec<Prefix>> {
+        let mut ptable: PMIB_UNICASTIPADDRESS_TABLE = std::ptr::null_mut();
+
+        let ret = unsafe { GetUnicastIpAddressTable(AF_UNSPEC as u16, &mut ptable) };
+        if ret != 0 {
+            return Err(code_to_error(ret, "Error getting address table"));
+        }
+
+        let rows = unsafe {
+            std::slice::from_raw_parts(
+                &(*ptable).Table as *const MIB_UNICASTIPADDRESS_ROW,
+                (*ptable).NumEntries as usize,
+            )
+        };
+        let res = rows
+            .iter()
+            .filter(|row| row.InterfaceIndex == ifindex)
+            .filter_map(|row| {
+                let addr = sockaddr_to_ip(&row.Address)?;
+                Prefix::truncate(addr, row.OnLinkPrefixLength).ok()
+            })
             .collect();
         unsafe { FreeMibTable(ptable as *mut _) };
         Ok(res)
     }
 
+    fn wait_stable_addresses(
+        &self,
+        family: Option<Family>,
+        timeout: Duration,
+        cancel: &CancelToken,
+    ) -> io::Result<()> {
+        let family = match family {
+            None => AF_UNSPEC,
+            Some(Family::V4) => AF_INET,
+            Some(Family::V6) => AF_INET6,
+        };
+        // boxed so that context handed to the callback outlives a pending notification, it is
+        // only dropped once the callback finished or the notification is cancelled
+        let (sender, receiver) = crossbeam_channel::bounded::<()>(1);
+        let wait = Box::new(StableWait {
+            sender,
+            state: AtomicU8::new(STABLE_PENDING),
+        });
+        let mut ptable: PMIB_UNICASTIPADDRESS_TABLE = std::ptr::null_mut();
+        let mut handle = std::ptr::null_mut();
+        let ret = unsafe {
+            NotifyStableUnicastIpAddressTable(
+                family as u16,
+                &mut ptable,
+                Some(stable_callback),
+                &*wait as *const StableWait as PVOID,
+                &mut handle,
+            )
+        };
+        match ret {
+            0 => {
+                unsafe { FreeMibTable(ptable as *mut _) };
+                return Ok(());
+            }
+            // ERROR_IO_PENDING, callback runs once table is stable
+            997 => {}
+            _ => return Err(code_to_error(ret, "error waiting for stable address table")),
+        }
+        let stable = crossbeam_channel::select! {
+            recv(receiver) -> _ => true,
+            recv(cancel.receiver()) -> _ => false,
+            default(timeout) => false,
+        };
+        // whoever leaves pending owns the outcome: a fired callback already released handle,
+        // a cancelled one never signals and CancelMibChangeNotify2 waits for it to return
+        let cancelled = !stable
+            && wait
+                .state
+                .compare_exchange(
+                    STABLE_PENDING,
+                    STABLE_CANCELLED,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                )
+                .is_ok();
+        if cancelled {
+            unsafe { CancelMibChangeNotify2(handle) };
+        } else {
+            // callback signals before returning, wait until it no longer uses the context
+            while wait.state.load(Ordering::Acquire) != STABLE_DONE {
+                std::thread::yield_now();
+            }
+            return Ok(());
+        }
+        cancel.check()?;
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "unicast addresses are still tentative",
+        ))
+    }
+
+    fn connection_events(&self) -> io::Result<Receiver<ConnectionEvent>> {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        self.connections
+            .senders
+            .lock()
+            .map_err(|_| io::Error::other("Can not lock connection subscribers"))?
+            .push(sender);
+        self.register_connection_listener()?;
+        Ok(receiver)
+    }
+
+    fn read_route(&self, route: &Route) -> io::Result<Route> {
+        let mut row = MIB_IPFORWARD_ROW2::from(route);
+
+        let err = unsafe { GetIpForwardEntry2(&mut row) };
+        if err != 0 {
+            return Err(code_to_error(err, "error reading entry"));
+        }
+        Ok(Route::from(&row))
+    }
+
+    fn delete_route(&self, route: &Route) -> io::Result<()> {
+        let row: MIB_IPFORWARD_ROW2 = MIB_IPFORWARD_ROW2::from(route);
+
+        let err = unsafe { DeleteIpForwardEntry2(&row) };
+        if err != 0 {
+            return Err(code_to_error(err, "error deleting entry"));
+        }
+        Ok(())
+    }
+
+    fn read_all_routes(&self) -> io::Result<Vec<Route>> {
+        read_consistent(read_route_table)
+    }
+
     fn init(&self) -> io::Result<()> {
         self.register_route_listener()?;
         Ok(())
     }
 
-    fn new(sender: Sender<RouteEvent>) -> Self
-    where
-        Self: Sized,
-    {
-        Self {
-            notify_handle: None,
-            sender,
-        }
+    fn event_receiver(&self) -> Receiver<TimedEvent> {
+        self.receiver.clone()
+    }
+
+    fn take_lost_events(&self) -> usize {
+        self.sender.lost().swap(0, Ordering::SeqCst)
     }
 }
 
+/// Single snapshot of both families' forwarding tables
+fn read_route_table() -> io::Result<Vec<Route>> {
+    let mut ptable: PMIB_IPFORWARD_TABLE2 = std::ptr::null_mut();
+
+    let ret = unsafe { GetIpForwardTable2(AF_UNSPEC as u16, &mut ptable) };
+    if ret != 0 {
+        return Err(code_to_error(ret, "Error getting table"));
+    }
+
+    let rows = unsafe {
+        std::slice::from_raw_parts(
+            &(*ptable).Table as *const MIB_IPFORWARD_ROW2,
+            (*ptable).NumEntries as usize,
+        )
+    };
+
+    // Converting full internet tables dominates refresh time, rows are independent
+    #[cfg(feature = "rayon")]
+    let res = {
+        use rayon::prelude::*;
+        rows.par_iter().map(Route::from).collect()
+    };
+    #[cfg(not(feature = "rayon"))]
+    let res = rows.iter().map(Route::from).collect();
+    unsafe { FreeMibTable(ptable as *mut _) };
+    Ok(res)
+}
+
 impl Drop for WindowsOperator {
     fn drop(&mut self) {
-        if let Some(handle) = self.notify_handle {
+        if let Some(handle) = self.notify_handle.get_mut().ok().and_then(|h| h.take()) {
+            unsafe {
+                CancelMibChangeNotify2(handle);
+            }
+        }
+        if let Some(handle) = self.connection_handle.get_mut().ok().and_then(|h| h.take()) {
             unsafe {
                 CancelMibChangeNotify2(handle);
             }
@@ -173,7 +687,8 @@ impl From<&MIB_IPFORWARD_ROW2> for Route {
         let mut route = Route::new(dst, dst_len)
             .ifindex((*row).InterfaceIndex)
             .luid(unsafe { std::mem::transmute((*row).InterfaceLuid) })
-            .metric((*row).Metric);
+            .metric((*row).Metric)
+            .protocol((*row).Protocol);
 
         route.gateway = gateway;
         route
@@ -184,50 +699,62 @@ impl From<&Route> for MIB_IPFORWARD_ROW2 {
     fn from(route: &Route) -> Self {
         let mut row: MIB_IPFORWARD_ROW2 = unsafe { std::mem::zeroed() };
         unsafe { InitializeIpForwardEntry(&mut row) };
+        fill_row(row, route)
+    }
+}
 
-        if let Some(ifindex) = route.ifindex {
-            row.InterfaceIndex = ifindex;
-        }
+/// Set route fields on an initialized row
+fn fill_row(mut row: MIB_IPFORWARD_ROW2, route: &Route) -> MIB_IPFORWARD_ROW2 {
+    if let Some(ifindex) = route.ifindex {
+        row.InterfaceIndex = ifindex;
+    }
 
-        if let Some(luid) = route.luid {
-            row.InterfaceLuid = unsafe { std::mem::transmute(luid) };
-        }
+    if let Some(luid) = route.luid {
+        row.InterfaceLuid.Value = luid;
+    }
 
-        match route.gateway {
-            IpAddr::V4(addr) => unsafe {
-                *row.NextHop.si_family_mut() = AF_INET as u16;
-                row.NextHop.Ipv4_mut().sin_addr = std::mem::transmute(addr.octets());
-            },
-            IpAddr::V6(addr) => unsafe {
-                *row.NextHop.si_family_mut() = AF_INET as u16;
-                row.NextHop.Ipv6_mut().sin6_addr = std::mem::transmute(addr.octets());
-            },
-        }
+    match route.gateway {
+        IpAddr::V4(addr) => unsafe {
+            *row.NextHop.si_family_mut() = AF_INET as u16;
+            *row.NextHop.Ipv4_mut().sin_addr.S_un.S_addr_mut() = u32::from_ne_bytes(addr.octets());
+        },
+        IpAddr::V6(addr) => unsafe {
+            *row.NextHop.si_family_mut() = AF_INET6 as u16;
+            *row.NextHop.Ipv6_mut().sin6_addr.u.Byte_mut() = addr.octets();
+        },
+    }
 
-        row.DestinationPrefix.PrefixLength = route.prefix;
-        match route.destination {
-            IpAddr::V4(addr) => unsafe {
-                *row.DestinationPrefix.Prefix.si_family_mut() = AF_INET as u16;
-                row.DestinationPrefix.Prefix.Ipv4_mut().sin_addr =
-                    std::mem::transmute(addr.octets());
-            },
-            IpAddr::V6(addr) => unsafe {
-                *row.DestinationPrefix.Prefix.si_family_mut() = AF_INET6 as u16;
-                row.DestinationPrefix.Prefix.Ipv6_mut().sin6_addr =
-                    std::mem::transmute(addr.octets());
-            },
-        }
+    row.DestinationPrefix.PrefixLength = route.prefix;
+    match route.destination {
+        IpAddr::V4(addr) => unsafe {
+            *row.DestinationPrefix.Prefix.si_family_mut() = AF_INET as u16;
+            *row.DestinationPrefix
+                .Prefix
+                .Ipv4_mut()
+                .sin_addr
+                .S_un
+                .S_addr_mut() = u32::from_ne_bytes(addr.octets());
+        },
+        IpAddr::V6(addr) => unsafe {
+            *row.DestinationPrefix.Prefix.si_family_mut() = AF_INET6 as u16;
+            *row.DestinationPrefix
+                .Prefix
+                .Ipv6_mut()
+                .sin6_addr
+                .u
+                .Byte_mut() = addr.octets();
+        },
+    }
 
-        if let Some(metric) = route.metric {
-            row.Metric = metric;
-        } else {
-            row.Metric = 0;
-        }
+    if let Some(metric) = route.metric {
+        row.Metric = metric;
+    } else {
+        row.Metric = 0;
+    }
 
-        row.Protocol = MIB_IPPROTO_NETMGMT;
+    row.Protocol = MIB_IPPROTO_NETMGMT;
 
-        row
-    }
+    row
 }
 
 unsafe extern "system" fn callback(
@@ -236,15 +763,133 @@ unsafe extern "system" fn callback(
     notification_type: MIB_NOTIFICATION_TYPE,
 ) {
     // let tx = &*(callercontext as *const broadcast::Sender<RouteChange>);
-    let route = Route::from(&*row);
-    let sender: &Sender<RouteEvent> = std::mem::transmute(callercontext);
+    let timestamp = Timestamp::now();
+    let sender = &*(callercontext as *const PolicySender<TimedEvent>);
+    // row is null for the initial notification
     let event = match notification_type {
-        n if n == MibParameterNotification => RouteEvent::Change(route),
-        n if n == MibAddInstance => RouteEvent::Add(route),
-        n if n == MibDeleteInstance => RouteEvent::Delete(route),
+        n if n == MibInitialNotification => RouteEvent::Initial,
+        _ if row.is_null() => return,
+        n if n == MibParameterNotification => RouteEvent::Change(Route::from(&*row)),
+        n if n == MibAddInstance => RouteEvent::Add(Route::from(&*row)),
+        n if n == MibDeleteInstance => RouteEvent::Delete(Route::from(&*row)),
         _ => return,
     };
-    sender.send(event).unwrap();
+    let _ = sender.send(TimedEvent { event, timestamp });
+}
+
+unsafe extern "system" fn interface_callback(
+    callercontext: PVOID,
+    row: PMIB_IPINTERFACE_ROW,
+    _notification_type: MIB_NOTIFICATION_TYPE,
+) {
+    if row.is_null() {
+        return;
+    }
+    let watch = &*(callercontext as *const ConnectionWatch);
+    // Deletion of one address family leaves the adapter up, so its state is read back
+    watch.update((*row).InterfaceIndex);
+}
+
+/// ```StableWait``` states, callback moves pending to fired then done, waiter giving up
+/// moves pending to cancelled
+const STABLE_PENDING: u8 = 0;
+const STABLE_FIRED: u8 = 1;
+const STABLE_DONE: u8 = 2;
+const STABLE_CANCELLED: u8 = 3;
+
+/// Context of ```wait_stable_addresses``` notification
+struct StableWait {
+    sender: Sender<()>,
+    /// Done is stored by callback as its last access to the context
+    state: AtomicU8,
+}
+
+unsafe extern "system" fn stable_callback(
+    callercontext: PVOID,
+    table: PMIB_UNICASTIPADDRESS_TABLE,
+) {
+    if !table.is_null() {
+        FreeMibTable(table as *mut _);
+    }
+    let wait = &*(callercontext as *const StableWait);
+    if wait
+        .state
+        .compare_exchange(
+            STABLE_PENDING,
+            STABLE_FIRED,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        )
+        .is_err()
+    {
+        // waiter gave up and is cancelling the notification
+        return;
+    }
+    let _ = wait.sender.try_send(());
+    wait.state.store(STABLE_DONE, Ordering::Release);
+}
+
+/// Dial-up, RAS or VPN adapter described by row, `None` for other adapters or when down
+fn adapter_from_row(row: &MIB_IF_ROW2) -> Option<Adapter> {
+    let kind = match row.Type {
+        IF_TYPE_PPP => AdapterKind::Ppp,
+        IF_TYPE_TUNNEL => AdapterKind::Tunnel,
+        IF_TYPE_PROP_VIRTUAL => AdapterKind::Virtual,
+        _ => return None,
+    };
+    if row.OperStatus != IfOperStatusUp {
+        return None;
+    }
+    Some(Adapter {
+        ifindex: row.InterfaceIndex,
+        luid: row.InterfaceLuid.Value,
+        alias: wide_to_string(&row.Alias),
+        kind,
+    })
+}
+
+fn read_adapter(ifindex: u32) -> Option<Adapter> {
+    let mut row: MIB_IF_ROW2 = unsafe { std::mem::zeroed() };
+    row.InterfaceIndex = ifindex;
+    if unsafe { GetIfEntry2(&mut row) } != 0 {
+        return None;
+    }
+    adapter_from_row(&row)
+}
+
+fn read_adapters() -> io::Result<Vec<Adapter>> {
+    let mut ptable: PMIB_IF_TABLE2 = std::ptr::null_mut();
+
+    let ret = unsafe { GetIfTable2(&mut ptable) };
+    if ret != 0 {
+        return Err(code_to_error(ret, "Error getting interface table"));
+    }
+
+    let rows = unsafe {
+        std::slice::from_raw_parts(
+            &(*ptable).Table as *const MIB_IF_ROW2,
+            (*ptable).NumEntries as usize,
+        )
+    };
+    let res = rows.iter().filter_map(adapter_from_row).collect();
+    unsafe { FreeMibTable(ptable as *mut _) };
+    Ok(res)
+}
+
+fn read_interface_row(family: Family, ifindex: u32) -> io::Result<MIB_IPINTERFACE_ROW> {
+    let mut row: MIB_IPINTERFACE_ROW = unsafe { std::mem::zeroed() };
+    unsafe { InitializeIpInterfaceEntry(&mut row) };
+    row.Family = match family {
+        Family::V4 => AF_INET as u16,
+        Family::V6 => AF_INET6 as u16,
+    };
+    row.InterfaceIndex = ifindex;
+
+    let err = unsafe { GetIpInterfaceEntry(&mut row) };
+    if err != 0 {
+        return Err(code_to_error(err, "error reading interface"));
+    }
+    Ok(row)
 }
 
 fn code_to_error(code: u32, msg: &str) -> io::Error {
@@ -259,6 +904,69 @@ fn code_to_error(code: u32, msg: &str) -> io::Error {
     io::Error::new(kind, format!("{}: {}", msg, kind.to_string()))
 }
 
+pub(crate) fn read_interfaces() -> io::Result<Vec<InterfaceInfo>> {
+    let mut ptable: PMIB_IF_TABLE2 = std::ptr::null_mut();
+
+    let ret = unsafe { GetIfTable2(&mut ptable) };
+    if ret != 0 {
+        return Err(code_to_error(ret, "Error getting interface table"));
+    }
+
+    let rows = unsafe {
+        std::slice::from_raw_parts(
+            &(*ptable).Table as *const MIB_IF_ROW2,
+            (*ptable).NumEntries as usize,
+        )
+    };
+    let res = rows
+        .iter()
+        .map(|row| InterfaceInfo {
+            ifindex: row.InterfaceIndex,
+            luid: row.InterfaceLuid.Value,
+            alias: wide_to_string(&row.Alias),
+            guid: guid_to_string(&row.InterfaceGuid),
+        })
+        .collect();
+    unsafe { FreeMibTable(ptable as *mut _) };
+    Ok(res)
+}
+
+fn sockaddr_to_ip(addr: &SOCKADDR_INET) -> Option<IpAddr> {
+    unsafe {
+        match *addr.si_family() as i32 {
+            AF_INET => Some(IpAddr::from(
+                addr.Ipv4().sin_addr.S_un.S_addr().to_ne_bytes(),
+            )),
+            AF_INET6 => Some(IpAddr::from(*addr.Ipv6().sin6_addr.u.Byte())),
+            _ => None,
+        }
+    }
+}
+
+fn guid_to_string(guid: &GUID) -> String {
+    let d = guid.Data4;
+    format!(
+        "{{{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}}}",
+        guid.Data1, guid.Data2, guid.Data3, d[0], d[1], d[2], d[3], d[4], d[5], d[6], d[7]
+    )
+}
+
+fn wide_to_string(wide: &[u16]) -> String {
+    let len = wide.iter().position(|c| *c == 0).unwrap_or(wide.len());
+    String::from_utf16_lossy(&wide[..len])
+}
+
+pub(crate) fn set_current_thread_priority(priority: ThreadPriority) {
+    let value = match priority {
+        ThreadPriority::Lowest => THREAD_PRIORITY_LOWEST,
+        ThreadPriority::BelowNormal => THREAD_PRIORITY_BELOW_NORMAL,
+        ThreadPriority::Normal => THREAD_PRIORITY_NORMAL,
+        ThreadPriority::AboveNormal => THREAD_PRIORITY_ABOVE_NORMAL,
+        ThreadPriority::Highest => THREAD_PRIORITY_HIGHEST,
+    };
+    unsafe { SetThreadPriority(GetCurrentThread(), value as i32) };
+}
+
 pub fn find_best_interface(ip: IpAddr) -> io::Result<u32> {
     let mut result: u32 = 0;
     let ret = match ip {
@@ -273,12 +981,8 @@ pub fn find_best_interface(ip: IpAddr) -> io::Result<u32> {
             let mut addr: SOCKADDR_IN6 = unsafe { std::mem::zeroed() };
             addr.sin6_family = AF_INET6 as u16;
             addr.sin6_addr = unsafe { std::mem::transmute(v6.octets()) };
-            let ptr: PSOCKADDR = unsafe { std::mem::transmute(&addr) };
-            let rp = result as *mut u32;
-            unsafe {
-                (*rp) = 100;
-            }
-            unsafe { GetBestInterfaceEx(ptr, result as *mut _) }
+            let ptr: PSOCKADDR = unsafe { std::mem::transmute(&mut addr) };
+            unsafe { GetBestInterfaceEx(ptr, &mut result as *mut _) }
         }
     };
 
@@ -301,6 +1005,7 @@ pub mod test_cast {
         let row = MIB_IPFORWARD_ROW2::from(&route);
         assert_eq!(0, row.Metric);
         assert_eq!(MIB_IPPROTO_NETMGMT, row.Protocol);
+        assert_eq!(Some(MIB_IPPROTO_NETMGMT), Route::from(&row).protocol);
         assert_eq!("192.168.1.0", route.destination.to_string());
     }
 